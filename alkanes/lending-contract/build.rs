@@ -0,0 +1,34 @@
+use std::process::Command;
+
+/// Ratio bound used when the deployment does not set one
+const DEFAULT_RATIO_BOUND: u128 = 1_000_000;
+
+fn main() {
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs");
+
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=LENDING_GIT_HASH={}", git_hash);
+
+    // Collateral-to-loan ratio bounds of this deployment (see lib.rs)
+    for name in ["LENDING_MAX_LOAN_PER_COLLATERAL", "LENDING_MAX_COLLATERAL_PER_LOAN"] {
+        println!("cargo:rerun-if-env-changed={}", name);
+        let bound = match std::env::var(name) {
+            Ok(value) => value
+                .trim()
+                .parse::<u128>()
+                .ok()
+                .filter(|bound| *bound != 0)
+                .unwrap_or_else(|| panic!("{} must be a positive integer", name)),
+            Err(_) => DEFAULT_RATIO_BOUND,
+        };
+        println!("cargo:rustc-env={}={}", name, bound);
+    }
+}
@@ -1,6 +1,9 @@
 mod math;
 
-use alkanes_runtime::{auth::AuthenticatedResponder, declare_alkane, message::MessageDispatch, runtime::AlkaneResponder};
+use math::precision::InterestRate;
+use math::repayment::{RepaymentQuote, BPS_PRECISION};
+
+use alkanes_runtime::{auth::AuthenticatedResponder, declare_alkane, message::MessageDispatch, runtime::AlkaneResponder, storage::StoragePointer};
 
 #[allow(unused_imports)]
 use alkanes_runtime::{
@@ -10,13 +13,17 @@ use alkanes_runtime::{
 use alkanes_macros::storage_variable;
 use alkanes_std_factory_support::MintableToken;
 use alkanes_support::{
+    cellpack::Cellpack,
+    constants::AUTH_TOKEN_FACTORY_ID,
     id::AlkaneId,
-    parcel::AlkaneTransfer,
+    parcel::{AlkaneTransfer, AlkaneTransferParcel},
     response::CallResponse,
 };
 use anyhow::{anyhow, Result};
+use bitcoin::hashes::{sha256, Hash};
 use metashrew_support::compat::to_arraybuffer_layout;
 use metashrew_support::index_pointer::KeyValuePointer;
+use std::sync::Arc;
 
 
 /// Lending contract states (Case 2 only: creditor offers loan)
@@ -31,14 +38,256 @@ const STATE_LOAN_ACTIVE: u128 = 2;
 const STATE_LOAN_REPAID: u128 = 3;
 const STATE_LOAN_DEFAULTED: u128 = 4;
 
-/// APR precision: 4 decimal places (e.g., 1000 = 10.00%, 500 = 5.00%)
+/// Default APR precision: 4 decimal places (e.g., 1000 = 10.00%, 500 = 5.00%)
 const APR_PRECISION: u128 = 10000;
 
+/// Finest APR precision an offer may choose: 10 decimal places of a percent
+const MAX_APR_PRECISION: u128 = 1_000_000_000_000;
+
 /// Blocks per year approximation (assuming ~10 min blocks)
 /// 6 blocks/hour * 24 hours * 365 days = 52560 blocks/year
 const BLOCKS_PER_YEAR: u128 = 52560;
 
+/// Collateral-to-loan ratio bounds (raw token units), enforced at init to
+/// reject offers with absurdly low or absurdly high collateral demands.
+/// loan_amount may be at most MAX_LOAN_PER_COLLATERAL times collateral_amount,
+/// collateral_amount may be at most MAX_COLLATERAL_PER_LOAN times loan_amount.
+/// Each deployment sets them when building the contract through the
+/// LENDING_MAX_LOAN_PER_COLLATERAL and LENDING_MAX_COLLATERAL_PER_LOAN
+/// environment variables (default 1e6, see build.rs), to fit the decimals of
+/// the tokens it lends; clones of a template share its bounds.
+const MAX_LOAN_PER_COLLATERAL: u128 = parse_u128(env!("LENDING_MAX_LOAN_PER_COLLATERAL"));
+const MAX_COLLATERAL_PER_LOAN: u128 = parse_u128(env!("LENDING_MAX_COLLATERAL_PER_LOAN"));
+
+/// Parse a decimal u128 at compile time; build.rs has already validated it
+const fn parse_u128(digits: &str) -> u128 {
+    let bytes = digits.as_bytes();
+    let mut value: u128 = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        value = value * 10 + (bytes[index] - b'0') as u128;
+        index += 1;
+    }
+    value
+}
+
+/// Loan option flags (bitfield passed to InitWithLoanOffer)
+/// FLAG_EARLY_REPAYMENT_REBATE: interest accrues per block, so repaying early
+/// only costs the interest accrued to date plus a break fee on the rebate
+/// FLAG_ZERO_COUPON_BOND: the creditor receives transferable bond alkanes, one
+/// per loan token due at maturity, instead of claiming through the auth token
+/// FLAG_OPEN_TERM: no deadline until the creditor calls the loan; duration_blocks
+/// is then the notice period before default rules apply
+/// FLAG_MINIMUM_BLOCK_INTEREST: repaying in the block interest started accruing
+/// (take or last capitalization) costs one block of interest instead of none;
+/// needs per-block accrual (rebate or open-term)
+/// FLAG_ABSOLUTE_DEADLINE: duration_blocks is the block height the loan must be
+/// repaid by; the duration is whatever is left of it when the loan is taken
+/// FLAG_PER_BLOCK_RATE: desired_apr is a rate per block scaled by 1e18
+/// (1e18 = 100% per block) instead of an APR, so no BLOCKS_PER_YEAR applies
+const FLAG_EARLY_REPAYMENT_REBATE: u128 = 1 << 0;
+const FLAG_ZERO_COUPON_BOND: u128 = 1 << 1;
+const FLAG_OPEN_TERM: u128 = 1 << 2;
+const FLAG_MINIMUM_BLOCK_INTEREST: u128 = 1 << 3;
+const FLAG_ABSOLUTE_DEADLINE: u128 = 1 << 4;
+const FLAG_PER_BLOCK_RATE: u128 = 1 << 5;
+const SUPPORTED_LOAN_FLAGS: u128 = FLAG_EARLY_REPAYMENT_REBATE
+    | FLAG_ZERO_COUPON_BOND
+    | FLAG_OPEN_TERM
+    | FLAG_MINIMUM_BLOCK_INTEREST
+    | FLAG_ABSOLUTE_DEADLINE
+    | FLAG_PER_BLOCK_RATE;
+
+/// Repayment deadline of an open-term loan that has not been called
+const NO_DEADLINE: u128 = u128::MAX;
+
+/// GetTotalSupply opcode of the std token templates, queried at init
+const TOKEN_TOTAL_SUPPLY_OPCODE: u128 = 101;
+
+/// AlkaneId blocks that are cellpack targets for creating or cloning an
+/// alkane (1, 3, 5, 6) rather than the id of a deployed token
+const RESERVED_ID_BLOCKS: [u128; 4] = [1, 3, 5, 6];
+
+/// Allowed taker of an offer anyone may take
+const PUBLIC_OFFER: AlkaneId = AlkaneId { block: 0, tx: 0 };
+
+/// Semver plus build metadata reported by GetVersion
+const CONTRACT_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("LENDING_GIT_HASH"));
+
+/// History log event codes (see GetHistory)
+const EVENT_OFFER_CREATED: u128 = 0;
+const EVENT_LOAN_TAKEN: u128 = 1;
+const EVENT_LOAN_REPAID: u128 = 2;
+const EVENT_REPAYMENT_CLAIMED: u128 = 3;
+const EVENT_COLLATERAL_CLAIMED: u128 = 4;
+const EVENT_OFFER_CANCELLED: u128 = 5;
+const EVENT_INTEREST_CAPITALIZED: u128 = 6;
+const EVENT_BONDS_REDEEMED: u128 = 7;
+const EVENT_LOAN_CALLED: u128 = 8;
+const EVENT_DEFAULT_NOTICE: u128 = 9;
+const EVENT_OFFER_REFRESHED: u128 = 10;
+const EVENT_OFFER_RESERVED: u128 = 11;
+const EVENT_RESERVATION_FORFEITED: u128 = 12;
+const EVENT_RESERVATION_RELEASED: u128 = 13;
+
+/// Size of one encoded history entry: block, event code, amount (3 × u128)
+const HISTORY_ENTRY_SIZE: usize = 48;
+
+/// Size of one encoded transfer entry: block, event code, sender role,
+/// recipient role, token block, token tx, amount (7 × u128)
+const TRANSFER_ENTRY_SIZE: usize = 112;
+
+/// Size of one encoded accrual checkpoint: block, principal, interest for
+/// the period, cumulative interest (4 × u128)
+const ACCRUAL_ENTRY_SIZE: usize = 64;
+
+/// Storage keywords of the append-only logs (see GetHistory, GetTransfers,
+/// GetAccrualHistory)
+const HISTORY_LOG: &str = "/history";
+const TRANSFER_LOG: &str = "/transfers";
+const ACCRUAL_LOG: &str = "/accruals";
+
+/// Repeatable calls (RefreshOffer, ReserveOffer, CapitalizeInterest) revert
+/// once the history log holds this many entries. Every other call appends a
+/// fixed number of entries at most once per loan, so the logs stay bounded
+/// and so does each scan over them (GetRealizedYield, GetStateRoot)
+const HISTORY_LOG_LIMIT: u128 = 64;
+
+/// Largest response a log page may encode. A page that would exceed it ends
+/// early with LOG_PAGE_TRUNCATED set, and the caller continues from the
+/// next offset
+const MAX_LOG_PAGE_SIZE: usize = 1024;
+/// Log page header: total entry count and page flags (2 × u128)
+const LOG_PAGE_HEADER_SIZE: usize = 32;
+const LOG_PAGE_TRUNCATED: u128 = 1 << 0;
+
+/// Storage keywords of the counterparty disclosure pointers (see
+/// GetDisclosures); each holds txid high, txid low, index (3 × u128)
+const CREDITOR_DISCLOSURE: &str = "/disclosure/creditor";
+const DEBITOR_DISCLOSURE: &str = "/disclosure/debitor";
+const DISCLOSURE_SIZE: usize = 48;
+
+/// Loan outcomes reported by GetRealizedYield
+const OUTCOME_OPEN: u128 = 0;
+const OUTCOME_REPAID: u128 = 1;
+const OUTCOME_DEFAULTED: u128 = 2;
+
+/// Status word layout (see GetStatusWord)
+/// Bits 0-7: contract state
+/// Bit 8: overdue - loan active and repayment deadline passed
+/// Bit 9: grace - overdue but not yet in default, so repayment is still
+/// accepted (grace period, notice period, or notice not yet given)
+/// Bit 10: paused - reserved, always 0 (no pause switch yet)
+/// Bit 11: claimable - a settlement payout is waiting to be collected
+const STATUS_STATE_MASK: u128 = 0xff;
+const STATUS_OVERDUE: u128 = 1 << 8;
+const STATUS_GRACE: u128 = 1 << 9;
+const STATUS_CLAIMABLE: u128 = 1 << 11;
+
+/// Roles that may call an opcode (see GetPermissions)
+/// ROLE_ANYONE: no token required
+/// ROLE_DEBITOR: debitor note must be sent with the call
+/// ROLE_CREDITOR: creditor auth token must be sent with the call
+const ROLE_ANYONE: u128 = 0;
+const ROLE_DEBITOR: u128 = 1;
+const ROLE_CREDITOR: u128 = 2;
+
+/// Recipient role of bond redemption payouts in the transfer log; bonds are
+/// redeemable by whoever holds them, so no opcode requires this role
+const ROLE_BOND_HOLDER: u128 = 3;
+
+/// Sender role of tokens the contract mints itself (notes, bonds) in the
+/// transfer log, as opposed to tokens paid out of a party's escrow
+const ROLE_CONTRACT: u128 = 4;
+
+/// State bitmasks used by the access table
+const IN_UNINITIALIZED: u128 = 1 << STATE_UNINITIALIZED;
+const IN_WAITING: u128 = 1 << STATE_WAITING_FOR_DEBITOR_TAKE;
+const IN_ACTIVE: u128 = 1 << STATE_LOAN_ACTIVE;
+const IN_REPAID: u128 = 1 << STATE_LOAN_REPAID;
+const IN_DEFAULTED: u128 = 1 << STATE_LOAN_DEFAULTED;
+const IN_ANY_STATE: u128 = IN_UNINITIALIZED | IN_WAITING | IN_ACTIVE | IN_REPAID | IN_DEFAULTED;
+
+/// Access rule for one opcode, enforced by `authorize` before a handler runs
+/// and reported by GetPermissions. Time-based conditions such as the
+/// repayment deadline are checked by the handlers themselves.
+struct OpcodePermission {
+    opcode: u128,
+    role: u128,
+    states: u128,
+    state_error: &'static str,
+}
+
+const fn permission(
+    opcode: u128,
+    role: u128,
+    states: u128,
+    state_error: &'static str,
+) -> OpcodePermission {
+    OpcodePermission {
+        opcode,
+        role,
+        states,
+        state_error,
+    }
+}
+
+/// Access table: every opcode must have an entry
+const OPCODE_PERMISSIONS: &[OpcodePermission] = &[
+    permission(0, ROLE_ANYONE, IN_UNINITIALIZED, "Contract already initialized"),
+    permission(1, ROLE_ANYONE, IN_WAITING, "Loan offer is not available"),
+    permission(2, ROLE_ANYONE, IN_ACTIVE, "No active loan to repay"),
+    permission(3, ROLE_CREDITOR, IN_ACTIVE, "No active loan to claim"),
+    permission(4, ROLE_CREDITOR, IN_WAITING, "Cannot cancel - loan offer not in cancellable state"),
+    permission(5, ROLE_CREDITOR, IN_REPAID, "Loan must be repaid to claim"),
+    permission(6, ROLE_CREDITOR, IN_ACTIVE, "No active loan to capitalize"),
+    permission(7, ROLE_DEBITOR, IN_ACTIVE, "No active loan to capitalize"),
+    permission(8, ROLE_ANYONE, IN_ACTIVE | IN_REPAID | IN_DEFAULTED, "Bonds are not redeemable yet"),
+    permission(9, ROLE_CREDITOR, IN_ACTIVE, "No active loan to call"),
+    permission(10, ROLE_CREDITOR, IN_ACTIVE, "No active loan to give notice on"),
+    permission(11, ROLE_CREDITOR, IN_ANY_STATE & !IN_UNINITIALIZED, "No position to label"),
+    permission(12, ROLE_DEBITOR, IN_ACTIVE | IN_REPAID | IN_DEFAULTED, "No position to label"),
+    permission(13, ROLE_CREDITOR, IN_WAITING, "No loan offer to refresh"),
+    permission(14, ROLE_CREDITOR, IN_ANY_STATE & !IN_UNINITIALIZED, "No position to disclose"),
+    permission(15, ROLE_DEBITOR, IN_ACTIVE | IN_REPAID | IN_DEFAULTED, "No position to disclose"),
+    permission(16, ROLE_ANYONE, IN_WAITING, "Loan offer is not available"),
+    permission(17, ROLE_ANYONE, IN_UNINITIALIZED, "Contract already initialized"),
+    permission(50, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(90, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(91, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(92, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(93, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(94, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(95, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(96, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(97, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(98, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(99, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(100, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(101, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(102, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(103, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(104, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(105, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(106, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(107, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(108, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(109, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(110, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(111, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(112, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(113, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(114, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(115, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(116, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(117, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(118, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(119, ROLE_ANYONE, IN_ANY_STATE, ""),
+    permission(120, ROLE_ANYONE, IN_ANY_STATE, ""),
+];
+
 #[derive(MessageDispatch)]
+#[allow(clippy::large_enum_variant)]
 pub enum LendingContractMessage {
     /// Creditor creates loan offer by depositing loan tokens (Case 2)
     /// Expects loan tokens to be sent with this call
@@ -48,8 +297,20 @@ pub enum LendingContractMessage {
         collateral_amount: u128,
         loan_token: AlkaneId,
         loan_amount: u128,
-        duration_blocks: u128,
-        desired_apr: u128, // with 4 decimal places of precision
+        duration_blocks: u128, // must be non-zero (notice period for open-term loans, deadline height with FLAG_ABSOLUTE_DEADLINE)
+        desired_apr: u128, // scaled by apr_precision (per block, 1e18 scale, with FLAG_PER_BLOCK_RATE), 0 = interest-free
+        loan_flags: u128, // bitfield of FLAG_* loan options
+        break_fee_bps: u128, // fee on rebated interest, requires FLAG_EARLY_REPAYMENT_REBATE
+        prepayment_penalty_bps: u128, // bps of remaining interest charged during lockout, requires FLAG_EARLY_REPAYMENT_REBATE
+        prepayment_lockout_blocks: u128, // blocks after take during which the penalty applies
+        default_notice_blocks: u128, // notice required before claiming collateral (0 = none)
+        max_take_delay_blocks: u128, // blocks after init or refresh the offer stays takeable (0 = no limit)
+        grace_blocks: u128, // blocks after the deadline repayment is still accepted (hard deadline = deadline + grace)
+        late_penalty_bps: u128, // bps of principal charged for repaying during the grace period
+        reservation_deposit: u128, // collateral locked by ReserveOffer (0 = no reservations)
+        reservation_blocks: u128, // blocks a reservation holds the offer
+        allowed_taker: AlkaneId, // alkane that must accompany the take (0:0 = public offer)
+        apr_precision: u128, // value of a 100% APR: a power of ten from 1e4 to 1e12 (0 = default 1e4)
     },
 
     /// Debitor takes loan by sending collateral
@@ -78,33 +339,267 @@ pub enum LendingContractMessage {
     #[opcode(5)]
     ClaimRepayment,
 
+    /// Creditor consents to capitalizing accrued interest, extending the
+    /// deadline by `extension_blocks` once the debitor executes it.
+    /// Passing 0 revokes a pending approval.
+    #[opcode(6)]
+    ApproveCapitalization { extension_blocks: u128 },
+
+    /// Debitor rolls accrued interest into principal under the creditor's
+    /// pending approval
+    /// Expects the debitor note to be sent with this call
+    #[opcode(7)]
+    CapitalizeInterest,
+
+    /// Bond holder redeems bonds (bond mode only): 1:1 for loan tokens once
+    /// repaid, or pro-rata for collateral once the loan has defaulted
+    /// Expects bond tokens to be sent with this call
+    #[opcode(8)]
+    RedeemBonds,
+
+    /// Creditor calls an open-term loan, starting the notice period after
+    /// which the loan defaults if not repaid
+    #[opcode(9)]
+    CallLoan,
+
+    /// Creditor gives notice of default once the deadline has passed; the
+    /// collateral becomes claimable after the configured notice period
+    #[opcode(10)]
+    NoticeOfDefault,
+
+    /// Creditor attaches a label to their side of the position (0 clears it)
+    #[opcode(11)]
+    SetCreditorLabel { label: u128 },
+
+    /// Debitor attaches a label to their side of the position (0 clears it)
+    /// Expects the debitor note to be sent with this call
+    #[opcode(12)]
+    SetDebitorLabel { label: u128 },
+
+    /// Creditor confirms a loan offer is still current, restarting its
+    /// max_take_delay_blocks window
+    #[opcode(13)]
+    RefreshOffer,
+
+    /// Creditor records a pointer to an identity attestation, e.g. the
+    /// inscription id txid:index with the txid split into two halves
+    /// (all zero clears it)
+    #[opcode(14)]
+    SetCreditorDisclosure { txid_high: u128, txid_low: u128, index: u128 },
+
+    /// Debitor records a pointer to an identity attestation (all zero clears it)
+    #[opcode(15)]
+    SetDebitorDisclosure { txid_high: u128, txid_low: u128, index: u128 },
+
+    /// Would-be debitor locks the reservation deposit (in collateral tokens)
+    /// to hold the offer for reservation_blocks; returns a reservation note
+    /// that must be sent with the take. The deposit is refunded on take (or
+    /// on a take refused because the offer went stale) and forfeited to the
+    /// creditor once the reservation expires. The offer must stay takeable
+    /// for the whole reservation.
+    #[opcode(16)]
+    ReserveOffer,
+
+    /// Creates the offer and takes it in one call, for settlements where
+    /// creditor and debitor fund the same transaction: expects the loan
+    /// tokens and the collateral. Everything both sides receive (creditor
+    /// note, loan tokens, debitor note) goes to the call's output, to be
+    /// split by the transaction's edicts.
+    #[opcode(17)]
+    InitAndTake {
+        collateral_token: AlkaneId,
+        collateral_amount: u128,
+        loan_token: AlkaneId,
+        loan_amount: u128,
+        duration_blocks: u128,
+        desired_apr: u128,
+        loan_flags: u128,
+        break_fee_bps: u128,
+        prepayment_penalty_bps: u128,
+        prepayment_lockout_blocks: u128,
+        default_notice_blocks: u128,
+        max_take_delay_blocks: u128,
+        grace_blocks: u128,
+        late_penalty_bps: u128,
+        reservation_deposit: u128,
+        reservation_blocks: u128,
+        allowed_taker: AlkaneId,
+        apr_precision: u128,
+    },
+
     /// Forward incoming tokens (utility)
     #[opcode(50)]
     ForwardIncoming,
 
     /// Get loan details
     #[opcode(90)]
+    #[returns(Vec<u8>)]
     GetLoanDetails,
 
     /// Get current repayment amount (principal + accrued interest)
     #[opcode(91)]
+    #[returns(u128)]
     GetRepaymentAmount,
 
     /// Get contract state
     #[opcode(92)]
+    #[returns(u128)]
     GetState,
 
     /// Get time remaining until deadline (in blocks)
     #[opcode(93)]
+    #[returns(u128)]
     GetTimeRemaining,
 
+    /// Get collateral price (scaled by 1e18) at which the loan becomes liquidatable
+    #[opcode(94)]
+    #[returns(u128)]
+    GetLiquidationPrice,
+
+    /// Get repayment quote breakdown (amount due, principal, interest, break fee,
+    /// prepayment penalty, late penalty)
+    #[opcode(95)]
+    #[returns(Vec<u8>)]
+    GetRepaymentQuote,
+
+    /// Get loan history entries (block, event, amount) starting at `offset`
+    #[opcode(96)]
+    #[returns(Vec<u8>)]
+    GetHistory { offset: u128, limit: u128 },
+
+    /// Get the debitor note minted when the loan was taken
+    #[opcode(97)]
+    #[returns(AlkaneId)]
+    GetDebitorNote,
+
+    /// Get the bond token and total bond supply (bond mode only)
+    #[opcode(98)]
+    #[returns(Vec<u8>)]
+    GetBondToken,
+
     /// Get contract name
     #[opcode(99)]
+    #[returns(String)]
     GetName,
 
     /// Get contract symbol
     #[opcode(100)]
+    #[returns(String)]
     GetSymbol,
+
+    /// Get the access table: required role per opcode and whether it is
+    /// callable in the current state
+    #[opcode(101)]
+    #[returns(Vec<u8>)]
+    GetPermissions,
+
+    /// Get the number of creditor auth tokens still in circulation
+    #[opcode(102)]
+    #[returns(u128)]
+    GetCreditorNoteSupply,
+
+    /// Get a compact status bitfield (state plus STATUS_* flags)
+    #[opcode(103)]
+    #[returns(u128)]
+    GetStatusWord,
+
+    /// Get default notice details (notice period, notice block, claimable after)
+    #[opcode(104)]
+    #[returns(Vec<u8>)]
+    GetDefaultNotice,
+
+    /// Get the collateral and loan token supplies cached at init
+    #[opcode(105)]
+    #[returns(Vec<u8>)]
+    GetTokenSupplies,
+
+    /// Get the contract ABI: every opcode with its named inputs and return
+    /// type, as generated by the MessageDispatch derive
+    #[opcode(106)]
+    #[returns(Vec<u8>)]
+    GetAbi,
+
+    /// Get the contract version as semver with the git hash as build
+    /// metadata, e.g. "0.1.0+1a2b3c4d5e6f"
+    #[opcode(107)]
+    #[returns(String)]
+    GetVersion,
+
+    /// Get deadline, current height, blocks remaining, whether the loan
+    /// has defaulted and the hard deadline
+    #[opcode(108)]
+    #[returns(Vec<u8>)]
+    GetBlocksUntilDeadline,
+
+    /// Get the creditor and debitor position labels
+    #[opcode(109)]
+    #[returns(Vec<u8>)]
+    GetLabels,
+
+    /// Get transfer log entries (block, event, sender role, recipient role,
+    /// token, amount) starting at `offset`
+    #[opcode(110)]
+    #[returns(Vec<u8>)]
+    GetTransfers { offset: u128, limit: u128 },
+
+    /// Get interest accrual checkpoints (block, principal, interest,
+    /// cumulative interest) starting at `offset`
+    #[opcode(111)]
+    #[returns(Vec<u8>)]
+    GetAccrualHistory { offset: u128, limit: u128 },
+
+    /// Get the interest rate: per-block flag, rate as quoted, equivalent APR,
+    /// equivalent per-block rate, APR precision
+    #[opcode(112)]
+    #[returns(Vec<u8>)]
+    GetInterestRate,
+
+    /// Get the creditor's realized return once the loan has closed: outcome,
+    /// interest received, blocks held, annualized yield
+    #[opcode(113)]
+    #[returns(Vec<u8>)]
+    GetRealizedYield,
+
+    /// Get the creditor and debitor disclosure pointers
+    #[opcode(114)]
+    #[returns(Vec<u8>)]
+    GetDisclosures,
+
+    /// Get the reservation: note id, reserved-until block, held deposit and
+    /// forfeited deposits owed to the creditor
+    #[opcode(115)]
+    #[returns(Vec<u8>)]
+    GetReservation,
+
+    /// Get the offer visibility: private flag and the allowed taker id
+    #[opcode(116)]
+    #[returns(Vec<u8>)]
+    GetAllowedTaker,
+
+    /// Get a hash committing to the full contract state, for indexers to
+    /// cross-check they derived identical state
+    #[opcode(117)]
+    #[returns(Vec<u8>)]
+    GetStateRoot,
+
+    /// Get the block height the contract executes at, which every deadline
+    /// check compares against
+    #[opcode(118)]
+    #[returns(u128)]
+    GetCurrentHeight,
+
+    /// Get the creditor note (auth token) minted when the offer was created
+    #[opcode(119)]
+    #[returns(Vec<u8>)]
+    GetCreditorNote,
+
+    /// Get the indicative present value of the creditor's claim at a
+    /// discount rate given as an APR with `apr_precision` (0 = 10000):
+    /// amount due at maturity, blocks to maturity, present value, present
+    /// value per 1e18 units due (a bond's price, scaled by 1e18)
+    #[opcode(120)]
+    #[returns(Vec<u8>)]
+    GetPresentValue { discount_apr: u128, apr_precision: u128 },
 }
 
 #[derive(Default)]
@@ -124,16 +619,74 @@ impl LendingContract {
     // Collateral parameters
     storage_variable!(collateral_token: AlkaneId);
     storage_variable!(collateral_amount: u128);
+    storage_variable!(collateral_token_supply: u128);
     
     // Loan parameters
     storage_variable!(loan_token: AlkaneId);
     storage_variable!(loan_amount: u128);
+    storage_variable!(loan_token_supply: u128);
     storage_variable!(duration_blocks: u128);
     storage_variable!(apr: u128);
+    storage_variable!(loan_flags: u128);
+    storage_variable!(break_fee_bps: u128);
+    storage_variable!(prepayment_penalty_bps: u128);
+    storage_variable!(prepayment_lockout_blocks: u128);
+    storage_variable!(default_notice_blocks: u128);
+    storage_variable!(max_take_delay_blocks: u128);
+    storage_variable!(grace_blocks: u128);
+    storage_variable!(late_penalty_bps: u128);
+    storage_variable!(reservation_deposit: u128);
+    storage_variable!(reservation_blocks: u128);
+    // Alkane a taker must present; 0:0 for a public offer
+    storage_variable!(allowed_taker: AlkaneId);
+    // Value of a 100% APR (0 before init)
+    storage_variable!(apr_precision: u128);
     
     // Loan timing
+    // Block the offer was created or last refreshed
+    storage_variable!(offer_refresh_block: u128);
+    // Current reservation: note that may take the offer until the block, and
+    // the deposit held for it; expired deposits accrue to the creditor
+    storage_variable!(reservation_note: AlkaneId);
+    storage_variable!(reserved_until: u128);
+    storage_variable!(held_deposit: u128);
+    storage_variable!(forfeited_deposits: u128);
     storage_variable!(loan_start_block: u128);
     storage_variable!(repayment_deadline: u128);
+    // Block interest accrues from; moves forward when interest is capitalized
+    storage_variable!(accrual_start_block: u128);
+    // Block the creditor gave notice of default at (0 = no notice given)
+    storage_variable!(default_notice_block: u128);
+
+    // Auth token identifying the debitor, minted on take
+    storage_variable!(debitor_note: AlkaneId);
+
+    // Deadline extension approved by the creditor for CapitalizeInterest (0 = none)
+    storage_variable!(capitalization_extension: u128);
+
+    // Bond mode: bond token minted to the creditor at init, its total supply
+    // and the bonds redeemed so far
+    storage_variable!(bond_token: AlkaneId);
+    storage_variable!(bond_supply: u128);
+    storage_variable!(bonds_redeemed: u128);
+
+    // Amount collected by repay_loan, paid out by claim_repayment
+    storage_variable!(repaid_amount: u128);
+
+    // Interest accrued over the life of the loan, as checkpointed in the accrual log
+    storage_variable!(total_interest_accrued: u128);
+
+    // Amount due memoized at take for loans whose repayment cannot change
+    // over time (0 = computed on every quote)
+    storage_variable!(fixed_repayment: u128);
+
+    // Creditor auth tokens in circulation; final claims consume them
+    storage_variable!(creditor_note_supply: u128);
+
+    // Free-form labels each party can attach to the position (0 = unset),
+    // named like state_value to avoid conflicting with the setter opcodes
+    storage_variable!(creditor_label_value: u128);
+    storage_variable!(debitor_label_value: u128);
 
     // ============ Helper Functions ============
 
@@ -146,35 +699,448 @@ impl LendingContract {
         Ok(context.caller.clone())
     }
 
+    /// Enforce the access table entry for the opcode being dispatched
+    ///
+    /// Checks the current state first, then the caller's role, so every
+    /// state-changing handler gets the same checks in the same order.
+    fn authorize(&self) -> Result<()> {
+        let context = self.context()?;
+        let opcode = context
+            .inputs
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("Missing opcode"))?;
+        let permission = OPCODE_PERMISSIONS
+            .iter()
+            .find(|permission| permission.opcode == opcode)
+            .ok_or_else(|| anyhow!("Opcode {} missing from access table", opcode))?;
+
+        if permission.states & (1u128 << self.state_value()) == 0 {
+            return Err(anyhow!(permission.state_error));
+        }
+
+        match permission.role {
+            ROLE_CREDITOR => self.only_owner(),
+            ROLE_DEBITOR => self.only_debitor(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Require the debitor note among incoming alkanes, the debitor-side
+    /// counterpart of `only_owner`
+    fn only_debitor(&self) -> Result<()> {
+        let context = self.context()?;
+        let debitor_note = self.debitor_note()?;
+        if !context
+            .incoming_alkanes
+            .0
+            .iter()
+            .any(|transfer| transfer.id == debitor_note && transfer.value > 0)
+        {
+            return Err(anyhow!("Debitor note is not in incoming alkanes"));
+        }
+        Ok(())
+    }
+
+    /// Query a token's total supply through its GetTotalSupply view
+    fn query_total_supply(&self, token: &AlkaneId) -> Result<u128> {
+        let cellpack = Cellpack {
+            target: token.clone(),
+            inputs: vec![TOKEN_TOTAL_SUPPLY_OPCODE],
+        };
+        let response = self
+            .staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel())
+            .map_err(|e| Self::extcall_error("Unable to query token supply", &cellpack, e))?;
+        let bytes: [u8; 16] = response
+            .data
+            .get(0..16)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!("Unable to query token supply"))?;
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    /// Prefix a failed extcall's error with what the lending contract was
+    /// doing and the cellpack it sent, e.g.
+    /// "Unable to query token supply: extcall to 2:5 opcode 101 failed: ..."
+    fn extcall_error(action: &str, cellpack: &Cellpack, error: anyhow::Error) -> anyhow::Error {
+        anyhow!(
+            "{}: extcall to {}:{} opcode {} failed: {}",
+            action,
+            cellpack.target.block,
+            cellpack.target.tx,
+            cellpack.inputs.first().copied().unwrap_or_default(),
+            error
+        )
+    }
+
+    /// Mint `units` of a fresh transferable token through the auth token factory
+    fn mint_factory_token(&self, units: u128) -> Result<AlkaneTransfer> {
+        let cellpack = Cellpack {
+            target: AlkaneId {
+                block: 6,
+                tx: AUTH_TOKEN_FACTORY_ID,
+            },
+            inputs: vec![0, units],
+        };
+        let response = self
+            .call(&cellpack, &AlkaneTransferParcel::default(), self.fuel())
+            .map_err(|e| Self::extcall_error("Unable to mint from auth token factory", &cellpack, e))?;
+        response
+            .alkanes
+            .0
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow!("Auth token factory returned no tokens"))
+    }
+
+    /// Mint a single debitor note through the auth token factory
+    fn mint_debitor_note(&self) -> Result<AlkaneTransfer> {
+        let note = self.mint_factory_token(1)?;
+        self.set_debitor_note(note.id.clone());
+        Ok(note)
+    }
+
+    fn is_bond_mode(&self) -> bool {
+        self.loan_flags() & FLAG_ZERO_COUPON_BOND != 0
+    }
+
+    fn is_open_term(&self) -> bool {
+        self.loan_flags() & FLAG_OPEN_TERM != 0
+    }
+
+    /// Interest rate represented as given by `loan_flags`, an APR scaled by
+    /// `apr_precision`
+    fn interest_rate_for(loan_flags: u128, rate: u128, apr_precision: u128) -> InterestRate {
+        if loan_flags & FLAG_PER_BLOCK_RATE != 0 {
+            InterestRate::PerBlock(rate)
+        } else {
+            InterestRate::Annual { apr: rate, precision: apr_precision }
+        }
+    }
+
+    /// Whether `precision` is a power of ten within the supported APR range
+    fn is_valid_apr_precision(precision: u128) -> bool {
+        let mut value = APR_PRECISION;
+        while value < precision && value < MAX_APR_PRECISION {
+            value *= 10;
+        }
+        value == precision
+    }
+
+    /// Whether the amount due stays the same for the whole term: full-term
+    /// interest with no per-block schedule and no prepayment penalty
+    fn has_fixed_repayment(&self) -> bool {
+        self.loan_flags() & (FLAG_EARLY_REPAYMENT_REBATE | FLAG_OPEN_TERM) == 0
+            && self.prepayment_penalty_bps() == 0
+    }
+
+    fn interest_rate(&self) -> InterestRate {
+        let apr_precision = match self.apr_precision() {
+            0 => APR_PRECISION,
+            precision => precision,
+        };
+        Self::interest_rate_for(self.loan_flags(), self.apr(), apr_precision)
+    }
+
+    /// Hard deadline: the last block repayment is accepted without a notice
+    /// of default, i.e. the soft repayment deadline plus the grace period
+    fn hard_deadline(&self) -> u128 {
+        self.repayment_deadline().saturating_add(self.grace_blocks())
+    }
+
+    /// Last block before the collateral becomes claimable, or None while a
+    /// required notice of default has not been given
+    fn default_claimable_after(&self) -> Option<u128> {
+        let notice_blocks = self.default_notice_blocks();
+        if notice_blocks == 0 {
+            return Some(self.hard_deadline());
+        }
+        match self.default_notice_block() {
+            0 => None,
+            notice_block => Some(notice_block.saturating_add(notice_blocks)),
+        }
+    }
+
+    /// Last block the offer can be taken before it goes stale, None when
+    /// offers never go stale
+    fn offer_stale_after(&self) -> Option<u128> {
+        match self.max_take_delay_blocks() {
+            0 => None,
+            max_take_delay => Some(self.offer_refresh_block().saturating_add(max_take_delay)),
+        }
+    }
+
+    /// Blocks left until the repayment deadline of an active loan: 0 once
+    /// passed or when no loan is active, NO_DEADLINE for an uncalled
+    /// open-term loan
+    fn blocks_remaining(&self) -> u128 {
+        if self.state_value() != STATE_LOAN_ACTIVE {
+            return 0;
+        }
+        match self.repayment_deadline() {
+            NO_DEADLINE => NO_DEADLINE,
+            deadline => deadline.saturating_sub(self.current_block()),
+        }
+    }
+
+    /// Whether the loan is past the point where it can still be repaid
+    fn is_in_default(&self) -> bool {
+        self.default_claimable_after()
+            .is_some_and(|block| self.current_block() > block)
+    }
+
+    fn log_length(&self, log: &str) -> u128 {
+        StoragePointer::from_keyword(log).keyword("/length").get_value::<u128>()
+    }
+
+    /// Refuse a repeatable call once the history log is at its limit
+    fn ensure_history_capacity(&self) -> Result<()> {
+        if self.log_length(HISTORY_LOG) >= HISTORY_LOG_LIMIT {
+            return Err(anyhow!("History log is full"));
+        }
+        Ok(())
+    }
+
+    /// Append an encoded entry to the log stored under `log`
+    fn append_log(&self, log: &str, entry: Vec<u8>) {
+        let pointer = StoragePointer::from_keyword(log);
+        let index = self.log_length(log);
+        pointer.keyword(&format!("/{}", index)).set(Arc::new(entry));
+        pointer.keyword("/length").set_value::<u128>(index + 1);
+    }
+
+    /// Read a page of a log: its total length and page flags, followed by
+    /// the entries from `offset`, at most `limit` of them and no more than
+    /// fit in MAX_LOG_PAGE_SIZE
+    fn read_log(&self, log: &str, offset: u128, limit: u128) -> Vec<u8> {
+        let pointer = StoragePointer::from_keyword(log);
+        let length = self.log_length(log);
+        let end = offset.saturating_add(limit).min(length);
+
+        let mut flags: u128 = 0;
+        let mut entries: Vec<u8> = Vec::new();
+        for index in offset..end {
+            let entry = pointer.keyword(&format!("/{}", index)).get();
+            if LOG_PAGE_HEADER_SIZE + entries.len() + entry.len() > MAX_LOG_PAGE_SIZE {
+                flags |= LOG_PAGE_TRUNCATED;
+                break;
+            }
+            entries.extend_from_slice(entry.as_ref());
+        }
+
+        let mut data: Vec<u8> = Vec::with_capacity(LOG_PAGE_HEADER_SIZE + entries.len());
+        data.extend_from_slice(&length.to_le_bytes());
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.extend_from_slice(&entries);
+        data
+    }
+
+    /// Decode the u128 at position `field` of an encoded log entry
+    fn entry_field(entry: &[u8], field: usize) -> u128 {
+        entry
+            .get(field * 16..(field + 1) * 16)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u128::from_le_bytes)
+            .unwrap_or(0)
+    }
+
+    /// Append an entry to the loan history log
+    fn record_history(&self, event: u128, amount: u128) {
+        let mut entry: Vec<u8> = Vec::with_capacity(HISTORY_ENTRY_SIZE);
+        entry.extend_from_slice(&self.current_block().to_le_bytes());
+        entry.extend_from_slice(&event.to_le_bytes());
+        entry.extend_from_slice(&amount.to_le_bytes());
+        self.append_log(HISTORY_LOG, entry);
+    }
+
+    /// Checkpoint interest at an interaction that settles or rolls it over
+    /// (take, capitalization, repayment): the interest accrued since the
+    /// previous checkpoint is added to the running total and logged with the
+    /// principal it accrued on
+    fn record_accrual(&self, principal: u128, interest: u128) -> Result<()> {
+        let total = self
+            .total_interest_accrued()
+            .checked_add(interest)
+            .ok_or_else(|| anyhow!("Overflow accumulating interest"))?;
+        self.set_total_interest_accrued(total);
+
+        let mut entry: Vec<u8> = Vec::with_capacity(ACCRUAL_ENTRY_SIZE);
+        entry.extend_from_slice(&self.current_block().to_le_bytes());
+        entry.extend_from_slice(&principal.to_le_bytes());
+        entry.extend_from_slice(&interest.to_le_bytes());
+        entry.extend_from_slice(&total.to_le_bytes());
+        self.append_log(ACCRUAL_LOG, entry);
+        Ok(())
+    }
+
+    /// Pay a transfer out of the contract and append it to the transfer log,
+    /// tagged with the event that caused it, the role of the party whose
+    /// escrow it is drawn from (the creditor's loan funds, the debitor's
+    /// collateral, deposit or repayment, or ROLE_CONTRACT for minted tokens)
+    /// and the recipient's role.
+    /// Refunds of incoming tokens are not logged: they never entered escrow.
+    fn pay_out(
+        &self,
+        response: &mut CallResponse,
+        event: u128,
+        from_role: u128,
+        to_role: u128,
+        transfer: AlkaneTransfer,
+    ) -> Result<()> {
+        let mut entry: Vec<u8> = Vec::with_capacity(TRANSFER_ENTRY_SIZE);
+        entry.extend_from_slice(&self.current_block().to_le_bytes());
+        entry.extend_from_slice(&event.to_le_bytes());
+        entry.extend_from_slice(&from_role.to_le_bytes());
+        entry.extend_from_slice(&to_role.to_le_bytes());
+        entry.extend_from_slice(&transfer.id.block.to_le_bytes());
+        entry.extend_from_slice(&transfer.id.tx.to_le_bytes());
+        entry.extend_from_slice(&transfer.value.to_le_bytes());
+        self.append_log(TRANSFER_LOG, entry);
+
+        Self::pay_merged(&mut response.alkanes, transfer)
+    }
+
     /// Pure arithmetic helper: compute repayment = principal + interest.
     ///
-    /// Uses high-precision math (18 decimal places) to avoid rounding errors
-    /// that could result in zero-interest loans for small principal amounts.
-    /// Called from both `init_with_loan_offer` and `calculate_repayment_amount`.
+    /// Interest is the exact floor of the formula, computed with a 256-bit
+    /// intermediate product, so large principals price as precisely as small.
+    /// Called from `init_with_loan_offer` to validate the full-term amount.
     fn compute_repayment(
         principal: u128,
-        apr: u128,
+        rate: InterestRate,
         duration: u128,
     ) -> Result<u128> {
-        let interest = math::precision::calculate_interest_precise(
-            principal,
-            apr,
-            duration,
-        )?;
+        let interest = rate.interest(principal, duration)?;
 
         principal
             .checked_add(interest)
             .ok_or_else(|| anyhow!("Overflow adding interest to principal"))
     }
 
-    /// Calculate the total repayment amount (principal + interest)
+    /// Quote the amount needed to close the active loan at the current block
     /// from the values stored in contract state.
+    fn quote_repayment(&self) -> Result<RepaymentQuote> {
+        let mut quote = self.quote_scheduled_repayment()?;
+
+        // Past the soft deadline the borrower pays the late penalty on top
+        if self.state_value() == STATE_LOAN_ACTIVE
+            && self.current_block() > self.repayment_deadline()
+        {
+            quote.late_penalty =
+                math::repayment::apply_bps(quote.principal, self.late_penalty_bps())?;
+        }
+
+        Ok(quote)
+    }
+
+    /// Quote principal, interest and early-repayment fees per the loan's
+    /// schedule, before any late penalty
+    fn quote_scheduled_repayment(&self) -> Result<RepaymentQuote> {
+        let principal = self.loan_amount();
+
+        let fixed_repayment = self.fixed_repayment();
+        if fixed_repayment != 0 {
+            return Ok(RepaymentQuote {
+                principal,
+                interest: fixed_repayment
+                    .checked_sub(principal)
+                    .ok_or_else(|| anyhow!("Memoized repayment below principal"))?,
+                break_fee: 0,
+                prepayment_penalty: 0,
+                late_penalty: 0,
+            });
+        }
+
+        let rate = self.interest_rate();
+        let duration = self.duration_blocks();
+
+        let current_block = self.current_block();
+        let mut elapsed = current_block.saturating_sub(self.accrual_start_block());
+        if self.loan_flags() & FLAG_MINIMUM_BLOCK_INTEREST != 0 {
+            elapsed = elapsed.max(1);
+        }
+        let loan_age = current_block.saturating_sub(self.loan_start_block());
+
+        let mut quote = if self.is_open_term() {
+            math::repayment::quote_open_term_repayment(principal, rate, elapsed)?
+        } else if self.loan_flags() & FLAG_EARLY_REPAYMENT_REBATE == 0 {
+            math::repayment::quote_full_term_repayment(principal, rate, duration)?
+        } else {
+            math::repayment::quote_early_repayment(
+                principal,
+                rate,
+                duration,
+                elapsed,
+                self.break_fee_bps(),
+            )?
+        };
+
+        if loan_age < self.prepayment_lockout_blocks() {
+            quote.prepayment_penalty = math::repayment::calculate_prepayment_penalty(
+                principal,
+                rate,
+                duration,
+                elapsed,
+                self.prepayment_penalty_bps(),
+            )?;
+        }
+
+        Ok(quote)
+    }
+
+    /// Calculate the amount needed to close the active loan at the current block
     fn calculate_repayment_amount(&self) -> Result<u128> {
-        Self::compute_repayment(
-            self.loan_amount(),
-            self.apr(),
-            self.duration_blocks(),
-        )
+        self.quote_repayment()?.amount_due()
+    }
+
+    /// Reject token ids that cannot name a deployed token: the zero id, the
+    /// lending contract itself and the reserved creation/factory blocks
+    fn validate_token_id(&self, token: &AlkaneId, label: &str) -> Result<()> {
+        if token.block == 0 && token.tx == 0 {
+            return Err(anyhow!("{} token id cannot be zero", label));
+        }
+        if *token == self.context()?.myself {
+            return Err(anyhow!("{} token cannot be the lending contract", label));
+        }
+        if RESERVED_ID_BLOCKS.contains(&token.block) {
+            return Err(anyhow!("{} token id is in reserved space", label));
+        }
+        Ok(())
+    }
+
+    /// Whether the call carries a nonzero amount of the given alkane
+    fn incoming_contains(&self, id: &AlkaneId) -> Result<bool> {
+        Ok(self
+            .context()?
+            .incoming_alkanes
+            .0
+            .iter()
+            .any(|transfer| transfer.id == *id && transfer.value > 0))
+    }
+
+    /// A private offer can only be taken (or reserved) by a call carrying the
+    /// allowed taker alkane, which is refunded like any other extra token
+    fn check_allowed_taker(&self) -> Result<()> {
+        let allowed_taker = self.allowed_taker().unwrap_or(PUBLIC_OFFER);
+        if allowed_taker != PUBLIC_OFFER && !self.incoming_contains(&allowed_taker)? {
+            return Err(anyhow!("Loan offer is private"));
+        }
+        Ok(())
+    }
+
+    /// Add a transfer to an outgoing parcel, merging it into an existing entry
+    /// for the same token so a parcel with duplicate ids is refunded once per id
+    fn pay_merged(parcel: &mut AlkaneTransferParcel, transfer: AlkaneTransfer) -> Result<()> {
+        match parcel.0.iter_mut().find(|pending| pending.id == transfer.id) {
+            Some(pending) => {
+                pending.value = pending
+                    .value
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+            }
+            None => parcel.pay(transfer),
+        }
+        Ok(())
     }
 
     /// Validate and collect incoming tokens of a specific type
@@ -184,17 +1150,27 @@ impl LendingContract {
         expected_amount: u128,
     ) -> Result<(u128, CallResponse)> {
         let context = self.context()?;
+        Self::collect_tokens(context.incoming_alkanes.0, expected_token, expected_amount)
+    }
+
+    /// Collect `expected_amount` of `expected_token` from `incoming`,
+    /// refunding everything else
+    fn collect_tokens(
+        incoming: Vec<AlkaneTransfer>,
+        expected_token: AlkaneId,
+        expected_amount: u128,
+    ) -> Result<(u128, CallResponse)> {
         let mut token_received: u128 = 0;
         let mut response = CallResponse::default();
 
-        for transfer in context.incoming_alkanes.0.clone() {
+        for transfer in incoming {
             if transfer.id == expected_token {
                 token_received = token_received
                     .checked_add(transfer.value)
                     .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
             } else {
                 // Refund unexpected tokens
-                response.alkanes.pay(transfer);
+                Self::pay_merged(&mut response.alkanes, transfer)?;
             }
         }
 
@@ -208,23 +1184,52 @@ impl LendingContract {
 
         // Refund excess tokens
         if token_received > expected_amount {
-            response.alkanes.pay(AlkaneTransfer {
-                id: expected_token,
-                value: token_received - expected_amount,
-            });
+            Self::pay_merged(
+                &mut response.alkanes,
+                AlkaneTransfer {
+                    id: expected_token,
+                    value: token_received - expected_amount,
+                },
+            )?;
         }
 
         Ok((expected_amount, response))
     }
 
-    /// Refund all incoming tokens
+    /// Refund all incoming tokens, one transfer per token id
     fn refund_all_incoming(&self) -> Result<CallResponse> {
-        Ok(CallResponse::forward(&self.context()?.incoming_alkanes))
+        let mut response = CallResponse::default();
+        for transfer in self.context()?.incoming_alkanes.0 {
+            Self::pay_merged(&mut response.alkanes, transfer)?;
+        }
+        Ok(response)
+    }
+
+    /// Refund incoming tokens except the creditor auth tokens, which are
+    /// retired so a settled position cannot be passed on as if still live
+    fn consume_creditor_note(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut consumed: u128 = 0;
+        let mut response = CallResponse::default();
+
+        for transfer in context.incoming_alkanes.0.clone() {
+            if transfer.id == context.myself {
+                consumed = consumed
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+            } else {
+                Self::pay_merged(&mut response.alkanes, transfer)?;
+            }
+        }
+
+        self.set_creditor_note_supply(self.creditor_note_supply().saturating_sub(consumed));
+        Ok(response)
     }
 
     // ============ Loan Offer (Case 2) ============
 
     /// Creditor creates loan offer by depositing loan tokens
+    #[allow(clippy::too_many_arguments)]
     fn init_with_loan_offer(
         &self,
         collateral_token: AlkaneId,
@@ -233,6 +1238,131 @@ impl LendingContract {
         loan_amount: u128,
         duration_blocks: u128,
         desired_apr: u128,
+        loan_flags: u128,
+        break_fee_bps: u128,
+        prepayment_penalty_bps: u128,
+        prepayment_lockout_blocks: u128,
+        default_notice_blocks: u128,
+        max_take_delay_blocks: u128,
+        grace_blocks: u128,
+        late_penalty_bps: u128,
+        reservation_deposit: u128,
+        reservation_blocks: u128,
+        allowed_taker: AlkaneId,
+        apr_precision: u128,
+    ) -> Result<CallResponse> {
+        self.authorize()?;
+
+        let incoming = self.context()?.incoming_alkanes.0;
+        self.open_offer(
+            incoming,
+            collateral_token,
+            collateral_amount,
+            loan_token,
+            loan_amount,
+            duration_blocks,
+            desired_apr,
+            loan_flags,
+            break_fee_bps,
+            prepayment_penalty_bps,
+            prepayment_lockout_blocks,
+            default_notice_blocks,
+            max_take_delay_blocks,
+            grace_blocks,
+            late_penalty_bps,
+            reservation_deposit,
+            reservation_blocks,
+            allowed_taker,
+            apr_precision,
+        )
+    }
+
+    /// Creditor and debitor settle in one call: the offer is opened with the
+    /// loan tokens and taken with the collateral sent alongside
+    #[allow(clippy::too_many_arguments)]
+    fn init_and_take(
+        &self,
+        collateral_token: AlkaneId,
+        collateral_amount: u128,
+        loan_token: AlkaneId,
+        loan_amount: u128,
+        duration_blocks: u128,
+        desired_apr: u128,
+        loan_flags: u128,
+        break_fee_bps: u128,
+        prepayment_penalty_bps: u128,
+        prepayment_lockout_blocks: u128,
+        default_notice_blocks: u128,
+        max_take_delay_blocks: u128,
+        grace_blocks: u128,
+        late_penalty_bps: u128,
+        reservation_deposit: u128,
+        reservation_blocks: u128,
+        allowed_taker: AlkaneId,
+        apr_precision: u128,
+    ) -> Result<CallResponse> {
+        self.authorize()?;
+
+        // Each leg collects its own token; anything else is refunded once,
+        // by the offer
+        let (collateral, funding): (Vec<AlkaneTransfer>, Vec<AlkaneTransfer>) = self
+            .context()?
+            .incoming_alkanes
+            .0
+            .into_iter()
+            .partition(|transfer| transfer.id == collateral_token);
+        let mut response = self.open_offer(
+            funding,
+            collateral_token,
+            collateral_amount,
+            loan_token,
+            loan_amount,
+            duration_blocks,
+            desired_apr,
+            loan_flags,
+            break_fee_bps,
+            prepayment_penalty_bps,
+            prepayment_lockout_blocks,
+            default_notice_blocks,
+            max_take_delay_blocks,
+            grace_blocks,
+            late_penalty_bps,
+            reservation_deposit,
+            reservation_blocks,
+            allowed_taker,
+            apr_precision,
+        )?;
+        for transfer in self.accept_offer(collateral)?.alkanes.0 {
+            Self::pay_merged(&mut response.alkanes, transfer)?;
+        }
+
+        Ok(response)
+    }
+
+    /// Open the offer funded by `incoming`, shared by InitWithLoanOffer and
+    /// InitAndTake
+    #[allow(clippy::too_many_arguments)]
+    fn open_offer(
+        &self,
+        incoming: Vec<AlkaneTransfer>,
+        collateral_token: AlkaneId,
+        collateral_amount: u128,
+        loan_token: AlkaneId,
+        loan_amount: u128,
+        duration_blocks: u128,
+        desired_apr: u128,
+        loan_flags: u128,
+        break_fee_bps: u128,
+        prepayment_penalty_bps: u128,
+        prepayment_lockout_blocks: u128,
+        default_notice_blocks: u128,
+        max_take_delay_blocks: u128,
+        grace_blocks: u128,
+        late_penalty_bps: u128,
+        reservation_deposit: u128,
+        reservation_blocks: u128,
+        allowed_taker: AlkaneId,
+        apr_precision: u128,
     ) -> Result<CallResponse> {
         // Ensure contract is not already initialized
         self.observe_initialization()?;
@@ -250,36 +1380,192 @@ impl LendingContract {
         if collateral_token == loan_token {
             return Err(anyhow!("Collateral and loan token cannot be the same"));
         }
+        self.validate_token_id(&collateral_token, "Collateral")?;
+        self.validate_token_id(&loan_token, "Loan")?;
+        if loan_amount > collateral_amount.saturating_mul(MAX_LOAN_PER_COLLATERAL) {
+            return Err(anyhow!("Collateral-to-loan ratio below minimum"));
+        }
+        if collateral_amount > loan_amount.saturating_mul(MAX_COLLATERAL_PER_LOAN) {
+            return Err(anyhow!("Collateral-to-loan ratio above maximum"));
+        }
+        if loan_flags & !SUPPORTED_LOAN_FLAGS != 0 {
+            return Err(anyhow!("Unsupported loan flags"));
+        }
+        // An absolute deadline is normalized to the longest duration the loan
+        // can run, i.e. if taken now; take shortens it to the blocks left then
+        let absolute_deadline = loan_flags & FLAG_ABSOLUTE_DEADLINE != 0;
+        let (deadline, duration_blocks) = if absolute_deadline {
+            let current_block = self.current_block();
+            if duration_blocks <= current_block {
+                return Err(anyhow!("Deadline must be in the future"));
+            }
+            (duration_blocks, duration_blocks - current_block)
+        } else {
+            (0, duration_blocks)
+        };
+        if break_fee_bps > BPS_PRECISION {
+            return Err(anyhow!("Break fee cannot exceed 100%"));
+        }
+        if break_fee_bps != 0 && loan_flags & FLAG_EARLY_REPAYMENT_REBATE == 0 {
+            return Err(anyhow!("Break fee requires early repayment rebate"));
+        }
+        if prepayment_penalty_bps > BPS_PRECISION {
+            return Err(anyhow!("Prepayment penalty cannot exceed 100%"));
+        }
+        if (prepayment_penalty_bps == 0) != (prepayment_lockout_blocks == 0) {
+            return Err(anyhow!("Prepayment penalty and lockout must be set together"));
+        }
+        if prepayment_lockout_blocks > duration_blocks {
+            return Err(anyhow!("Prepayment lockout cannot exceed duration"));
+        }
+        // Without the rebate an early repayment already pays full-term interest
+        if prepayment_penalty_bps != 0 && loan_flags & FLAG_EARLY_REPAYMENT_REBATE == 0 {
+            return Err(anyhow!("Prepayment penalty requires early repayment rebate"));
+        }
+        // Both are charged on the same remaining interest during the lockout
+        if break_fee_bps + prepayment_penalty_bps > BPS_PRECISION {
+            return Err(anyhow!("Break fee and prepayment penalty cannot exceed 100% combined"));
+        }
+        if late_penalty_bps > BPS_PRECISION {
+            return Err(anyhow!("Late penalty cannot exceed 100%"));
+        }
+        if late_penalty_bps != 0 && grace_blocks == 0 {
+            return Err(anyhow!("Late penalty requires a grace period"));
+        }
+        if (reservation_deposit == 0) != (reservation_blocks == 0) {
+            return Err(anyhow!("Reservation deposit and period must be set together"));
+        }
+        // Forfeited deposits are paid out with the creditor's claim, which
+        // bond mode replaces with redemptions
+        if reservation_deposit != 0 && loan_flags & FLAG_ZERO_COUPON_BOND != 0 {
+            return Err(anyhow!("Bond mode does not support reservations"));
+        }
+        if allowed_taker != PUBLIC_OFFER {
+            self.validate_token_id(&allowed_taker, "Allowed taker")?;
+        }
+        let apr_precision = if apr_precision == 0 { APR_PRECISION } else { apr_precision };
+        if !Self::is_valid_apr_precision(apr_precision) {
+            return Err(anyhow!("APR precision must be a power of ten from 1e4 to 1e12"));
+        }
+        // A per-block rate has its own fixed 1e18 scale
+        if apr_precision != APR_PRECISION && loan_flags & FLAG_PER_BLOCK_RATE != 0 {
+            return Err(anyhow!("APR precision does not apply to per-block rates"));
+        }
+        // An interest-free loan repays exactly its principal; fees priced off
+        // interest would silently be zero
+        if desired_apr == 0 && (break_fee_bps != 0 || prepayment_penalty_bps != 0) {
+            return Err(anyhow!("Interest-free loans cannot charge interest-based fees"));
+        }
+        // Bonds are minted for the full-term amount, so it must be the only
+        // possible repayment and known at init
+        if loan_flags & FLAG_ZERO_COUPON_BOND != 0
+            && (loan_flags & (FLAG_EARLY_REPAYMENT_REBATE | FLAG_ABSOLUTE_DEADLINE) != 0
+                || prepayment_penalty_bps != 0
+                || late_penalty_bps != 0)
+        {
+            return Err(anyhow!("Bond mode requires fixed full-term repayment"));
+        }
+        // Open-term interest simply accrues until repayment, so none of the
+        // term-based options apply
+        if loan_flags & FLAG_OPEN_TERM != 0
+            && (loan_flags & !(FLAG_OPEN_TERM | FLAG_MINIMUM_BLOCK_INTEREST) != 0
+                || prepayment_penalty_bps != 0)
+        {
+            return Err(anyhow!("Open-term loans cannot use term-based options"));
+        }
+        // Fixed-term loans without rebate are charged full-term interest, so a
+        // same-block repayment already pays more than one block
+        if loan_flags & FLAG_MINIMUM_BLOCK_INTEREST != 0
+            && loan_flags & (FLAG_EARLY_REPAYMENT_REBATE | FLAG_OPEN_TERM) == 0
+        {
+            return Err(anyhow!("Minimum block interest requires per-block accrual"));
+        }
 
         // Validate that the repayment amount is calculable without overflow.
         // Without this check a malicious creditor could craft loan terms where
         // the interest calculation overflows, making repay_loan always revert.
         // The debitor would be unable to repay and would lose their collateral.
-        Self::compute_repayment(loan_amount, desired_apr, duration_blocks)?;
+        let rate = Self::interest_rate_for(loan_flags, desired_apr, apr_precision);
+        let full_term_repayment = Self::compute_repayment(loan_amount, rate, duration_blocks)?;
+        // Both representations are reported by GetInterestRate
+        if rate.as_annual().is_err() || rate.as_per_block().is_err() {
+            return Err(anyhow!("Interest rate out of range"));
+        }
+
+        // Amounts larger than the token supply (e.g. on an indivisible,
+        // supply-1 alkane) could never be delivered, leaving the offer
+        // unserviceable
+        let collateral_token_supply = self.query_total_supply(&collateral_token)?;
+        if collateral_amount > collateral_token_supply {
+            return Err(anyhow!("Collateral amount exceeds token supply"));
+        }
+        let loan_token_supply = self.query_total_supply(&loan_token)?;
+        if loan_amount > loan_token_supply {
+            return Err(anyhow!("Loan amount exceeds token supply"));
+        }
 
         // Collect loan tokens from creditor
-        let (_, mut response) = self.collect_incoming_tokens(loan_token.clone(), loan_amount)?;
+        let (_, mut response) = Self::collect_tokens(incoming, loan_token, loan_amount)?;
 
         // Store loan parameters
         self.set_collateral_token(collateral_token);
         self.set_collateral_amount(collateral_amount);
+        self.set_collateral_token_supply(collateral_token_supply);
         self.set_loan_token(loan_token);
         self.set_loan_amount(loan_amount);
+        self.set_loan_token_supply(loan_token_supply);
         self.set_duration_blocks(duration_blocks);
+        if absolute_deadline {
+            self.set_repayment_deadline(deadline);
+        }
         self.set_apr(desired_apr);
-        response.alkanes.pay(self.deploy_self_auth_token(1)?);
+        self.set_loan_flags(loan_flags);
+        self.set_break_fee_bps(break_fee_bps);
+        self.set_prepayment_penalty_bps(prepayment_penalty_bps);
+        self.set_prepayment_lockout_blocks(prepayment_lockout_blocks);
+        self.set_default_notice_blocks(default_notice_blocks);
+        self.set_max_take_delay_blocks(max_take_delay_blocks);
+        self.set_grace_blocks(grace_blocks);
+        self.set_late_penalty_bps(late_penalty_bps);
+        self.set_reservation_deposit(reservation_deposit);
+        self.set_reservation_blocks(reservation_blocks);
+        self.set_allowed_taker(allowed_taker);
+        self.set_apr_precision(apr_precision);
+        self.set_offer_refresh_block(self.current_block());
+        let auth_token = self.deploy_self_auth_token(1)?;
+        self.pay_out(&mut response, EVENT_OFFER_CREATED, ROLE_CONTRACT, ROLE_CREDITOR, auth_token)?;
+        self.set_creditor_note_supply(1);
+        if loan_flags & FLAG_ZERO_COUPON_BOND != 0 {
+            let bonds = self.mint_factory_token(full_term_repayment)?;
+            self.set_bond_token(bonds.id.clone());
+            self.set_bond_supply(full_term_repayment);
+            self.pay_out(&mut response, EVENT_OFFER_CREATED, ROLE_CONTRACT, ROLE_CREDITOR, bonds)?;
+        }
         self.set_state_value(STATE_WAITING_FOR_DEBITOR_TAKE);
+        self.record_history(EVENT_OFFER_CREATED, loan_amount);
 
         Ok(response)
     }
 
     /// Debitor takes loan by providing collateral
     fn take_loan_with_collateral(&self) -> Result<CallResponse> {
-        let state = self.state_value();
-        if state != STATE_WAITING_FOR_DEBITOR_TAKE {
-            return Err(anyhow!("Loan offer is not available"));
+        // A take that lost the race for the offer (e.g. ordered after another
+        // take in the same block) is told so rather than that no offer exists
+        if matches!(
+            self.state_value(),
+            STATE_LOAN_ACTIVE | STATE_LOAN_REPAID | STATE_LOAN_DEFAULTED
+        ) {
+            return Err(anyhow!("Loan offer already taken"));
         }
+        self.authorize()?;
 
+        let incoming = self.context()?.incoming_alkanes.0;
+        self.accept_offer(incoming)
+    }
+
+    /// Take the offer with the collateral in `incoming`, shared by
+    /// TakeLoanWithCollateral and InitAndTake
+    fn accept_offer(&self, incoming: Vec<AlkaneTransfer>) -> Result<CallResponse> {
         let collateral_token = self.collateral_token()?;
         let collateral_amount: u128 = self.collateral_amount();
         let loan_token = self.loan_token()?;
@@ -287,60 +1573,248 @@ impl LendingContract {
         let duration = self.duration_blocks();
         let current_block = self.current_block();
 
+        // Terms priced under old market conditions must not be taken
+        if self.offer_stale_after().is_some_and(|block| current_block > block) {
+            return self.release_stale_reservation(incoming);
+        }
+
+        self.check_allowed_taker()?;
+
+        // A reserved offer can only be taken with the reservation note, whose
+        // deposit is then refunded
+        self.forfeit_expired_reservation()?;
+        let deposit = self.held_deposit();
+        if deposit != 0 {
+            if !self.incoming_contains(&self.reservation_note()?)? {
+                return Err(anyhow!("Loan offer is reserved"));
+            }
+            self.set_held_deposit(0);
+        }
+
         // Collect collateral from debitor
-        let (_, mut response) = self.collect_incoming_tokens(collateral_token, collateral_amount)?;
+        let (_, mut response) = Self::collect_tokens(incoming, collateral_token, collateral_amount)?;
+        if deposit != 0 {
+            self.pay_out(
+                &mut response,
+                EVENT_LOAN_TAKEN,
+                ROLE_DEBITOR,
+                ROLE_DEBITOR,
+                AlkaneTransfer {
+                    id: collateral_token,
+                    value: deposit,
+                },
+            )?;
+        }
 
-        // Calculate deadline
-        let deadline = current_block
-            .checked_add(duration)
-            .ok_or_else(|| anyhow!("Overflow calculating deadline"))?;
+        // Calculate deadline; open-term loans have none until called
+        let deadline = if self.is_open_term() {
+            NO_DEADLINE
+        } else if self.loan_flags() & FLAG_ABSOLUTE_DEADLINE != 0 {
+            let deadline = self.repayment_deadline();
+            if current_block >= deadline {
+                return Err(anyhow!("Loan offer has expired"));
+            }
+            self.set_duration_blocks(deadline - current_block);
+            deadline
+        } else {
+            current_block
+                .checked_add(duration)
+                .ok_or_else(|| anyhow!("Overflow calculating deadline"))?
+        };
+
+        if self.has_fixed_repayment() {
+            let repayment =
+                Self::compute_repayment(loan_amount, self.interest_rate(), self.duration_blocks())?;
+            self.set_fixed_repayment(repayment);
+        }
 
         // Start loan
         self.set_loan_start_block(current_block);
+        self.set_accrual_start_block(current_block);
         self.set_repayment_deadline(deadline);
         self.set_state_value(STATE_LOAN_ACTIVE);
-
-        // Transfer loan tokens to debitor
-        response.alkanes.pay(AlkaneTransfer {
-            id: loan_token,
-            value: loan_amount,
-        });
+        self.record_history(EVENT_LOAN_TAKEN, loan_amount);
+        self.record_accrual(loan_amount, 0)?;
+
+        // Transfer loan tokens and debitor note to debitor
+        self.pay_out(
+            &mut response,
+            EVENT_LOAN_TAKEN,
+            ROLE_CREDITOR,
+            ROLE_DEBITOR,
+            AlkaneTransfer {
+                id: loan_token,
+                value: loan_amount,
+            },
+        )?;
+        let note = self.mint_debitor_note()?;
+        self.pay_out(&mut response, EVENT_LOAN_TAKEN, ROLE_CONTRACT, ROLE_DEBITOR, note)?;
 
         Ok(response)
     }
 
-    // ============ Loan Lifecycle ============
+    /// Would-be debitor reserves the offer by locking the reservation deposit
+    fn reserve_offer(&self) -> Result<CallResponse> {
+        self.authorize()?;
 
-    /// Repay the loan (principal + interest)
-    fn repay_loan(&self) -> Result<CallResponse> {
-        let state = self.state_value();
-        if state != STATE_LOAN_ACTIVE {
-            return Err(anyhow!("No active loan to repay"));
+        let deposit = self.reservation_deposit();
+        if deposit == 0 {
+            return Err(anyhow!("Offer does not take reservations"));
         }
-
-        // Check deadline hasn't passed
-        let deadline = self.repayment_deadline();
-        let current_block = self.current_block();
-        if current_block > deadline {
-            return Err(anyhow!("Loan has defaulted - deadline passed"));
+        // Otherwise anyone could hold a private offer away from its taker
+        self.check_allowed_taker()?;
+        self.forfeit_expired_reservation()?;
+        if self.held_deposit() != 0 {
+            return Err(anyhow!("Loan offer is reserved"));
+        }
+        self.ensure_history_capacity()?;
+
+        // The reserver must be able to take the offer until the reservation
+        // ends, or the deposit would be forfeited for the creditor's delay
+        let reserved_until = self
+            .current_block()
+            .checked_add(self.reservation_blocks())
+            .ok_or_else(|| anyhow!("Overflow calculating reservation expiry"))?;
+        if let Some(stale_after) = self.offer_stale_after() {
+            if self.current_block() > stale_after {
+                return Err(anyhow!("Loan offer is stale - creditor must refresh it"));
+            }
+            if reserved_until > stale_after {
+                return Err(anyhow!("Loan offer would go stale during the reservation"));
+            }
         }
 
-        let loan_token = self.loan_token()?;
-        let repayment_amount = self.calculate_repayment_amount()?;
-        let collateral_token = self.collateral_token()?;
-        let collateral_amount = self.collateral_amount();
+        let (_, mut response) = self.collect_incoming_tokens(self.collateral_token()?, deposit)?;
+        self.set_reserved_until(reserved_until);
+        self.set_held_deposit(deposit);
+        self.record_history(EVENT_OFFER_RESERVED, reserved_until);
 
-        // Collect repayment
+        let note = self.mint_factory_token(1)?;
+        self.set_reservation_note(note.id);
+        self.pay_out(&mut response, EVENT_OFFER_RESERVED, ROLE_CONTRACT, ROLE_DEBITOR, note)?;
+
+        Ok(response)
+    }
+
+    /// Refuse the take of a stale offer. A reserver sending the reservation
+    /// note gets the deposit back instead, as the take failed through no
+    /// fault of theirs; ReserveOffer keeps reservations within the take
+    /// window, so this only guards against the window closing early
+    fn release_stale_reservation(&self, incoming: Vec<AlkaneTransfer>) -> Result<CallResponse> {
+        self.forfeit_expired_reservation()?;
+        let deposit = self.held_deposit();
+        if deposit == 0 || !self.incoming_contains(&self.reservation_note()?)? {
+            return Err(anyhow!("Loan offer is stale - creditor must refresh it"));
+        }
+
+        self.set_held_deposit(0);
+        self.record_history(EVENT_RESERVATION_RELEASED, deposit);
+
+        let mut response = CallResponse::default();
+        for transfer in incoming {
+            Self::pay_merged(&mut response.alkanes, transfer)?;
+        }
+        self.pay_out(
+            &mut response,
+            EVENT_RESERVATION_RELEASED,
+            ROLE_DEBITOR,
+            ROLE_DEBITOR,
+            AlkaneTransfer {
+                id: self.collateral_token()?,
+                value: deposit,
+            },
+        )?;
+        Ok(response)
+    }
+
+    /// Move the deposit of a reservation that expired without a take to the
+    /// creditor's forfeited deposits
+    fn forfeit_expired_reservation(&self) -> Result<()> {
+        let deposit = self.held_deposit();
+        if deposit == 0 || self.current_block() <= self.reserved_until() {
+            return Ok(());
+        }
+
+        let forfeited = self
+            .forfeited_deposits()
+            .checked_add(deposit)
+            .ok_or_else(|| anyhow!("Overflow accumulating forfeited deposits"))?;
+        self.set_forfeited_deposits(forfeited);
+        self.set_held_deposit(0);
+        self.record_history(EVENT_RESERVATION_FORFEITED, deposit);
+        Ok(())
+    }
+
+    /// Pay forfeited reservation deposits out with a creditor settlement
+    fn pay_forfeited_deposits(&self, response: &mut CallResponse, event: u128) -> Result<()> {
+        self.forfeit_expired_reservation()?;
+        let forfeited = self.forfeited_deposits();
+        if forfeited == 0 {
+            return Ok(());
+        }
+
+        self.set_forfeited_deposits(0);
+        self.pay_out(
+            response,
+            event,
+            ROLE_DEBITOR,
+            ROLE_CREDITOR,
+            AlkaneTransfer {
+                id: self.collateral_token()?,
+                value: forfeited,
+            },
+        )
+    }
+
+    /// Creditor restarts the take window of a stale or soon-stale offer
+    fn refresh_offer(&self) -> Result<CallResponse> {
+        self.authorize()?;
+        self.ensure_history_capacity()?;
+
+        let current_block = self.current_block();
+        self.set_offer_refresh_block(current_block);
+        self.record_history(EVENT_OFFER_REFRESHED, current_block);
+
+        self.refund_all_incoming()
+    }
+
+    // ============ Loan Lifecycle ============
+
+    /// Repay the loan (principal + interest)
+    fn repay_loan(&self) -> Result<CallResponse> {
+        self.authorize()?;
+
+        // Check deadline (and any default notice period) hasn't passed
+        if self.is_in_default() {
+            return Err(anyhow!("Loan has defaulted - deadline passed"));
+        }
+
+        let loan_token = self.loan_token()?;
+        let quote = self.quote_repayment()?;
+        let repayment_amount = quote.amount_due()?;
+        let collateral_token = self.collateral_token()?;
+        let collateral_amount = self.collateral_amount();
+
+        // Collect repayment
         let (_, mut response) = self.collect_incoming_tokens(loan_token.clone(), repayment_amount)?;
 
         // Mark loan as repaid
+        self.set_repaid_amount(repayment_amount);
         self.set_state_value(STATE_LOAN_REPAID);
+        self.record_history(EVENT_LOAN_REPAID, repayment_amount);
+        self.record_accrual(quote.principal, quote.interest)?;
 
         // Return collateral to debitor
-        response.alkanes.pay(AlkaneTransfer {
-            id: collateral_token,
-            value: collateral_amount,
-        });
+        self.pay_out(
+            &mut response,
+            EVENT_LOAN_REPAID,
+            ROLE_DEBITOR,
+            ROLE_DEBITOR,
+            AlkaneTransfer {
+                id: collateral_token,
+                value: collateral_amount,
+            },
+        )?;
 
         // Repayment held for creditor claim
         Ok(response)
@@ -348,54 +1822,72 @@ impl LendingContract {
 
     /// Creditor claims collateral after loan default
     fn claim_defaulted_collateral(&self) -> Result<CallResponse> {
-        let state = self.state_value();
-        if state != STATE_LOAN_ACTIVE {
-            return Err(anyhow!("No active loan to claim"));
+        self.authorize()?;
+        if self.is_bond_mode() {
+            return Err(anyhow!("Bond mode claims are made with RedeemBonds"));
         }
 
-        self.only_owner()?;
-
-        // Check deadline has passed
-        let deadline = self.repayment_deadline();
+        // Check the hard deadline has passed
+        let deadline = self.hard_deadline();
         let current_block = self.current_block();
         if current_block <= deadline {
             return Err(anyhow!("Loan has not defaulted yet - deadline not passed"));
         }
+        if !self.is_in_default() {
+            if self.default_notice_block() == 0 {
+                return Err(anyhow!("Notice of default required"));
+            }
+            return Err(anyhow!("Default notice period has not elapsed"));
+        }
 
         let collateral_token = self.collateral_token()?;
         let collateral_amount = self.collateral_amount();
 
         // Mark loan as defaulted
         self.set_state_value(STATE_LOAN_DEFAULTED);
+        self.record_history(EVENT_COLLATERAL_CLAIMED, collateral_amount);
 
         // Transfer collateral to creditor
-        let mut response = self.refund_all_incoming()?;
-        response.alkanes.pay(AlkaneTransfer {
-            id: collateral_token,
-            value: collateral_amount,
-        });
+        let mut response = self.consume_creditor_note()?;
+        self.pay_out(
+            &mut response,
+            EVENT_COLLATERAL_CLAIMED,
+            ROLE_DEBITOR,
+            ROLE_CREDITOR,
+            AlkaneTransfer {
+                id: collateral_token,
+                value: collateral_amount,
+            },
+        )?;
+        self.pay_forfeited_deposits(&mut response, EVENT_COLLATERAL_CLAIMED)?;
 
         Ok(response)
     }
 
     /// Creditor claims loan token after duration
     fn claim_repayment(&self) -> Result<CallResponse> {
-        let state = self.state_value();
-        if state != STATE_LOAN_REPAID {
-            return Err(anyhow!("Loan must be repaid to claim"));
+        self.authorize()?;
+        if self.is_bond_mode() {
+            return Err(anyhow!("Bond mode claims are made with RedeemBonds"));
         }
 
-        self.only_owner()?;
-
         let loan_token = self.loan_token()?;
-        let repayment_amount = self.calculate_repayment_amount()?;
+        let repayment_amount = self.repaid_amount();
+        self.record_history(EVENT_REPAYMENT_CLAIMED, repayment_amount);
 
         // Transfer repayment to creditor
-        let mut response = self.refund_all_incoming()?;
-        response.alkanes.pay(AlkaneTransfer {
-            id: loan_token,
-            value: repayment_amount,
-        });
+        let mut response = self.consume_creditor_note()?;
+        self.pay_out(
+            &mut response,
+            EVENT_REPAYMENT_CLAIMED,
+            ROLE_DEBITOR,
+            ROLE_CREDITOR,
+            AlkaneTransfer {
+                id: loan_token,
+                value: repayment_amount,
+            },
+        )?;
+        self.pay_forfeited_deposits(&mut response, EVENT_REPAYMENT_CLAIMED)?;
 
         Ok(response)
     }
@@ -404,29 +1896,288 @@ impl LendingContract {
 
     /// Creditor cancels loan offer (only before debitor takes)
     fn cancel_loan_offer(&self) -> Result<CallResponse> {
-        let state = self.state_value();
-        if state != STATE_WAITING_FOR_DEBITOR_TAKE {
-            return Err(anyhow!("Cannot cancel - loan offer not in cancellable state"));
+        self.authorize()?;
+        self.forfeit_expired_reservation()?;
+        if self.held_deposit() != 0 {
+            return Err(anyhow!("Loan offer is reserved"));
         }
 
-        self.only_owner()?;
-
         let loan_token = self.loan_token()?;
         let loan_amount = self.loan_amount();
 
+        // In bond mode every bond must come back before the escrow is released
+        let mut response = if self.is_bond_mode() {
+            let (_, response) =
+                self.collect_incoming_tokens(self.bond_token()?, self.bond_supply())?;
+            response
+        } else {
+            self.refund_all_incoming()?
+        };
+
         // Return loan tokens to creditor
-        let mut response = self.refund_all_incoming()?;
-        response.alkanes.pay(AlkaneTransfer {
-            id: loan_token,
-            value: loan_amount,
-        });
+        self.pay_out(
+            &mut response,
+            EVENT_OFFER_CANCELLED,
+            ROLE_CREDITOR,
+            ROLE_CREDITOR,
+            AlkaneTransfer {
+                id: loan_token,
+                value: loan_amount,
+            },
+        )?;
+
+        self.pay_forfeited_deposits(&mut response, EVENT_OFFER_CANCELLED)?;
 
         // Reset state
         self.set_state_value(STATE_UNINITIALIZED);
+        self.record_history(EVENT_OFFER_CANCELLED, loan_amount);
 
         Ok(response)
     }
 
+    // ============ Interest Capitalization ============
+
+    /// Creditor approves rolling accrued interest into principal
+    fn approve_capitalization(&self, extension_blocks: u128) -> Result<CallResponse> {
+        self.authorize()?;
+        if self.is_bond_mode() {
+            return Err(anyhow!("Capitalization is not available in bond mode"));
+        }
+        if self.is_open_term() {
+            return Err(anyhow!("Capitalization is not available for open-term loans"));
+        }
+
+        self.set_capitalization_extension(extension_blocks);
+
+        self.refund_all_incoming()
+    }
+
+    /// Debitor executes an approved capitalization
+    ///
+    /// Interest accrued since the last accrual start is added to principal,
+    /// accrual restarts at the current block and the deadline moves out by the
+    /// approved extension. The original start block is kept, so a prepayment
+    /// lockout is not restarted.
+    fn capitalize_interest(&self) -> Result<CallResponse> {
+        self.authorize()?;
+        self.ensure_history_capacity()?;
+
+        let extension = self.capitalization_extension();
+        if extension == 0 {
+            return Err(anyhow!("Capitalization not approved by creditor"));
+        }
+
+        let principal = self.loan_amount();
+        let rate = self.interest_rate();
+        let current_block = self.current_block();
+        let elapsed = current_block
+            .saturating_sub(self.accrual_start_block())
+            .min(self.duration_blocks());
+
+        let accrued_interest = rate.interest(principal, elapsed)?;
+        let new_principal = principal
+            .checked_add(accrued_interest)
+            .ok_or_else(|| anyhow!("Overflow adding interest to principal"))?;
+
+        let new_deadline = self
+            .repayment_deadline()
+            .checked_add(extension)
+            .ok_or_else(|| anyhow!("Overflow calculating deadline"))?;
+        if new_deadline <= current_block {
+            return Err(anyhow!("Extended deadline has already passed"));
+        }
+        let new_duration = new_deadline - current_block;
+
+        // Same guarantee as at init: the new terms must stay repayable
+        let new_repayment = Self::compute_repayment(new_principal, rate, new_duration)?;
+        if self.has_fixed_repayment() {
+            self.set_fixed_repayment(new_repayment);
+        }
+
+        self.set_loan_amount(new_principal);
+        self.set_duration_blocks(new_duration);
+        self.set_accrual_start_block(current_block);
+        self.set_repayment_deadline(new_deadline);
+        self.set_default_notice_block(0);
+        self.set_capitalization_extension(0);
+        self.record_history(EVENT_INTEREST_CAPITALIZED, accrued_interest);
+        self.record_accrual(new_principal, accrued_interest)?;
+
+        // Debitor note is returned with any other incoming tokens
+        self.refund_all_incoming()
+    }
+
+    // ============ Open-Term Loans ============
+
+    /// Creditor calls an open-term loan: the deadline becomes the current
+    /// block plus the notice period, after which default rules apply
+    fn call_loan(&self) -> Result<CallResponse> {
+        self.authorize()?;
+        if !self.is_open_term() {
+            return Err(anyhow!("Loan is not open-term"));
+        }
+        if self.repayment_deadline() != NO_DEADLINE {
+            return Err(anyhow!("Loan has already been called"));
+        }
+
+        let deadline = self
+            .current_block()
+            .checked_add(self.duration_blocks())
+            .ok_or_else(|| anyhow!("Overflow calculating deadline"))?;
+        self.set_repayment_deadline(deadline);
+        self.record_history(EVENT_LOAN_CALLED, deadline);
+
+        self.refund_all_incoming()
+    }
+
+    // ============ Default Notice ============
+
+    /// Creditor gives notice of default, opening the borrower's last window
+    fn notice_of_default(&self) -> Result<CallResponse> {
+        self.authorize()?;
+
+        let notice_blocks = self.default_notice_blocks();
+        if notice_blocks == 0 {
+            return Err(anyhow!("Loan does not require a notice of default"));
+        }
+        let current_block = self.current_block();
+        if current_block <= self.hard_deadline() {
+            return Err(anyhow!("Loan has not defaulted yet - deadline not passed"));
+        }
+        if self.default_notice_block() != 0 {
+            return Err(anyhow!("Notice of default already given"));
+        }
+
+        self.set_default_notice_block(current_block);
+        self.record_history(EVENT_DEFAULT_NOTICE, notice_blocks);
+
+        self.refund_all_incoming()
+    }
+
+    // ============ Bond Redemption ============
+
+    /// Redeem bonds for loan tokens (repaid) or collateral (defaulted)
+    ///
+    /// Redeemed bonds stay in the contract, retiring them from circulation.
+    /// Once the deadline passes without repayment the first redemption moves
+    /// the loan to the defaulted state.
+    fn redeem_bonds(&self) -> Result<CallResponse> {
+        self.authorize()?;
+        if !self.is_bond_mode() {
+            return Err(anyhow!("Loan is not in bond mode"));
+        }
+
+        let mut state = self.state_value();
+        if state == STATE_LOAN_ACTIVE && self.is_in_default() {
+            state = STATE_LOAN_DEFAULTED;
+            self.set_state_value(STATE_LOAN_DEFAULTED);
+            self.record_history(EVENT_COLLATERAL_CLAIMED, self.collateral_amount());
+        }
+
+        let (payout_token, payout) = match state {
+            STATE_LOAN_REPAID => (self.loan_token()?, None),
+            STATE_LOAN_DEFAULTED => (self.collateral_token()?, Some(self.collateral_amount())),
+            _ => return Err(anyhow!("Bonds are not redeemable yet")),
+        };
+
+        let context = self.context()?;
+        let bond_token = self.bond_token()?;
+        let mut bonds: u128 = 0;
+        let mut response = CallResponse::default();
+        for transfer in context.incoming_alkanes.0.clone() {
+            if transfer.id == bond_token {
+                bonds = bonds
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+            } else {
+                Self::pay_merged(&mut response.alkanes, transfer)?;
+            }
+        }
+        if bonds == 0 {
+            return Err(anyhow!("No bonds to redeem"));
+        }
+
+        let value = match payout {
+            // Bonds were minted for the full-term repayment, so they redeem 1:1
+            None => bonds,
+            Some(collateral_amount) => {
+                math::precision::mul_div(collateral_amount, bonds, self.bond_supply())
+                    .ok_or_else(|| anyhow!("Overflow calculating collateral share"))?
+            }
+        };
+
+        let redeemed = self
+            .bonds_redeemed()
+            .checked_add(bonds)
+            .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+        self.set_bonds_redeemed(redeemed);
+        self.record_history(EVENT_BONDS_REDEEMED, bonds);
+        self.pay_out(
+            &mut response,
+            EVENT_BONDS_REDEEMED,
+            ROLE_DEBITOR,
+            ROLE_BOND_HOLDER,
+            AlkaneTransfer {
+                id: payout_token,
+                value,
+            },
+        )?;
+
+        Ok(response)
+    }
+
+    // ============ Position Labels ============
+
+    /// Creditor sets the label on their side of the position
+    fn set_creditor_label(&self, label: u128) -> Result<CallResponse> {
+        self.authorize()?;
+        self.set_creditor_label_value(label);
+        self.refund_all_incoming()
+    }
+
+    /// Debitor sets the label on their side of the position
+    fn set_debitor_label(&self, label: u128) -> Result<CallResponse> {
+        self.authorize()?;
+        self.set_debitor_label_value(label);
+        self.refund_all_incoming()
+    }
+
+    // ============ Counterparty Disclosure ============
+
+    /// Store a disclosure pointer under `keyword`; disclosure is opt-in, so
+    /// nothing about the pointer is validated
+    fn record_disclosure(&self, keyword: &str, txid_high: u128, txid_low: u128, index: u128) {
+        let mut pointer: Vec<u8> = Vec::with_capacity(DISCLOSURE_SIZE);
+        pointer.extend_from_slice(&txid_high.to_le_bytes());
+        pointer.extend_from_slice(&txid_low.to_le_bytes());
+        pointer.extend_from_slice(&index.to_le_bytes());
+        StoragePointer::from_keyword(keyword).set(Arc::new(pointer));
+    }
+
+    /// Creditor discloses an identity attestation for their side of the position
+    fn set_creditor_disclosure(
+        &self,
+        txid_high: u128,
+        txid_low: u128,
+        index: u128,
+    ) -> Result<CallResponse> {
+        self.authorize()?;
+        self.record_disclosure(CREDITOR_DISCLOSURE, txid_high, txid_low, index);
+        self.refund_all_incoming()
+    }
+
+    /// Debitor discloses an identity attestation for their side of the position
+    fn set_debitor_disclosure(
+        &self,
+        txid_high: u128,
+        txid_low: u128,
+        index: u128,
+    ) -> Result<CallResponse> {
+        self.authorize()?;
+        self.record_disclosure(DEBITOR_DISCLOSURE, txid_high, txid_low, index);
+        self.refund_all_incoming()
+    }
+
     // ============ View Functions ============
 
     fn forward_incoming(&self) -> Result<CallResponse> {
@@ -501,6 +2252,201 @@ impl LendingContract {
         Ok(response)
     }
 
+    /// Get repayment quote breakdown at the current block
+    ///
+    /// Returns amount due, principal, interest, break fee, prepayment penalty
+    /// and late penalty (6 × u128). All fields are zero when no loan is active.
+    fn get_repayment_quote(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let state = self.state_value();
+        let mut data: Vec<u8> = Vec::new();
+        if state != STATE_LOAN_ACTIVE {
+            data.resize(96, 0);
+        } else {
+            let quote = self.quote_repayment()?;
+            data.extend_from_slice(&quote.amount_due()?.to_le_bytes());
+            data.extend_from_slice(&quote.principal.to_le_bytes());
+            data.extend_from_slice(&quote.interest.to_le_bytes());
+            data.extend_from_slice(&quote.break_fee.to_le_bytes());
+            data.extend_from_slice(&quote.prepayment_penalty.to_le_bytes());
+            data.extend_from_slice(&quote.late_penalty.to_le_bytes());
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get a page of the loan history log
+    ///
+    /// Returns the total entry count and page flags (2 × u128) followed by up
+    /// to `limit` entries starting at `offset`, each encoded as block, event
+    /// code and amount.
+    fn get_history(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        response.data = self.read_log(HISTORY_LOG, offset, limit);
+        Ok(response)
+    }
+
+    /// Get the transfer log
+    ///
+    /// Returns the total number of entries and page flags (2 × u128) followed
+    /// by up to `limit` entries from `offset`. Each entry is block, event code (EVENT_*),
+    /// sender role, recipient role (ROLE_*), token block, token tx and amount
+    /// (7 × u128, 112 bytes). The sender is the party whose escrow the
+    /// transfer is drawn from, or ROLE_CONTRACT for tokens the contract mints.
+    /// Every transfer out of escrow or minted by the contract is logged;
+    /// refunds of incoming tokens are not.
+    fn get_transfers(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.read_log(TRANSFER_LOG, offset, limit);
+        Ok(response)
+    }
+
+    /// Get the interest accrual log
+    ///
+    /// Returns the total number of checkpoints and page flags (2 × u128)
+    /// followed by up to `limit` checkpoints from `offset`, one per take, capitalization and
+    /// repayment. Each is block, principal, interest for the period ending at
+    /// that block (for a repayment: the interest charged) and cumulative
+    /// interest (4 × u128).
+    fn get_accrual_history(&self, offset: u128, limit: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.read_log(ACCRUAL_LOG, offset, limit);
+        Ok(response)
+    }
+
+    /// Get the realized yield of a closed loan, replayed from the history log
+    ///
+    /// Returns the outcome (0 = open, 1 = repaid, 2 = defaulted), the interest
+    /// received (repayment minus the principal lent), the blocks from take to
+    /// close and the annualized yield with the loan's APR precision (4 × u128). A
+    /// defaulted loan reports no interest: the creditor was paid in collateral.
+    fn get_realized_yield(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let pointer = StoragePointer::from_keyword(HISTORY_LOG);
+        let mut outcome = OUTCOME_OPEN;
+        let (mut lent, mut taken_at) = (0u128, 0u128);
+        let (mut received, mut closed_at) = (0u128, 0u128);
+        for index in 0..self.log_length(HISTORY_LOG) {
+            let entry = pointer.keyword(&format!("/{}", index)).get();
+            let block = Self::entry_field(&entry, 0);
+            let amount = Self::entry_field(&entry, 2);
+            match Self::entry_field(&entry, 1) {
+                EVENT_LOAN_TAKEN => (lent, taken_at) = (amount, block),
+                EVENT_LOAN_REPAID => (outcome, received, closed_at) = (OUTCOME_REPAID, amount, block),
+                EVENT_COLLATERAL_CLAIMED if outcome == OUTCOME_OPEN => {
+                    (outcome, closed_at) = (OUTCOME_DEFAULTED, block)
+                }
+                _ => {}
+            }
+        }
+
+        let (interest, held, annualized) = if outcome == OUTCOME_OPEN {
+            (0, 0, 0)
+        } else {
+            let interest = received.saturating_sub(lent);
+            let held = closed_at.saturating_sub(taken_at);
+            let annualized = math::precision::calculate_annualized_yield(
+                interest,
+                lent,
+                held,
+                self.interest_rate().apr_precision(),
+            )?;
+            (interest, held, annualized)
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&outcome.to_le_bytes());
+        data.extend_from_slice(&interest.to_le_bytes());
+        data.extend_from_slice(&held.to_le_bytes());
+        data.extend_from_slice(&annualized.to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the interest rate in both representations: per-block flag (0 = APR
+    /// quoted), rate as quoted, equivalent APR, equivalent per-block rate, APR
+    /// precision (5 × u128). Equivalents are rounded down; the quoted rate is
+    /// exact.
+    fn get_interest_rate(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let rate = self.interest_rate();
+        let per_block = match rate {
+            InterestRate::Annual { .. } => 0u128,
+            InterestRate::PerBlock(_) => 1u128,
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&per_block.to_le_bytes());
+        data.extend_from_slice(&self.apr().to_le_bytes());
+        data.extend_from_slice(&rate.as_annual()?.to_le_bytes());
+        data.extend_from_slice(&rate.as_per_block()?.to_le_bytes());
+        data.extend_from_slice(&rate.apr_precision().to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the debitor note id (zero id before the loan is taken)
+    fn get_debitor_note(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let note = self
+            .debitor_note()
+            .unwrap_or(AlkaneId { block: 0, tx: 0 });
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&note.block.to_le_bytes());
+        data.extend_from_slice(&note.tx.to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the creditor note id (zero id before the offer is created)
+    fn get_creditor_note(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let note = self.auth_token().unwrap_or(AlkaneId { block: 0, tx: 0 });
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&note.block.to_le_bytes());
+        data.extend_from_slice(&note.tx.to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the bond token id and total bond supply (zeros outside bond mode)
+    fn get_bond_token(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut data: Vec<u8> = Vec::new();
+        if self.is_bond_mode() {
+            let bond_token = self.bond_token()?;
+            data.extend_from_slice(&bond_token.block.to_le_bytes());
+            data.extend_from_slice(&bond_token.tx.to_le_bytes());
+            data.extend_from_slice(&self.bond_supply().to_le_bytes());
+        } else {
+            data.resize(48, 0);
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
     /// Get current state
     fn get_state(&self) -> Result<CallResponse> {
         let context = self.context()?;
@@ -510,24 +2456,413 @@ impl LendingContract {
     }
 
     /// Get time remaining until deadline
+    ///
+    /// Returns u128::MAX for an open-term loan that has not been called.
     fn get_time_remaining(&self) -> Result<CallResponse> {
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
 
+        response.data = self.blocks_remaining().to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Get the deadline countdown in one call
+    ///
+    /// Returns the (soft) repayment deadline, the current height, the blocks
+    /// remaining (as GetTimeRemaining), 1 if the loan has defaulted, else 0,
+    /// and the hard deadline after which collateral is claimable (5 × u128).
+    fn get_blocks_until_deadline(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let defaulted = match self.state_value() {
+            STATE_LOAN_DEFAULTED => true,
+            STATE_LOAN_ACTIVE => self.is_in_default(),
+            _ => false,
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&self.repayment_deadline().to_le_bytes());
+        data.extend_from_slice(&self.current_block().to_le_bytes());
+        data.extend_from_slice(&self.blocks_remaining().to_le_bytes());
+        data.extend_from_slice(&u128::from(defaulted).to_le_bytes());
+        data.extend_from_slice(&self.hard_deadline().to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the collateral price at which the position becomes liquidatable
+    ///
+    /// Returned in loan token units per collateral unit, scaled by 1e18: below
+    /// this price the escrowed collateral is worth less than the current debt.
+    fn get_liquidation_price(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
         let state = self.state_value();
         if state != STATE_LOAN_ACTIVE {
             response.data = 0u128.to_le_bytes().to_vec();
         } else {
-            let deadline = self.repayment_deadline();
-            let current_block = self.current_block();
-            if current_block >= deadline {
-                response.data = 0u128.to_le_bytes().to_vec();
-            } else {
-                let remaining = deadline - current_block;
-                response.data = remaining.to_le_bytes().to_vec();
+            let debt = self.calculate_repayment_amount()?;
+            let price = math::precision::calculate_liquidation_price(
+                debt,
+                self.collateral_amount(),
+            )?;
+            response.data = price.to_le_bytes().to_vec();
+        }
+
+        Ok(response)
+    }
+
+    /// Get the present value of the amount due at maturity, discounted at
+    /// `discount_apr`
+    ///
+    /// An offer is priced as if taken now. Zeros when there is no fixed
+    /// maturity to discount from: no open offer or active loan, or an
+    /// open-term loan.
+    fn get_present_value(&self, discount_apr: u128, apr_precision: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let apr_precision = if apr_precision == 0 { APR_PRECISION } else { apr_precision };
+        if !Self::is_valid_apr_precision(apr_precision) {
+            return Err(anyhow!("APR precision must be a power of ten from 1e4 to 1e12"));
+        }
+
+        let maturity = match self.state_value() {
+            _ if self.is_open_term() => None,
+            STATE_WAITING_FOR_DEBITOR_TAKE => {
+                let blocks = match self.repayment_deadline() {
+                    0 => self.duration_blocks(),
+                    deadline => deadline.saturating_sub(self.current_block()),
+                };
+                let amount = Self::compute_repayment(self.loan_amount(), self.interest_rate(), blocks)?;
+                Some((amount, blocks))
+            }
+            STATE_LOAN_ACTIVE => {
+                let amount = match self.fixed_repayment() {
+                    0 => Self::compute_repayment(
+                        self.loan_amount(),
+                        self.interest_rate(),
+                        self.duration_blocks(),
+                    )?,
+                    fixed_repayment => fixed_repayment,
+                };
+                Some((amount, self.blocks_remaining()))
+            }
+            _ => None,
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        match maturity {
+            Some((amount, blocks)) => {
+                let value = math::precision::calculate_present_value(
+                    amount,
+                    discount_apr,
+                    blocks,
+                    apr_precision,
+                )?;
+                let unit_value = math::precision::calculate_present_value(
+                    math::precision::PRECISION_MULTIPLIER,
+                    discount_apr,
+                    blocks,
+                    apr_precision,
+                )?;
+                for field in [amount, blocks, value, unit_value] {
+                    data.extend_from_slice(&field.to_le_bytes());
+                }
+            }
+            None => data.resize(64, 0),
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the per-opcode access table
+    ///
+    /// Returns one entry per opcode: opcode, required role (ROLE_*) and 1 if
+    /// the opcode is callable in the current state, else 0 (3 × u128 each).
+    fn get_permissions(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let state_bit = 1u128 << self.state_value();
+        let mut data: Vec<u8> = Vec::with_capacity(OPCODE_PERMISSIONS.len() * 48);
+        for permission in OPCODE_PERMISSIONS {
+            let allowed = u128::from(permission.states & state_bit != 0);
+            data.extend_from_slice(&permission.opcode.to_le_bytes());
+            data.extend_from_slice(&permission.role.to_le_bytes());
+            data.extend_from_slice(&allowed.to_le_bytes());
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the creditor auth token supply still in circulation
+    fn get_creditor_note_supply(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.creditor_note_supply().to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Get the status word: state in the low byte plus STATUS_* flags
+    fn get_status_word(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let state = self.state_value();
+        let overdue =
+            state == STATE_LOAN_ACTIVE && self.current_block() > self.repayment_deadline();
+        let in_default = state == STATE_LOAN_ACTIVE && self.is_in_default();
+        // Bond mode pays out through redemptions, never the creditor's claim
+        let bond_mode = self.is_bond_mode();
+        let bonds_outstanding = bond_mode && self.bonds_redeemed() < self.bond_supply();
+        let claimable = match state {
+            STATE_LOAN_ACTIVE => in_default,
+            STATE_LOAN_REPAID if bond_mode => bonds_outstanding,
+            STATE_LOAN_REPAID => self.creditor_note_supply() > 0,
+            STATE_LOAN_DEFAULTED => bonds_outstanding,
+            _ => false,
+        };
+
+        let mut status = state & STATUS_STATE_MASK;
+        if overdue {
+            status |= STATUS_OVERDUE;
+        }
+        if overdue && !in_default {
+            status |= STATUS_GRACE;
+        }
+        if claimable {
+            status |= STATUS_CLAIMABLE;
+        }
+
+        response.data = status.to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Get default notice details
+    ///
+    /// Returns the configured notice period, the block notice was given at and
+    /// the last block before the collateral becomes claimable (3 × u128; the
+    /// last two are 0 while no notice has been given).
+    fn get_default_notice(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let notice_blocks = self.default_notice_blocks();
+        let notice_block = self.default_notice_block();
+        let claimable_after = if notice_block == 0 {
+            0
+        } else {
+            notice_block.saturating_add(notice_blocks)
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&notice_blocks.to_le_bytes());
+        data.extend_from_slice(&notice_block.to_le_bytes());
+        data.extend_from_slice(&claimable_after.to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the collateral and loan token supplies cached at init (2 × u128)
+    fn get_token_supplies(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&self.collateral_token_supply().to_le_bytes());
+        data.extend_from_slice(&self.loan_token_supply().to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the ABI exported by the MessageDispatch derive (JSON bytes)
+    fn get_abi(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = <LendingContractMessage as MessageDispatch<LendingContract>>::export_abi();
+        Ok(response)
+    }
+
+    /// Get the contract version baked in at build time
+    fn get_version(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = CONTRACT_VERSION.as_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Get the position labels: creditor label, debitor label (2 × u128)
+    fn get_labels(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&self.creditor_label_value().to_le_bytes());
+        data.extend_from_slice(&self.debitor_label_value().to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the disclosure pointers: creditor txid high, txid low, index, then
+    /// the same for the debitor (6 × u128, zeros where nothing is disclosed)
+    fn get_disclosures(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut data: Vec<u8> = Vec::new();
+        for keyword in [CREDITOR_DISCLOSURE, DEBITOR_DISCLOSURE] {
+            let mut pointer = StoragePointer::from_keyword(keyword).get().as_ref().clone();
+            pointer.resize(DISCLOSURE_SIZE, 0);
+            data.extend_from_slice(&pointer);
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the reservation: note block and tx (zeros if never reserved), the
+    /// block the reservation holds the offer until, the deposit held for it
+    /// and the forfeited deposits owed to the creditor (5 × u128)
+    fn get_reservation(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let note = self
+            .reservation_note()
+            .unwrap_or(AlkaneId { block: 0, tx: 0 });
+        let mut held = self.held_deposit();
+        let mut forfeited = self.forfeited_deposits();
+        if held != 0 && self.current_block() > self.reserved_until() {
+            forfeited = forfeited.saturating_add(held);
+            held = 0;
+        }
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&note.block.to_le_bytes());
+        data.extend_from_slice(&note.tx.to_le_bytes());
+        data.extend_from_slice(&self.reserved_until().to_le_bytes());
+        data.extend_from_slice(&held.to_le_bytes());
+        data.extend_from_slice(&forfeited.to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the offer visibility: 1 if private else 0, then the allowed
+    /// taker block and tx (3 × u128)
+    fn get_allowed_taker(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let allowed_taker = self.allowed_taker().unwrap_or(PUBLIC_OFFER);
+        let private = (allowed_taker != PUBLIC_OFFER) as u128;
+
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&private.to_le_bytes());
+        data.extend_from_slice(&allowed_taker.block.to_le_bytes());
+        data.extend_from_slice(&allowed_taker.tx.to_le_bytes());
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the state root: sha256 over every storage variable, the
+    /// disclosure pointers and the logs in a fixed order (32 bytes). It is
+    /// derived from storage on each call, so it always reflects the latest
+    /// mutation
+    fn get_state_root(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut state: Vec<u8> = Vec::new();
+        let values = [
+            self.state_value(),
+            self.collateral_amount(),
+            self.collateral_token_supply(),
+            self.loan_amount(),
+            self.loan_token_supply(),
+            self.duration_blocks(),
+            self.apr(),
+            self.apr_precision(),
+            self.loan_flags(),
+            self.break_fee_bps(),
+            self.prepayment_penalty_bps(),
+            self.prepayment_lockout_blocks(),
+            self.default_notice_blocks(),
+            self.max_take_delay_blocks(),
+            self.grace_blocks(),
+            self.late_penalty_bps(),
+            self.reservation_deposit(),
+            self.reservation_blocks(),
+            self.offer_refresh_block(),
+            self.reserved_until(),
+            self.held_deposit(),
+            self.forfeited_deposits(),
+            self.loan_start_block(),
+            self.repayment_deadline(),
+            self.accrual_start_block(),
+            self.default_notice_block(),
+            self.capitalization_extension(),
+            self.bond_supply(),
+            self.repaid_amount(),
+            self.total_interest_accrued(),
+            self.fixed_repayment(),
+            self.creditor_note_supply(),
+            self.creditor_label_value(),
+            self.debitor_label_value(),
+        ];
+        let ids = [
+            self.collateral_token(),
+            self.loan_token(),
+            self.allowed_taker(),
+            self.reservation_note(),
+            self.debitor_note(),
+            self.bond_token(),
+        ];
+        for value in values {
+            state.extend_from_slice(&value.to_le_bytes());
+        }
+        // Unset ids are encoded as 0:0
+        for id in ids {
+            let id = id.unwrap_or(AlkaneId { block: 0, tx: 0 });
+            state.extend_from_slice(&id.block.to_le_bytes());
+            state.extend_from_slice(&id.tx.to_le_bytes());
+        }
+        for keyword in [CREDITOR_DISCLOSURE, DEBITOR_DISCLOSURE] {
+            let mut pointer = StoragePointer::from_keyword(keyword).get().as_ref().clone();
+            pointer.resize(DISCLOSURE_SIZE, 0);
+            state.extend_from_slice(&pointer);
+        }
+        // Entries are fixed-size per log, so the length prefix keeps the
+        // encoding unambiguous
+        for log in [HISTORY_LOG, TRANSFER_LOG, ACCRUAL_LOG] {
+            let pointer = StoragePointer::from_keyword(log);
+            let length = self.log_length(log);
+            state.extend_from_slice(&length.to_le_bytes());
+            for index in 0..length {
+                state.extend_from_slice(pointer.keyword(&format!("/{}", index)).get().as_ref());
             }
         }
 
+        response.data = sha256::Hash::hash(&state).to_byte_array().to_vec();
+        Ok(response)
+    }
+
+    /// Get the current block height as seen by the contract (u128)
+    fn get_current_height(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        response.data = self.current_block().to_le_bytes().to_vec();
         Ok(response)
     }
 
@@ -1 +1,2 @@
-pub mod precision;
\ No newline at end of file
+pub mod precision;
+pub mod repayment;
\ No newline at end of file
@@ -1,11 +1,11 @@
 use anyhow::{anyhow, Result};
+use ruint::aliases::U256;
 
-/// Precision multiplier for internal calculations (1e18)
-/// This allows for 18 decimal places of precision during interest calculations
-/// to prevent rounding errors on small loan amounts or short durations.
+/// Precision multiplier for fixed-point values (1e18): per-block rates and
+/// liquidation prices carry 18 decimal places.
 pub const PRECISION_MULTIPLIER: u128 = 1_000_000_000_000_000_000;
 
-/// APR precision from contract (10000 = 100.00%)
+/// Default APR precision (10000 = 100.00%)
 pub const APR_PRECISION: u128 = 10_000;
 
 /// Blocks per year constant
@@ -13,61 +13,190 @@ pub const BLOCKS_PER_YEAR: u128 = 52_560;
 
 /// Calculate interest with high precision
 ///
-/// Formula: (principal * apr * duration * PRECISION_MULTIPLIER) / (APR_PRECISION * BLOCKS_PER_YEAR) / PRECISION_MULTIPLIER
+/// Formula: principal * (apr * duration) / (apr_precision * BLOCKS_PER_YEAR)
 ///
-/// This prevents rounding to zero for small loans where:
-/// (principal * apr * duration) < (APR_PRECISION * BLOCKS_PER_YEAR)
+/// The product with the principal is taken in 256 bits, so the result is the
+/// exact floor of the interest whenever it fits in u128, however large the
+/// principal or fine the APR precision.
 pub fn calculate_interest_precise(
     principal: u128,
     apr: u128,
     duration: u128,
+    apr_precision: u128,
 ) -> Result<u128> {
-    // First multiply by precision to keep significant digits
-    // We use u128, so we need to be careful about overflow
-    // principal * apr * duration * PRECISION_MULTIPLIER
-    
-    // Check if we can do the multiplication without overflow
-    // If principal is large, we might need to be careful
-    
-    // Alternative ordering to maximize precision while minimizing overflow risk:
-    // 1. (principal * apr)
-    // 2. Multiply by PRECISION_MULTIPLIER
-    // 3. Multiply by duration
-    // 4. Divide by denominator
-    // 5. Divide by PRECISION_MULTIPLIER
-    
-    // However, with u128, we have ~3.4e38 space.
-    // PRECISION_MULTIPLIER is 1e18.
-    // So we have ~3.4e20 space left for (principal * apr * duration).
-    // If principal is 1e13 (10T), apr is 1e4, duration is 1e5, product is 1e22.
-    // This would overflow u128 if we just multiply everything.
-    
-    // We need a safer way to handle this.
-    // If the product would overflow, we can skip the precision multiplier
-    // because if it's that large, rounding errors aren't significant.
-    
-    let numerator_part = principal
-        .checked_mul(apr)
-        .ok_or_else(|| anyhow!("Overflow in interest calculation"))?
+    let rate = apr
+        .checked_mul(duration)
+        .ok_or_else(|| anyhow!("Overflow in interest calculation"))?;
+
+    let denominator = apr_precision
+        .checked_mul(BLOCKS_PER_YEAR)
+        .ok_or_else(|| anyhow!("Overflow in interest calculation"))?;
+    if denominator == 0 {
+        return Err(anyhow!("Division error"));
+    }
+
+    mul_div(principal, rate, denominator)
+        .ok_or_else(|| anyhow!("Overflow in interest calculation"))
+}
+
+/// Per-block rate precision (1e18 = 100% of principal per block)
+pub const PER_BLOCK_RATE_PRECISION: u128 = PRECISION_MULTIPLIER;
+
+/// Interest rate of a loan, in the representation the creditor quoted it in
+#[derive(Clone, Copy)]
+pub enum InterestRate {
+    /// Annualized rate with the loan's APR precision (APR_PRECISION unless it
+    /// chose a finer one), spread over BLOCKS_PER_YEAR
+    Annual { apr: u128, precision: u128 },
+    /// Rate per block with PER_BLOCK_RATE_PRECISION
+    PerBlock(u128),
+}
+
+impl InterestRate {
+    /// Interest on `principal` over `duration` blocks
+    pub fn interest(self, principal: u128, duration: u128) -> Result<u128> {
+        match self {
+            InterestRate::Annual { apr, precision } => {
+                calculate_interest_precise(principal, apr, duration, precision)
+            }
+            InterestRate::PerBlock(rate) => calculate_interest_per_block(principal, rate, duration),
+        }
+    }
+
+    /// Precision of the annualized rates reported for this rate: the loan's
+    /// own for an APR, the default for a per-block rate
+    pub fn apr_precision(self) -> u128 {
+        match self {
+            InterestRate::Annual { precision, .. } => precision,
+            InterestRate::PerBlock(_) => APR_PRECISION,
+        }
+    }
+
+    /// Equivalent annualized rate with `apr_precision` (rounded down)
+    pub fn as_annual(self) -> Result<u128> {
+        match self {
+            InterestRate::Annual { apr, .. } => Ok(apr),
+            InterestRate::PerBlock(rate) => rate
+                .checked_mul(APR_PRECISION * BLOCKS_PER_YEAR)
+                .map(|scaled| scaled / PER_BLOCK_RATE_PRECISION)
+                .ok_or_else(|| anyhow!("Overflow in rate conversion")),
+        }
+    }
+
+    /// Equivalent per-block rate with PER_BLOCK_RATE_PRECISION (rounded down)
+    pub fn as_per_block(self) -> Result<u128> {
+        match self {
+            InterestRate::Annual { apr, precision } => apr
+                .checked_mul(PER_BLOCK_RATE_PRECISION)
+                .zip(precision.checked_mul(BLOCKS_PER_YEAR))
+                .map(|(scaled, denominator)| scaled / denominator)
+                .ok_or_else(|| anyhow!("Overflow in rate conversion")),
+            InterestRate::PerBlock(rate) => Ok(rate),
+        }
+    }
+}
+
+/// Calculate interest for a per-block rate without any annualization
+///
+/// Formula: principal * duration * rate / PER_BLOCK_RATE_PRECISION, rounded down.
+/// The principal-blocks are split around the precision so the multiplication
+/// by the rate only overflows when the interest itself would.
+pub fn calculate_interest_per_block(
+    principal: u128,
+    rate: u128,
+    duration: u128,
+) -> Result<u128> {
+    let exposure = principal
         .checked_mul(duration)
         .ok_or_else(|| anyhow!("Overflow in interest calculation"))?;
-        
-    let denominator = APR_PRECISION * BLOCKS_PER_YEAR;
-    
-    // Try high precision first
-    if let Some(scaled_numerator) = numerator_part.checked_mul(PRECISION_MULTIPLIER) {
-        
-        let scaled_interest = scaled_numerator
-            .checked_div(denominator)
-            .ok_or_else(|| anyhow!("Division error"))?;
-            
-        Ok(scaled_interest / PRECISION_MULTIPLIER)
+
+    let whole = (exposure / PER_BLOCK_RATE_PRECISION)
+        .checked_mul(rate)
+        .ok_or_else(|| anyhow!("Overflow in interest calculation"))?;
+    let fraction = (exposure % PER_BLOCK_RATE_PRECISION)
+        .checked_mul(rate)
+        .ok_or_else(|| anyhow!("Overflow in interest calculation"))?
+        / PER_BLOCK_RATE_PRECISION;
+
+    whole
+        .checked_add(fraction)
+        .ok_or_else(|| anyhow!("Overflow in interest calculation"))
+}
+
+/// Annualize a realized return: `interest` earned on `principal` over
+/// `blocks` blocks, with `apr_precision` (a return within one block counts as
+/// one block)
+pub fn calculate_annualized_yield(
+    interest: u128,
+    principal: u128,
+    blocks: u128,
+    apr_precision: u128,
+) -> Result<u128> {
+    if principal == 0 {
+        return Err(anyhow!("Division error"));
+    }
+
+    // Dividing by the principal and then by the blocks floors the same as
+    // dividing by their product, which need not fit in u128
+    mul_div(interest, apr_precision * BLOCKS_PER_YEAR, principal)
+        .map(|per_block| per_block / blocks.max(1))
+        .ok_or_else(|| anyhow!("Overflow in yield calculation"))
+}
+
+
+/// Discount `amount` due in `blocks` blocks at `discount_apr` (with
+/// `apr_precision`), using simple interest the way loans accrue it
+///
+/// Formula: amount * apr_precision * BLOCKS_PER_YEAR
+///          / (apr_precision * BLOCKS_PER_YEAR + discount_apr * blocks), rounded down
+pub fn calculate_present_value(
+    amount: u128,
+    discount_apr: u128,
+    blocks: u128,
+    apr_precision: u128,
+) -> Result<u128> {
+    let year = apr_precision
+        .checked_mul(BLOCKS_PER_YEAR)
+        .filter(|year| *year != 0)
+        .ok_or_else(|| anyhow!("Division error"))?;
+    let denominator = discount_apr
+        .checked_mul(blocks)
+        .and_then(|discount| discount.checked_add(year))
+        .ok_or_else(|| anyhow!("Overflow in present value calculation"))?;
+
+    if let Some(scaled) = amount.checked_mul(year) {
+        Ok(scaled / denominator)
     } else {
-        // If high precision overflows, fallback to standard calculation
-        // since the numbers are large enough that precision loss is negligible
-        numerator_part
-            .checked_div(denominator)
-            .ok_or_else(|| anyhow!("Division error"))
+        // Split the amount around the denominator so only the remainder is
+        // scaled; the result never exceeds the amount
+        let whole = (amount / denominator) * year;
+        (amount % denominator)
+            .checked_mul(year)
+            .map(|scaled| whole + scaled / denominator)
+            .ok_or_else(|| anyhow!("Overflow in present value calculation"))
+    }
+}
+
+/// Calculate `amount * numerator / denominator` rounded down, with the
+/// product in 256 bits so it only overflows when the result does. None on
+/// overflow or a zero denominator.
+pub fn mul_div(amount: u128, numerator: u128, denominator: u128) -> Option<u128> {
+    if denominator == 0 {
+        return None;
     }
+    let result = U256::from(amount) * U256::from(numerator) / U256::from(denominator);
+    u128::try_from(result).ok()
 }
 
+/// Calculate the collateral price at which `collateral` is worth exactly `debt`
+///
+/// The price is expressed in loan token units per collateral unit, scaled by
+/// PRECISION_MULTIPLIER. Below this price the position is under-collateralized.
+pub fn calculate_liquidation_price(debt: u128, collateral: u128) -> Result<u128> {
+    if collateral == 0 {
+        return Err(anyhow!("Division error"));
+    }
+
+    mul_div(debt, PRECISION_MULTIPLIER, collateral)
+        .ok_or_else(|| anyhow!("Overflow in liquidation price calculation"))
+}
@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+
+use super::precision::InterestRate;
+
+/// Basis-point precision for fee parameters (10000 = 100.00%)
+pub const BPS_PRECISION: u128 = 10_000;
+
+/// Take `bps` basis points of `amount`
+pub fn apply_bps(amount: u128, bps: u128) -> Result<u128> {
+    amount
+        .checked_mul(bps)
+        .map(|scaled| scaled / BPS_PRECISION)
+        .ok_or_else(|| anyhow!("Overflow in fee calculation"))
+}
+
+/// Breakdown of the amount owed to close a loan at a given block
+pub struct RepaymentQuote {
+    pub principal: u128,
+    pub interest: u128,
+    pub break_fee: u128,
+    pub prepayment_penalty: u128,
+    pub late_penalty: u128,
+}
+
+impl RepaymentQuote {
+    /// Total amount due: principal + interest + fees and penalties
+    pub fn amount_due(&self) -> Result<u128> {
+        self.principal
+            .checked_add(self.interest)
+            .and_then(|amount| amount.checked_add(self.break_fee))
+            .and_then(|amount| amount.checked_add(self.prepayment_penalty))
+            .and_then(|amount| amount.checked_add(self.late_penalty))
+            .ok_or_else(|| anyhow!("Overflow adding interest to principal"))
+    }
+}
+
+/// Quote a repayment charging interest for the full loan duration
+pub fn quote_full_term_repayment(
+    principal: u128,
+    rate: InterestRate,
+    duration: u128,
+) -> Result<RepaymentQuote> {
+    Ok(RepaymentQuote {
+        principal,
+        interest: rate.interest(principal, duration)?,
+        break_fee: 0,
+        prepayment_penalty: 0,
+        late_penalty: 0,
+    })
+}
+
+/// Quote a repayment for an open-term loan: interest accrues per block for
+/// the `elapsed` blocks since the loan was taken, with no term to cap it
+pub fn quote_open_term_repayment(
+    principal: u128,
+    rate: InterestRate,
+    elapsed: u128,
+) -> Result<RepaymentQuote> {
+    Ok(RepaymentQuote {
+        principal,
+        interest: rate.interest(principal, elapsed)?,
+        break_fee: 0,
+        prepayment_penalty: 0,
+        late_penalty: 0,
+    })
+}
+
+/// Quote a repayment under the early repayment rebate schedule
+///
+/// Interest is only charged for the `elapsed` blocks (capped at `duration`).
+/// The borrower additionally pays `break_fee_bps` of the interest that is
+/// rebated, so an early repayment never costs more than a full-term one.
+pub fn quote_early_repayment(
+    principal: u128,
+    rate: InterestRate,
+    duration: u128,
+    elapsed: u128,
+    break_fee_bps: u128,
+) -> Result<RepaymentQuote> {
+    let accrued_interest = rate.interest(principal, elapsed.min(duration))?;
+    let rebate = calculate_remaining_interest(principal, rate, duration, elapsed)?;
+
+    Ok(RepaymentQuote {
+        principal,
+        interest: accrued_interest,
+        break_fee: apply_bps(rebate, break_fee_bps)?,
+        prepayment_penalty: 0,
+        late_penalty: 0,
+    })
+}
+
+/// Interest for the part of the loan duration that has not yet elapsed
+pub fn calculate_remaining_interest(
+    principal: u128,
+    rate: InterestRate,
+    duration: u128,
+    elapsed: u128,
+) -> Result<u128> {
+    let full_interest = rate.interest(principal, duration)?;
+    let accrued_interest = rate.interest(principal, elapsed.min(duration))?;
+    full_interest
+        .checked_sub(accrued_interest)
+        .ok_or_else(|| anyhow!("Accrued interest exceeds full-term interest"))
+}
+
+/// Prepayment penalty: `penalty_bps` of the interest remaining after `elapsed` blocks
+pub fn calculate_prepayment_penalty(
+    principal: u128,
+    rate: InterestRate,
+    duration: u128,
+    elapsed: u128,
+    penalty_bps: u128,
+) -> Result<u128> {
+    apply_bps(
+        calculate_remaining_interest(principal, rate, duration, elapsed)?,
+        penalty_bps,
+    )
+}
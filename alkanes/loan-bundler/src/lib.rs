@@ -0,0 +1,854 @@
+//! Loan bundler: pools the creditor notes of active lending contracts and
+//! issues fungible bundle shares against them
+//!
+//! The sponsor opens a bundle for one loan token and, during the join
+//! window, deposits creditor notes of active loans lent in that token, minting
+//! one share per unit of principal. Shares are the bundler's own token and
+//! trade freely. Anyone can collect a bundled loan once it is repaid (the
+//! repayment) or claimable on default (the collateral); the proceeds are held
+//! by the bundle. The bundle holds the creditor notes, so the sponsor acts as
+//! creditor where a loan needs one before it can default: calling an
+//! open-term loan, or giving notice of default. Once the join window has
+//! closed, share holders exit with their pro-rata part of every collection
+//! made so far. Shares redeemed before every loan is collected also receive
+//! an exit note, which claims their part of each later collection.
+//!
+//! The bundler cannot tell a genuine lending contract from one that only
+//! answers its views, so only the sponsor may join loans: holders trust the
+//! sponsor's selection the way they trust its choice of terms.
+
+use alkanes_runtime::{declare_alkane, message::MessageDispatch, runtime::AlkaneResponder, storage::StoragePointer};
+
+#[allow(unused_imports)]
+use alkanes_runtime::{
+    println,
+    stdio::{stdout, Write},
+};
+use alkanes_macros::storage_variable;
+use alkanes_support::{
+    cellpack::Cellpack,
+    constants::AUTH_TOKEN_FACTORY_ID,
+    id::AlkaneId,
+    parcel::{AlkaneTransfer, AlkaneTransferParcel},
+    response::CallResponse,
+};
+use anyhow::{anyhow, Result};
+use metashrew_support::compat::to_arraybuffer_layout;
+use metashrew_support::index_pointer::KeyValuePointer;
+use ruint::aliases::U256;
+use std::sync::Arc;
+
+/// Lending contract opcodes the bundler calls
+const LENDING_CLAIM_DEFAULTED_COLLATERAL: u128 = 3;
+const LENDING_CLAIM_REPAYMENT: u128 = 5;
+const LENDING_CALL_LOAN: u128 = 9;
+const LENDING_NOTICE_OF_DEFAULT: u128 = 10;
+const LENDING_GET_LOAN_DETAILS: u128 = 90;
+const LENDING_GET_STATE: u128 = 92;
+const LENDING_GET_BOND_TOKEN: u128 = 98;
+const LENDING_GET_CREDITOR_NOTE: u128 = 119;
+const LENDING_GET_PRESENT_VALUE: u128 = 120;
+
+/// GetPresentValue field holding the present value of the creditor's claim
+const PRESENT_VALUE: usize = 2;
+
+/// Fixed-point scale of per-share values (1e18)
+const SHARE_VALUE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Lending contract states the bundler acts on
+const LENDING_STATE_ACTIVE: u128 = 2;
+const LENDING_STATE_REPAID: u128 = 3;
+
+/// GetLoanDetails fields (u128 index): state, collateral token (2),
+/// collateral amount, loan token (2), loan amount, ...
+const DETAILS_STATE: usize = 0;
+const DETAILS_LOAN_TOKEN_BLOCK: usize = 4;
+const DETAILS_LOAN_TOKEN_TX: usize = 5;
+const DETAILS_LOAN_AMOUNT: usize = 6;
+
+/// Bundled loans, one entry per join: lending contract (2), creditor note
+/// (2), shares minted, collected flag (6 × u128)
+const LOANS: &str = "/loans";
+const LOAN_COLLECTED: usize = 5;
+
+/// Tokens held for share holders, one entry per token: token (2), amount
+/// (3 × u128)
+const HOLDINGS: &str = "/holdings";
+const HOLDING_AMOUNT: usize = 2;
+
+/// Proceeds of every collection, one entry per token received: token (2),
+/// amount (3 × u128). Each share is owed its part of every entry
+const COLLECTIONS: &str = "/collections";
+const COLLECTION_AMOUNT: usize = 2;
+
+/// Exits made before every loan was collected, one entry per exit: exit note
+/// (2), shares redeemed, first collection not yet paid, settled flag
+/// (5 × u128)
+const EXITS: &str = "/exits";
+const EXIT_SHARES: usize = 2;
+const EXIT_NEXT_COLLECTION: usize = 3;
+const EXIT_SETTLED: usize = 4;
+
+#[derive(MessageDispatch)]
+pub enum LoanBundlerMessage {
+    /// Sponsor opens a bundle of loans lent in `loan_token` that accepts
+    /// loans for `join_blocks` blocks; returns the sponsor note
+    #[opcode(0)]
+    Initialize { loan_token: AlkaneId, join_blocks: u128 },
+
+    /// Sponsor bundles an active loan by depositing its creditor note, and
+    /// receives one share per unit of principal lent
+    /// Expects the sponsor note and the creditor note to be sent
+    /// with this call
+    #[opcode(1)]
+    Join { lending: AlkaneId },
+
+    /// Collect a bundled loan once it is repaid or claimable on default,
+    /// adding the repayment or collateral to the holdings
+    #[opcode(2)]
+    Collect { index: u128 },
+
+    /// Redeem shares for their pro-rata part of every collection made so
+    /// far, once the join window has closed. While loans are still
+    /// outstanding, also returns an exit note for ClaimExit
+    /// Expects bundle shares to be sent with this call
+    #[opcode(3)]
+    Exit,
+
+    /// Sponsor calls a bundled open-term loan, starting its notice period
+    /// Expects the sponsor note to be sent with this call
+    #[opcode(4)]
+    CallLoan { index: u128 },
+
+    /// Sponsor gives notice of default on a bundled loan past its deadline
+    /// Expects the sponsor note to be sent with this call
+    #[opcode(5)]
+    NoticeOfDefault { index: u128 },
+
+    /// Pay an early exit its part of the collections made since it last
+    /// claimed; the note is kept once every loan is collected
+    /// Expects the exit's note to be sent with this call
+    #[opcode(6)]
+    ClaimExit { index: u128 },
+
+    /// Get the bundle: loan token, sponsor note, last join block, loans
+    /// bundled, loans collected, share supply
+    #[opcode(90)]
+    #[returns(Vec<u8>)]
+    GetBundle,
+
+    /// Get a bundled loan: lending contract, creditor note, shares minted,
+    /// collected flag
+    #[opcode(91)]
+    #[returns(Vec<u8>)]
+    GetLoan { index: u128 },
+
+    /// Get the holdings: count, then token and amount of each
+    #[opcode(92)]
+    #[returns(Vec<u8>)]
+    GetHoldings,
+
+    /// Get the indicative present value of the bundle at a discount rate
+    /// given as an APR with `apr_precision` (0 = 10000): uncollected loans,
+    /// their present value, loan token held, total, value per share (scaled
+    /// by 1e18)
+    #[opcode(93)]
+    #[returns(Vec<u8>)]
+    GetPresentValue { discount_apr: u128, apr_precision: u128 },
+
+    /// Get an early exit: exit note, shares redeemed, first collection not
+    /// yet paid, settled flag
+    #[opcode(94)]
+    #[returns(Vec<u8>)]
+    GetExit { index: u128 },
+
+    /// Get the share supply, so shares can be used where a token's supply is
+    /// queried (e.g. as lending collateral)
+    #[opcode(101)]
+    #[returns(u128)]
+    GetTotalSupply,
+}
+
+#[derive(Default)]
+pub struct LoanBundler();
+
+impl AlkaneResponder for LoanBundler {}
+
+impl LoanBundler {
+    // ============ Storage Variables (using alkanes-macros) ============
+
+    storage_variable!(loan_token: AlkaneId);
+    // Held by the sponsor; the bundle's own token is its shares
+    storage_variable!(sponsor_note: AlkaneId);
+    // Last block loans can be joined
+    storage_variable!(join_end_block: u128);
+    storage_variable!(share_supply: u128);
+    // Shares ever minted; redeemed shares keep their part of each collection
+    storage_variable!(issued_shares: u128);
+    storage_variable!(collected_count: u128);
+    // Early exits not yet settled
+    storage_variable!(open_exits: u128);
+
+    // ============ Helper Functions ============
+
+    fn current_block(&self) -> u128 {
+        self.height() as u128
+    }
+
+    fn list_length(&self, list: &str) -> u128 {
+        StoragePointer::from_keyword(list).keyword("/length").get_value::<u128>()
+    }
+
+    fn list_entry(&self, list: &str, index: u128) -> Vec<u8> {
+        StoragePointer::from_keyword(list)
+            .keyword(&format!("/{}", index))
+            .get()
+            .as_ref()
+            .clone()
+    }
+
+    fn set_list_entry(&self, list: &str, index: u128, entry: Vec<u8>) {
+        StoragePointer::from_keyword(list)
+            .keyword(&format!("/{}", index))
+            .set(Arc::new(entry));
+    }
+
+    fn push_list_entry(&self, list: &str, entry: Vec<u8>) {
+        let index = self.list_length(list);
+        self.set_list_entry(list, index, entry);
+        StoragePointer::from_keyword(list).keyword("/length").set_value::<u128>(index + 1);
+    }
+
+    /// Read the u128 at `field` of a fixed-size entry or response
+    fn field(data: &[u8], field: usize) -> Option<u128> {
+        data.get(field * 16..(field + 1) * 16)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u128::from_le_bytes)
+    }
+
+    fn set_field(data: &mut [u8], field: usize, value: u128) {
+        data[field * 16..(field + 1) * 16].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn entry_id(entry: &[u8], field: usize) -> AlkaneId {
+        AlkaneId {
+            block: Self::field(entry, field).unwrap_or(0),
+            tx: Self::field(entry, field + 1).unwrap_or(0),
+        }
+    }
+
+    /// Compute `amount * numerator / denominator` rounded down, with the
+    /// product in 256 bits so it cannot overflow
+    fn mul_div(amount: u128, numerator: u128, denominator: u128) -> Result<u128> {
+        if denominator == 0 {
+            return Err(anyhow!("Division error"));
+        }
+        let scaled = U256::from(amount) * U256::from(numerator) / U256::from(denominator);
+        u128::try_from(scaled).map_err(|_| anyhow!("Overflow computing share value"))
+    }
+
+    /// Require the sponsor note among incoming alkanes
+    fn only_sponsor(&self) -> Result<()> {
+        let context = self.context()?;
+        let sponsor_note = self.sponsor_note()?;
+        if !context
+            .incoming_alkanes
+            .0
+            .iter()
+            .any(|transfer| transfer.id == sponsor_note && transfer.value > 0)
+        {
+            return Err(anyhow!("Sponsor note is not in incoming alkanes"));
+        }
+        Ok(())
+    }
+
+    /// Mint a single note through the auth token factory
+    fn mint_note(&self) -> Result<AlkaneTransfer> {
+        let cellpack = Cellpack {
+            target: AlkaneId {
+                block: 6,
+                tx: AUTH_TOKEN_FACTORY_ID,
+            },
+            inputs: vec![0, 1],
+        };
+        let response = self
+            .call(&cellpack, &AlkaneTransferParcel::default(), self.fuel())
+            .map_err(|e| Self::extcall_error("Unable to mint from auth token factory", &cellpack, e))?;
+        response
+            .alkanes
+            .0
+            .first()
+            .cloned()
+            .ok_or_else(|| anyhow!("Auth token factory returned no tokens"))
+    }
+
+    /// Mint the sponsor note
+    fn mint_sponsor_note(&self) -> Result<AlkaneTransfer> {
+        let note = self.mint_note()?;
+        self.set_sponsor_note(note.id);
+        Ok(note)
+    }
+
+    /// Add a transfer to an outgoing parcel, merging it into an existing entry
+    /// for the same token
+    fn pay_merged(parcel: &mut AlkaneTransferParcel, transfer: AlkaneTransfer) -> Result<()> {
+        match parcel.0.iter_mut().find(|pending| pending.id == transfer.id) {
+            Some(pending) => {
+                pending.value = pending
+                    .value
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+            }
+            None => parcel.pay(transfer),
+        }
+        Ok(())
+    }
+
+    /// Prefix a failed extcall's error with what the bundler was doing and
+    /// the cellpack it sent
+    fn extcall_error(action: &str, cellpack: &Cellpack, error: anyhow::Error) -> anyhow::Error {
+        anyhow!(
+            "{}: extcall to {}:{} opcode {} failed: {}",
+            action,
+            cellpack.target.block,
+            cellpack.target.tx,
+            cellpack.inputs.first().copied().unwrap_or_default(),
+            error
+        )
+    }
+
+    /// Call a lending contract view and return its data
+    fn query_lending(&self, lending: &AlkaneId, opcode: u128) -> Result<Vec<u8>> {
+        self.query_lending_with_inputs(lending, vec![opcode])
+    }
+
+    /// Call a lending contract view taking arguments; `inputs` starts with
+    /// the opcode
+    fn query_lending_with_inputs(&self, lending: &AlkaneId, inputs: Vec<u128>) -> Result<Vec<u8>> {
+        let cellpack = Cellpack {
+            target: *lending,
+            inputs,
+        };
+        self.staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel())
+            .map(|response| response.data)
+            .map_err(|e| Self::extcall_error("Unable to query lending contract", &cellpack, e))
+    }
+
+    /// Read the u128 at `field` of a lending view response
+    fn lending_field(data: &[u8], field: usize) -> Result<u128> {
+        Self::field(data, field).ok_or_else(|| anyhow!("Unexpected lending contract response"))
+    }
+
+    /// Read the entry of a bundled loan that has not been collected yet
+    fn uncollected_loan(&self, index: u128) -> Result<Vec<u8>> {
+        if index >= self.list_length(LOANS) {
+            return Err(anyhow!("No bundled loan at index {}", index));
+        }
+        let entry = self.list_entry(LOANS, index);
+        if Self::field(&entry, LOAN_COLLECTED).unwrap_or(0) != 0 {
+            return Err(anyhow!("Loan already collected"));
+        }
+        Ok(entry)
+    }
+
+    /// Call `opcode` on a bundled loan's lending contract as its creditor,
+    /// sending the creditor note; returns the lending contract's response
+    fn call_as_creditor(&self, entry: &[u8], opcode: u128, action: &str) -> Result<CallResponse> {
+        let cellpack = Cellpack {
+            target: Self::entry_id(entry, 0),
+            inputs: vec![opcode],
+        };
+        let mut parcel = AlkaneTransferParcel::default();
+        parcel.pay(AlkaneTransfer {
+            id: Self::entry_id(entry, 2),
+            value: 1,
+        });
+        self.call(&cellpack, &parcel, self.fuel())
+            .map_err(|e| Self::extcall_error(action, &cellpack, e))
+    }
+
+    /// Add tokens to the holdings
+    fn add_holding(&self, transfer: AlkaneTransfer) -> Result<()> {
+        let count = self.list_length(HOLDINGS);
+        for index in 0..count {
+            let mut entry = self.list_entry(HOLDINGS, index);
+            if Self::entry_id(&entry, 0) == transfer.id {
+                let amount = Self::field(&entry, HOLDING_AMOUNT)
+                    .unwrap_or(0)
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+                Self::set_field(&mut entry, HOLDING_AMOUNT, amount);
+                self.set_list_entry(HOLDINGS, index, entry);
+                return Ok(());
+            }
+        }
+
+        let mut entry: Vec<u8> = Vec::with_capacity(48);
+        entry.extend_from_slice(&transfer.id.block.to_le_bytes());
+        entry.extend_from_slice(&transfer.id.tx.to_le_bytes());
+        entry.extend_from_slice(&transfer.value.to_le_bytes());
+        self.push_list_entry(HOLDINGS, entry);
+        Ok(())
+    }
+
+    /// Take tokens out of the holdings
+    fn take_holding(&self, transfer: &AlkaneTransfer) -> Result<()> {
+        for index in 0..self.list_length(HOLDINGS) {
+            let mut entry = self.list_entry(HOLDINGS, index);
+            if Self::entry_id(&entry, 0) == transfer.id {
+                let amount = Self::field(&entry, HOLDING_AMOUNT)
+                    .unwrap_or(0)
+                    .checked_sub(transfer.value)
+                    .ok_or_else(|| anyhow!("Holdings cannot cover payout"))?;
+                Self::set_field(&mut entry, HOLDING_AMOUNT, amount);
+                self.set_list_entry(HOLDINGS, index, entry);
+                return Ok(());
+            }
+        }
+        Err(anyhow!("Holdings cannot cover payout"))
+    }
+
+    /// Pay `shares` their part of every collection from index `from` on.
+    /// Each entry is divided on its own, so rounding never lets the payouts
+    /// of one collection exceed it
+    fn pay_collections(&self, response: &mut CallResponse, shares: u128, from: u128) -> Result<()> {
+        let issued = self.issued_shares();
+        let mut payouts = AlkaneTransferParcel::default();
+        for index in from..self.list_length(COLLECTIONS) {
+            let entry = self.list_entry(COLLECTIONS, index);
+            let amount = Self::field(&entry, COLLECTION_AMOUNT).unwrap_or(0);
+            let value = Self::mul_div(amount, shares, issued)?;
+            if value != 0 {
+                Self::pay_merged(
+                    &mut payouts,
+                    AlkaneTransfer {
+                        id: Self::entry_id(&entry, 0),
+                        value,
+                    },
+                )?;
+            }
+        }
+        for transfer in payouts.0 {
+            self.take_holding(&transfer)?;
+            Self::pay_merged(&mut response.alkanes, transfer)?;
+        }
+        Ok(())
+    }
+
+    /// Pay out every holding, emptying the bundle
+    fn pay_all_holdings(&self, response: &mut CallResponse) -> Result<()> {
+        for index in 0..self.list_length(HOLDINGS) {
+            let mut entry = self.list_entry(HOLDINGS, index);
+            let amount = Self::field(&entry, HOLDING_AMOUNT).unwrap_or(0);
+            if amount == 0 {
+                continue;
+            }
+            Self::set_field(&mut entry, HOLDING_AMOUNT, 0);
+            self.set_list_entry(HOLDINGS, index, entry.clone());
+            Self::pay_merged(
+                &mut response.alkanes,
+                AlkaneTransfer {
+                    id: Self::entry_id(&entry, 0),
+                    value: amount,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    // ============ Bundle Lifecycle ============
+
+    /// Sponsor opens the bundle
+    fn initialize(&self, loan_token: AlkaneId, join_blocks: u128) -> Result<CallResponse> {
+        self.observe_initialization()?;
+        let context = self.context()?;
+
+        if loan_token.block == 0 && loan_token.tx == 0 {
+            return Err(anyhow!("Loan token id cannot be zero"));
+        }
+        if loan_token == context.myself {
+            return Err(anyhow!("Loan token cannot be the bundle"));
+        }
+        if join_blocks == 0 {
+            return Err(anyhow!("Join window cannot be zero"));
+        }
+        let join_end = self
+            .current_block()
+            .checked_add(join_blocks)
+            .ok_or_else(|| anyhow!("Overflow calculating join window"))?;
+
+        self.set_loan_token(loan_token);
+        self.set_join_end_block(join_end);
+
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.alkanes.pay(self.mint_sponsor_note()?);
+        Ok(response)
+    }
+
+    /// Sponsor bundles an active loan by its creditor note
+    fn join(&self, lending: AlkaneId) -> Result<CallResponse> {
+        self.only_sponsor()?;
+        let context = self.context()?;
+        if self.current_block() > self.join_end_block() {
+            return Err(anyhow!("Join window has closed"));
+        }
+
+        let details = self.query_lending(&lending, LENDING_GET_LOAN_DETAILS)?;
+        if Self::lending_field(&details, DETAILS_STATE)? != LENDING_STATE_ACTIVE {
+            return Err(anyhow!("Only active loans can be bundled"));
+        }
+        let loan_token = AlkaneId {
+            block: Self::lending_field(&details, DETAILS_LOAN_TOKEN_BLOCK)?,
+            tx: Self::lending_field(&details, DETAILS_LOAN_TOKEN_TX)?,
+        };
+        if loan_token != self.loan_token()? {
+            return Err(anyhow!("Loan is not lent in the bundle's loan token"));
+        }
+        let principal = Self::lending_field(&details, DETAILS_LOAN_AMOUNT)?;
+
+        // Bond-mode creditors are paid by bond redemptions, which the
+        // creditor note cannot claim
+        let bond = self.query_lending(&lending, LENDING_GET_BOND_TOKEN)?;
+        if Self::lending_field(&bond, 0)? != 0 || Self::lending_field(&bond, 1)? != 0 {
+            return Err(anyhow!("Bond-mode loans cannot be bundled"));
+        }
+
+        let note_data = self.query_lending(&lending, LENDING_GET_CREDITOR_NOTE)?;
+        let note = AlkaneId {
+            block: Self::lending_field(&note_data, 0)?,
+            tx: Self::lending_field(&note_data, 1)?,
+        };
+
+        // Keep one unit of the creditor note, refund everything else
+        let mut response = CallResponse::default();
+        let mut kept = false;
+        for mut transfer in context.incoming_alkanes.0 {
+            if !kept && transfer.id == note && transfer.value > 0 {
+                kept = true;
+                transfer.value -= 1;
+                if transfer.value == 0 {
+                    continue;
+                }
+            }
+            Self::pay_merged(&mut response.alkanes, transfer)?;
+        }
+        if !kept {
+            return Err(anyhow!("Creditor note is not in incoming alkanes"));
+        }
+
+        let supply = self
+            .share_supply()
+            .checked_add(principal)
+            .ok_or_else(|| anyhow!("Overflow minting bundle shares"))?;
+        self.set_share_supply(supply);
+        self.set_issued_shares(self.issued_shares() + principal);
+
+        let mut entry: Vec<u8> = Vec::with_capacity(96);
+        for value in [lending.block, lending.tx, note.block, note.tx, principal, 0] {
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+        self.push_list_entry(LOANS, entry);
+
+        Self::pay_merged(
+            &mut response.alkanes,
+            AlkaneTransfer {
+                id: context.myself,
+                value: principal,
+            },
+        )?;
+        Ok(response)
+    }
+
+    /// Collect a bundled loan's repayment, or its collateral once defaulted
+    fn collect(&self, index: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut entry = self.uncollected_loan(index)?;
+
+        // The lending contract decides whether a default is claimable yet
+        let state = self.query_lending(&Self::entry_id(&entry, 0), LENDING_GET_STATE)?;
+        let opcode = if Self::lending_field(&state, 0)? == LENDING_STATE_REPAID {
+            LENDING_CLAIM_REPAYMENT
+        } else {
+            LENDING_CLAIM_DEFAULTED_COLLATERAL
+        };
+        let proceeds = self.call_as_creditor(&entry, opcode, "Unable to collect loan")?;
+        for transfer in proceeds.alkanes.0 {
+            let mut collection: Vec<u8> = Vec::with_capacity(48);
+            for value in [transfer.id.block, transfer.id.tx, transfer.value] {
+                collection.extend_from_slice(&value.to_le_bytes());
+            }
+            self.push_list_entry(COLLECTIONS, collection);
+            self.add_holding(transfer)?;
+        }
+
+        Self::set_field(&mut entry, LOAN_COLLECTED, 1);
+        self.set_list_entry(LOANS, index, entry);
+        self.set_collected_count(self.collected_count() + 1);
+
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Sponsor calls a bundled open-term loan
+    fn call_loan(&self, index: u128) -> Result<CallResponse> {
+        self.only_sponsor()?;
+        let context = self.context()?;
+        let entry = self.uncollected_loan(index)?;
+        // The lending contract refunds the creditor note to the bundle
+        self.call_as_creditor(&entry, LENDING_CALL_LOAN, "Unable to call loan")?;
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Sponsor gives notice of default on a bundled loan
+    fn notice_of_default(&self, index: u128) -> Result<CallResponse> {
+        self.only_sponsor()?;
+        let context = self.context()?;
+        let entry = self.uncollected_loan(index)?;
+        // The lending contract refunds the creditor note to the bundle
+        self.call_as_creditor(&entry, LENDING_NOTICE_OF_DEFAULT, "Unable to give notice of default")?;
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Redeem shares for their part of every collection made so far
+    fn exit(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        if self.current_block() <= self.join_end_block() {
+            return Err(anyhow!("Join window is still open"));
+        }
+
+        let mut shares: u128 = 0;
+        let mut response = CallResponse::default();
+        for transfer in context.incoming_alkanes.0 {
+            if transfer.id == context.myself {
+                shares = shares
+                    .checked_add(transfer.value)
+                    .ok_or_else(|| anyhow!("Overflow collecting tokens"))?;
+            } else {
+                Self::pay_merged(&mut response.alkanes, transfer)?;
+            }
+        }
+        if shares == 0 {
+            return Err(anyhow!("No bundle shares sent"));
+        }
+
+        let supply = self.share_supply();
+        let outstanding = self.collected_count() < self.list_length(LOANS);
+        if shares == supply && !outstanding && self.open_exits() == 0 {
+            // Nothing else is owed, so the last shares take any rounding dust
+            self.pay_all_holdings(&mut response)?;
+        } else {
+            self.pay_collections(&mut response, shares, 0)?;
+        }
+
+        // The note claims the shares' part of the loans still outstanding
+        if outstanding {
+            let note = self.mint_note()?;
+            let mut entry: Vec<u8> = Vec::with_capacity(80);
+            let next = self.list_length(COLLECTIONS);
+            for value in [note.id.block, note.id.tx, shares, next, 0] {
+                entry.extend_from_slice(&value.to_le_bytes());
+            }
+            self.push_list_entry(EXITS, entry);
+            self.set_open_exits(self.open_exits() + 1);
+            Self::pay_merged(&mut response.alkanes, note)?;
+        }
+
+        // Redeemed shares stay with the bundle, out of circulation
+        self.set_share_supply(supply - shares);
+
+        Ok(response)
+    }
+
+    /// Pay an early exit its part of the collections made since it last
+    /// claimed
+    fn claim_exit(&self, index: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        if index >= self.list_length(EXITS) {
+            return Err(anyhow!("No exit at index {}", index));
+        }
+        let mut entry = self.list_entry(EXITS, index);
+        if Self::field(&entry, EXIT_SETTLED).unwrap_or(0) != 0 {
+            return Err(anyhow!("Exit already settled"));
+        }
+        let note = Self::entry_id(&entry, 0);
+        let settled = self.collected_count() == self.list_length(LOANS);
+
+        // Refund the note until the exit is settled, then keep one unit
+        let mut response = CallResponse::default();
+        let mut found = false;
+        for mut transfer in context.incoming_alkanes.0 {
+            if !found && transfer.id == note && transfer.value > 0 {
+                found = true;
+                if settled {
+                    transfer.value -= 1;
+                    if transfer.value == 0 {
+                        continue;
+                    }
+                }
+            }
+            Self::pay_merged(&mut response.alkanes, transfer)?;
+        }
+        if !found {
+            return Err(anyhow!("Exit note is not in incoming alkanes"));
+        }
+
+        let shares = Self::field(&entry, EXIT_SHARES).unwrap_or(0);
+        let from = Self::field(&entry, EXIT_NEXT_COLLECTION).unwrap_or(0);
+        self.pay_collections(&mut response, shares, from)?;
+
+        Self::set_field(&mut entry, EXIT_NEXT_COLLECTION, self.list_length(COLLECTIONS));
+        if settled {
+            Self::set_field(&mut entry, EXIT_SETTLED, 1);
+            self.set_open_exits(self.open_exits() - 1);
+        }
+        self.set_list_entry(EXITS, index, entry);
+
+        Ok(response)
+    }
+
+    // ============ View Functions ============
+
+    /// Get the bundle: loan token (2), sponsor note (2), last join block,
+    /// loans bundled, loans collected, share supply (8 × u128)
+    fn get_bundle(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let loan_token = self.loan_token().unwrap_or(AlkaneId { block: 0, tx: 0 });
+        let sponsor_note = self.sponsor_note().unwrap_or(AlkaneId { block: 0, tx: 0 });
+        let mut data: Vec<u8> = Vec::new();
+        for value in [
+            loan_token.block,
+            loan_token.tx,
+            sponsor_note.block,
+            sponsor_note.tx,
+            self.join_end_block(),
+            self.list_length(LOANS),
+            self.collected_count(),
+            self.share_supply(),
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get a bundled loan entry (6 × u128)
+    fn get_loan(&self, index: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        if index >= self.list_length(LOANS) {
+            return Err(anyhow!("No bundled loan at index {}", index));
+        }
+
+        response.data = self.list_entry(LOANS, index);
+        Ok(response)
+    }
+
+    /// Get the holdings: count, then 3 × u128 per token
+    fn get_holdings(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let count = self.list_length(HOLDINGS);
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(&count.to_le_bytes());
+        for index in 0..count {
+            data.extend_from_slice(&self.list_entry(HOLDINGS, index));
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get an early exit entry (5 × u128)
+    fn get_exit(&self, index: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        if index >= self.list_length(EXITS) {
+            return Err(anyhow!("No exit at index {}", index));
+        }
+
+        response.data = self.list_entry(EXITS, index);
+        Ok(response)
+    }
+
+    /// Get the bundle's present value: uncollected loans priced by their
+    /// lending contracts, plus the loan token already collected at face
+    /// value. Collected collateral has no price without an oracle and is left
+    /// out. The value per share is what a share still in circulation is owed:
+    /// its part of the uncollected loans and of all loan token collected,
+    /// including what earlier exits have already taken (5 × u128)
+    fn get_present_value(&self, discount_apr: u128, apr_precision: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+
+        let mut pending: u128 = 0;
+        let mut pending_value: u128 = 0;
+        for index in 0..self.list_length(LOANS) {
+            let entry = self.list_entry(LOANS, index);
+            if Self::field(&entry, LOAN_COLLECTED).unwrap_or(0) != 0 {
+                continue;
+            }
+            let data = self.query_lending_with_inputs(
+                &Self::entry_id(&entry, 0),
+                vec![LENDING_GET_PRESENT_VALUE, discount_apr, apr_precision],
+            )?;
+            pending += 1;
+            pending_value = pending_value
+                .checked_add(Self::lending_field(&data, PRESENT_VALUE)?)
+                .ok_or_else(|| anyhow!("Overflow in present value calculation"))?;
+        }
+
+        let loan_token = self.loan_token().unwrap_or(AlkaneId { block: 0, tx: 0 });
+        let mut held: u128 = 0;
+        for index in 0..self.list_length(HOLDINGS) {
+            let entry = self.list_entry(HOLDINGS, index);
+            if Self::entry_id(&entry, 0) == loan_token {
+                held = Self::field(&entry, HOLDING_AMOUNT).unwrap_or(0);
+            }
+        }
+
+        let total = pending_value
+            .checked_add(held)
+            .ok_or_else(|| anyhow!("Overflow in present value calculation"))?;
+        let mut collected: u128 = 0;
+        for index in 0..self.list_length(COLLECTIONS) {
+            let entry = self.list_entry(COLLECTIONS, index);
+            if Self::entry_id(&entry, 0) == loan_token {
+                collected = collected
+                    .checked_add(Self::field(&entry, COLLECTION_AMOUNT).unwrap_or(0))
+                    .ok_or_else(|| anyhow!("Overflow in present value calculation"))?;
+            }
+        }
+        let owed = pending_value
+            .checked_add(collected)
+            .ok_or_else(|| anyhow!("Overflow in present value calculation"))?;
+        let per_share = match self.issued_shares() {
+            0 => 0,
+            issued => Self::mul_div(owed, SHARE_VALUE_PRECISION, issued)?,
+        };
+
+        let mut data: Vec<u8> = Vec::new();
+        for value in [pending, pending_value, held, total, per_share] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Get the share supply
+    fn get_total_supply(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.share_supply().to_le_bytes().to_vec();
+        Ok(response)
+    }
+}
+
+declare_alkane! {
+    impl AlkaneResponder for LoanBundler {
+        type Message = LoanBundlerMessage;
+    }
+}
@@ -0,0 +1,411 @@
+//! Loan bundler test helpers
+//!
+//! Deploys a bundler next to two lending contracts that lend the same loan
+//! token, and wraps each bundler opcode so tests read as a sequence of
+//! high-level steps, like the lending helpers.
+
+#![allow(dead_code)]
+
+use crate::tests::helper::lending_helpers::{
+    self as h, LendingDeploymentIds, DEPLOY_HEIGHT, INIT_TOKEN_SUPPLY,
+};
+use crate::tests::std::{lending_contract_build, loan_bundler_build};
+
+use alkanes::indexer::index_block;
+use alkanes::precompiled::{alkanes_std_auth_token_build, alkanes_std_owned_token_build};
+use alkanes::tests::helpers::{self as alkane_helpers, BinaryAndCellpack};
+use alkanes_support::constants::AUTH_TOKEN_FACTORY_ID;
+use alkanes_support::{cellpack::Cellpack, id::AlkaneId};
+use anyhow::Result;
+use bitcoin::blockdata::transaction::OutPoint;
+use bitcoin::{Block, ScriptBuf, Sequence, TxIn, Witness};
+use protorune::test_helpers::create_block_with_coinbase_tx;
+use protorune_support::protostone::ProtostoneEdict;
+
+/// Output of a split transaction holding everything that did not reach the
+/// call (see [`h::execute_cellpack_with_split`])
+pub const SPLIT_LEFTOVER_VOUT: u32 = 1;
+
+// ============================================================================
+// Deployment IDs
+// ============================================================================
+
+/// Deployment IDs produced by [`deploy_bundler_with_loans`].
+pub struct BundlerDeploymentIds {
+    pub lending_a: AlkaneId,
+    pub lending_b: AlkaneId,
+    pub collateral_token: AlkaneId,
+    pub loan_token: AlkaneId,
+    pub bundler: AlkaneId,
+}
+
+impl BundlerDeploymentIds {
+    /// Lending deployment IDs for one of the two lending contracts, so the
+    /// lending helpers and [`h::LoanTerms::default_from`] can be reused.
+    pub fn lending(&self, lending_contract: &AlkaneId) -> LendingDeploymentIds {
+        LendingDeploymentIds {
+            lending_contract: *lending_contract,
+            collateral_token: self.collateral_token,
+            loan_token: self.loan_token,
+        }
+    }
+}
+
+/// Bundle fields returned by GetBundle (opcode 90).
+pub struct BundleView {
+    pub loan_token: AlkaneId,
+    pub sponsor_note: AlkaneId,
+    pub join_end_block: u128,
+    pub loan_count: u128,
+    pub collected_count: u128,
+    pub share_supply: u128,
+}
+
+/// Early exit fields returned by GetExit (opcode 94).
+pub struct ExitView {
+    pub note: AlkaneId,
+    pub shares: u128,
+    pub next_collection: u128,
+    pub settled: bool,
+}
+
+// ============================================================================
+// Deployment
+// ============================================================================
+
+/// Deploy the auth-token factory, two lending contracts, the collateral and
+/// loan tokens and the loan bundler in one block. Returns the genesis block
+/// and deployment IDs.
+pub fn deploy_bundler_with_loans() -> Result<(Block, BundlerDeploymentIds)> {
+    deploy_bundler_with_supply(INIT_TOKEN_SUPPLY)
+}
+
+/// Same as [`deploy_bundler_with_loans`] with both tokens minted with
+/// `token_supply` to the deploying wallet.
+pub fn deploy_bundler_with_supply(token_supply: u128) -> Result<(Block, BundlerDeploymentIds)> {
+    alkane_helpers::clear();
+
+    let lending = || BinaryAndCellpack {
+        binary: lending_contract_build::get_bytes(),
+        cellpack: Cellpack {
+            target: AlkaneId { block: 1, tx: 0 },
+            inputs: vec![99],
+        },
+    };
+    let token = || BinaryAndCellpack {
+        binary: alkanes_std_owned_token_build::get_bytes(),
+        cellpack: Cellpack {
+            target: AlkaneId { block: 1, tx: 0 },
+            inputs: vec![0, 1, token_supply],
+        },
+    };
+
+    let cellpack_pairs: Vec<BinaryAndCellpack> = vec![
+        // Auth token factory at reserved factory ID
+        BinaryAndCellpack {
+            binary: alkanes_std_auth_token_build::get_bytes(),
+            cellpack: Cellpack {
+                target: AlkaneId {
+                    block: 3,
+                    tx: AUTH_TOKEN_FACTORY_ID,
+                },
+                inputs: vec![100],
+            },
+        },
+        // Lending contract A → sequence 1
+        lending(),
+        // Collateral token → sequence 2 (auth at 3)
+        token(),
+        // Loan token → sequence 4 (auth at 5)
+        token(),
+        // Lending contract B → sequence 6
+        lending(),
+        // Loan bundler → sequence 7, left uninitialized by a view call
+        BinaryAndCellpack {
+            binary: loan_bundler_build::get_bytes(),
+            cellpack: Cellpack {
+                target: AlkaneId { block: 1, tx: 0 },
+                inputs: vec![90],
+            },
+        },
+    ];
+
+    let test_block = alkane_helpers::init_with_cellpack_pairs(cellpack_pairs);
+    index_block(&test_block, DEPLOY_HEIGHT)?;
+
+    let ids = BundlerDeploymentIds {
+        lending_a: AlkaneId { block: 2, tx: 1 },
+        collateral_token: AlkaneId { block: 2, tx: 2 },
+        loan_token: AlkaneId { block: 2, tx: 4 },
+        lending_b: AlkaneId { block: 2, tx: 6 },
+        bundler: AlkaneId { block: 2, tx: 7 },
+    };
+
+    Ok((test_block, ids))
+}
+
+// ============================================================================
+// Bundler operations
+// ============================================================================
+
+/// Sponsor opens the bundle (opcode 0) and receives the sponsor note.
+pub fn init_bundler(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    loan_token: &AlkaneId,
+    join_blocks: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![0, loan_token.block, loan_token.tx, join_blocks],
+    };
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, vec![])
+}
+
+/// Sponsor bundles `lending_id`'s loan (opcode 1), sending the sponsor note
+/// and the creditor note (1 unit of the lending contract's self-token).
+/// Receives bundle shares.
+pub fn join_bundle(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    sponsor_note: &AlkaneId,
+    lending_id: &AlkaneId,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![1, lending_id.block, lending_id.tx],
+    };
+    let edicts = vec![
+        ProtostoneEdict {
+            id: (*sponsor_note).into(),
+            amount: 1,
+            output: 0,
+        },
+        ProtostoneEdict {
+            id: (*lending_id).into(),
+            amount: 1,
+            output: 0,
+        },
+    ];
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Collect the bundled loan at `index` (opcode 2).
+pub fn collect_loan(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    index: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![2, index],
+    };
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, vec![])
+}
+
+/// Sponsor calls the bundled open-term loan at `index` (opcode 4), sending
+/// the sponsor note.
+pub fn call_bundled_loan(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    sponsor_note: &AlkaneId,
+    index: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![4, index],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: (*sponsor_note).into(),
+        amount: 1,
+        output: 0,
+    }];
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Sponsor gives notice of default on the bundled loan at `index` (opcode 5),
+/// sending the sponsor note.
+pub fn notice_bundled_loan(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    sponsor_note: &AlkaneId,
+    index: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![5, index],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: (*sponsor_note).into(),
+        amount: 1,
+        output: 0,
+    }];
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Redeem bundle shares for their part of the holdings (opcode 3), sending
+/// `shares` of them.
+pub fn exit_bundle(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    shares: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![3],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: (*bundler_id).into(),
+        amount: shares,
+        output: 0,
+    }];
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Redeem only `shares` of the wallet's bundle shares (opcode 3). The payout
+/// goes to vout 0 and the rest of the wallet to [`SPLIT_LEFTOVER_VOUT`], so
+/// the two act as separate holders from then on.
+pub fn exit_bundle_split(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    shares: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![3],
+    };
+    h::execute_cellpack_with_split(prev_block, height, cellpack, *bundler_id, shares)
+}
+
+/// Redeem every bundle share held at `vout` of the last transaction in
+/// `holder_block` (opcode 3), e.g. the leftovers of [`exit_bundle_split`].
+/// The payout goes to vout 0 of the returned block's last transaction.
+pub fn exit_bundle_from(
+    holder_block: &Block,
+    vout: u32,
+    height: u32,
+    bundler_id: &AlkaneId,
+) -> Result<Block> {
+    let txin = TxIn {
+        previous_output: OutPoint {
+            txid: holder_block.txdata.last().unwrap().compute_txid(),
+            vout,
+        },
+        script_sig: ScriptBuf::new(),
+        sequence: Sequence::MAX,
+        witness: Witness::new(),
+    };
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![3],
+    };
+    let mut block = create_block_with_coinbase_tx(height);
+    block.txdata.push(
+        alkane_helpers::create_multiple_cellpack_with_witness_and_txins_edicts(
+            vec![cellpack],
+            vec![txin],
+            false,
+            vec![],
+        ),
+    );
+    index_block(&block, height)?;
+    Ok(block)
+}
+
+/// Claim an early exit's part of later collections (opcode 6), sending the
+/// exit note.
+pub fn claim_exit(
+    prev_block: &Block,
+    height: u32,
+    bundler_id: &AlkaneId,
+    note: &AlkaneId,
+    index: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: *bundler_id,
+        inputs: vec![6, index],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: (*note).into(),
+        amount: 1,
+        output: 0,
+    }];
+    h::execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+// ============================================================================
+// View helpers
+// ============================================================================
+
+/// Read the bundle via GetBundle (opcode 90).
+pub fn get_bundle(height: u32, bundler_id: &AlkaneId) -> Result<BundleView> {
+    let data = h::call_view(height, bundler_id, 90)?;
+    let field = |index: usize| h::read_u128_le(&data, index * 16);
+    Ok(BundleView {
+        loan_token: AlkaneId {
+            block: field(0),
+            tx: field(1),
+        },
+        sponsor_note: AlkaneId {
+            block: field(2),
+            tx: field(3),
+        },
+        join_end_block: field(4),
+        loan_count: field(5),
+        collected_count: field(6),
+        share_supply: field(7),
+    })
+}
+
+/// Read the early exit at `index` via GetExit (opcode 94).
+pub fn get_exit(height: u32, bundler_id: &AlkaneId, index: u128) -> Result<ExitView> {
+    let data = h::call_view_with_inputs(height, bundler_id, vec![94, index])?;
+    let field = |index: usize| h::read_u128_le(&data, index * 16);
+    Ok(ExitView {
+        note: AlkaneId {
+            block: field(0),
+            tx: field(1),
+        },
+        shares: field(2),
+        next_collection: field(3),
+        settled: field(4) != 0,
+    })
+}
+
+/// Read GetPresentValue (opcode 93): uncollected loans, their present value,
+/// loan token held, total and value per share.
+pub fn get_present_value(
+    height: u32,
+    bundler_id: &AlkaneId,
+    discount_apr: u128,
+    apr_precision: u128,
+) -> Result<[u128; 5]> {
+    let data = h::call_view_with_inputs(height, bundler_id, vec![93, discount_apr, apr_precision])?;
+    Ok([0, 1, 2, 3, 4].map(|field| h::read_u128_le(&data, field * 16)))
+}
+
+/// Read the holdings via GetHoldings (opcode 92) as (token, amount) pairs.
+pub fn get_holdings(height: u32, bundler_id: &AlkaneId) -> Result<Vec<(AlkaneId, u128)>> {
+    let data = h::call_view(height, bundler_id, 92)?;
+    let count = h::read_u128_le(&data, 0) as usize;
+    Ok((0..count)
+        .map(|index| {
+            let offset = 16 + index * 48;
+            (
+                AlkaneId {
+                    block: h::read_u128_le(&data, offset),
+                    tx: h::read_u128_le(&data, offset + 16),
+                },
+                h::read_u128_le(&data, offset + 32),
+            )
+        })
+        .collect())
+}
+
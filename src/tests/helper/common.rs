@@ -8,6 +8,27 @@ pub const APR_PRECISION: u128 = 10000;
 /// Blocks per year approximation (matches contract)
 pub const BLOCKS_PER_YEAR: u128 = 52560;
 
+/// Basis-point precision for fee parameters (matches contract)
+pub const BPS_PRECISION: u128 = 10_000;
+
+/// Loan option flags (matches contract)
+pub const FLAG_EARLY_REPAYMENT_REBATE: u128 = 1 << 0;
+pub const FLAG_ZERO_COUPON_BOND: u128 = 1 << 1;
+pub const FLAG_OPEN_TERM: u128 = 1 << 2;
+pub const FLAG_MINIMUM_BLOCK_INTEREST: u128 = 1 << 3;
+pub const FLAG_ABSOLUTE_DEADLINE: u128 = 1 << 4;
+pub const FLAG_PER_BLOCK_RATE: u128 = 1 << 5;
+
+/// Per-block rate precision (matches contract)
+pub const PER_BLOCK_RATE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Fixed-point scale of price views (matches contract's PRECISION_MULTIPLIER)
+pub const PRICE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Collateral-to-loan ratio bounds (matches the contract built without overrides)
+pub const MAX_LOAN_PER_COLLATERAL: u128 = 1_000_000;
+pub const MAX_COLLATERAL_PER_LOAN: u128 = 1_000_000;
+
 /// Calculate expected repayment amount (principal + interest)
 /// Matches the contract's calculation logic
 pub fn calculate_repayment_amount(
@@ -18,3 +39,72 @@ pub fn calculate_repayment_amount(
     let interest = principal * apr * duration_blocks / (APR_PRECISION * BLOCKS_PER_YEAR);
     principal + interest
 }
+
+/// Calculate expected repayment amount for a per-block rate
+/// Matches the contract's calculation logic
+pub fn calculate_per_block_repayment_amount(
+    principal: u128,
+    rate_per_block: u128,
+    duration_blocks: u128,
+) -> u128 {
+    let interest = principal * duration_blocks * rate_per_block / PER_BLOCK_RATE_PRECISION;
+    principal + interest
+}
+
+/// Calculate expected repayment amount under the early repayment rebate
+/// schedule: principal + interest accrued over `elapsed_blocks` + break fee
+/// on the rebated interest. Matches the contract's calculation logic.
+pub fn calculate_early_repayment_amount(
+    principal: u128,
+    apr: u128,
+    duration_blocks: u128,
+    elapsed_blocks: u128,
+    break_fee_bps: u128,
+) -> u128 {
+    let elapsed_blocks = elapsed_blocks.min(duration_blocks);
+    let accrued_interest = principal * apr * elapsed_blocks / (APR_PRECISION * BLOCKS_PER_YEAR);
+    let break_fee =
+        calculate_remaining_interest(principal, apr, duration_blocks, elapsed_blocks) * break_fee_bps
+            / BPS_PRECISION;
+    principal + accrued_interest + break_fee
+}
+
+/// Calculate interest for the part of the duration not yet elapsed
+/// Matches the contract's calculation logic
+pub fn calculate_remaining_interest(
+    principal: u128,
+    apr: u128,
+    duration_blocks: u128,
+    elapsed_blocks: u128,
+) -> u128 {
+    let elapsed_blocks = elapsed_blocks.min(duration_blocks);
+    let full_interest = principal * apr * duration_blocks / (APR_PRECISION * BLOCKS_PER_YEAR);
+    let accrued_interest = principal * apr * elapsed_blocks / (APR_PRECISION * BLOCKS_PER_YEAR);
+    full_interest - accrued_interest
+}
+
+/// Discount `amount` due in `blocks` blocks at `discount_apr` (with
+/// `apr_precision`) using simple interest
+/// Matches the contract's calculation logic
+pub fn calculate_present_value(
+    amount: u128,
+    discount_apr: u128,
+    blocks: u128,
+    apr_precision: u128,
+) -> u128 {
+    let year = apr_precision * BLOCKS_PER_YEAR;
+    amount * year / (year + discount_apr * blocks)
+}
+
+/// Calculate the prepayment penalty: `penalty_bps` of the remaining interest
+/// Matches the contract's calculation logic
+pub fn calculate_prepayment_penalty(
+    principal: u128,
+    apr: u128,
+    duration_blocks: u128,
+    elapsed_blocks: u128,
+    penalty_bps: u128,
+) -> u128 {
+    calculate_remaining_interest(principal, apr, duration_blocks, elapsed_blocks) * penalty_bps
+        / BPS_PRECISION
+}
@@ -33,6 +33,11 @@ pub const APR_500_BPS: u128 = 500; // 5.00% APR
 /// Initial token supply for test tokens
 pub const INIT_TOKEN_SUPPLY: u128 = 10_000_000_000_000; // 10 trillion
 
+/// Extreme supplies for [`deploy_lending_with_supplies`]: a single
+/// indivisible unit, and the largest amount an alkane can hold
+pub const DUST_TOKEN_SUPPLY: u128 = 1;
+pub const HUGE_TOKEN_SUPPLY: u128 = u128::MAX;
+
 /// First block height used for deployment
 pub const DEPLOY_HEIGHT: u32 = 840_000;
 
@@ -60,6 +65,18 @@ pub struct LoanTerms {
     pub loan_amount: u128,
     pub duration_blocks: u128,
     pub apr: u128,
+    pub loan_flags: u128,
+    pub break_fee_bps: u128,
+    pub prepayment_penalty_bps: u128,
+    pub prepayment_lockout_blocks: u128,
+    pub default_notice_blocks: u128,
+    pub max_take_delay_blocks: u128,
+    pub grace_blocks: u128,
+    pub late_penalty_bps: u128,
+    pub reservation_deposit: u128,
+    pub reservation_blocks: u128,
+    pub allowed_taker: AlkaneId,
+    pub apr_precision: u128,
 }
 
 impl LoanTerms {
@@ -72,6 +89,18 @@ impl LoanTerms {
             loan_amount: LOAN_AMOUNT,
             duration_blocks: DURATION_BLOCKS,
             apr: APR_500_BPS,
+            loan_flags: 0,
+            break_fee_bps: 0,
+            prepayment_penalty_bps: 0,
+            prepayment_lockout_blocks: 0,
+            default_notice_blocks: 0,
+            max_take_delay_blocks: 0,
+            grace_blocks: 0,
+            late_penalty_bps: 0,
+            reservation_deposit: 0,
+            reservation_blocks: 0,
+            allowed_taker: AlkaneId { block: 0, tx: 0 },
+            apr_precision: 0,
         }
     }
 }
@@ -119,6 +148,40 @@ pub fn execute_cellpack_with_edicts(
     Ok(block)
 }
 
+/// Execute several cellpacks as consecutive transactions of one block.
+///
+/// Each transaction spends vout 0 of the one before it (the first spends the
+/// last tx in `prev_block`), so the calls run in the given order at `height`.
+/// Returns the indexed block.
+pub fn execute_cellpacks_in_block(
+    prev_block: &Block,
+    height: u32,
+    calls: Vec<(Cellpack, Vec<ProtostoneEdict>)>,
+) -> Result<Block> {
+    let mut block = create_block_with_coinbase_tx(height);
+    let mut txin = txin_from_last_tx(prev_block);
+    for (cellpack, edicts) in calls {
+        let tx = alkane_helpers::create_multiple_cellpack_with_witness_and_txins_edicts(
+            vec![cellpack],
+            vec![txin],
+            false,
+            edicts,
+        );
+        txin = TxIn {
+            previous_output: OutPoint {
+                txid: tx.compute_txid(),
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        block.txdata.push(tx);
+    }
+    index_block(&block, height)?;
+    Ok(block)
+}
+
 /// Execute a cellpack from a default (empty) outpoint — no real token balance.
 /// Used for calls that are expected to revert.
 pub fn execute_cellpack_no_balance(
@@ -198,6 +261,18 @@ pub fn assert_revert(block: &Block, expected_msg: &str) -> Result<()> {
     )
 }
 
+/// Assert that the tx at `index` in `block` reverted at the standard
+/// protostone vout with a message containing `expected_msg`.
+pub fn assert_revert_at(block: &Block, index: usize, expected_msg: &str) -> Result<()> {
+    alkane_helpers::assert_revert_context(
+        &OutPoint {
+            txid: block.txdata[index].compute_txid(),
+            vout: PROTOSTONE_VOUT,
+        },
+        expected_msg,
+    )
+}
+
 /// Assert revert for a split-transaction (cellpack protostone at vout 5).
 pub fn assert_revert_split(block: &Block, expected_msg: &str) -> Result<()> {
     alkane_helpers::assert_revert_context(
@@ -213,6 +288,15 @@ pub fn assert_revert_split(block: &Block, expected_msg: &str) -> Result<()> {
 /// Deploy lending contract, auth-token factory, and two test tokens
 /// (collateral + loan). Returns the genesis block and deployment IDs.
 pub fn deploy_lending_with_tokens() -> Result<(Block, LendingDeploymentIds)> {
+    deploy_lending_with_supplies(INIT_TOKEN_SUPPLY, INIT_TOKEN_SUPPLY)
+}
+
+/// Same as [`deploy_lending_with_tokens`] with the given token supplies, all
+/// minted to the deploying wallet.
+pub fn deploy_lending_with_supplies(
+    collateral_supply: u128,
+    loan_supply: u128,
+) -> Result<(Block, LendingDeploymentIds)> {
     alkane_helpers::clear();
 
     let cellpack_pairs: Vec<BinaryAndCellpack> = vec![
@@ -240,7 +324,7 @@ pub fn deploy_lending_with_tokens() -> Result<(Block, LendingDeploymentIds)> {
             binary: alkanes_std_owned_token_build::get_bytes(),
             cellpack: Cellpack {
                 target: AlkaneId { block: 1, tx: 0 },
-                inputs: vec![0, 1, INIT_TOKEN_SUPPLY],
+                inputs: vec![0, 1, collateral_supply],
             },
         },
         // Loan token → sequence 4 (auth at 5)
@@ -248,7 +332,7 @@ pub fn deploy_lending_with_tokens() -> Result<(Block, LendingDeploymentIds)> {
             binary: alkanes_std_owned_token_build::get_bytes(),
             cellpack: Cellpack {
                 target: AlkaneId { block: 1, tx: 0 },
-                inputs: vec![0, 1, INIT_TOKEN_SUPPLY],
+                inputs: vec![0, 1, loan_supply],
             },
         },
     ];
@@ -275,20 +359,7 @@ pub fn init_loan_offer(
     lending_id: &AlkaneId,
     terms: &LoanTerms,
 ) -> Result<Block> {
-    let cellpack = Cellpack {
-        target: lending_id.clone(),
-        inputs: vec![
-            0,
-            terms.collateral_token.block,
-            terms.collateral_token.tx,
-            terms.collateral_amount,
-            terms.loan_token.block,
-            terms.loan_token.tx,
-            terms.loan_amount,
-            terms.duration_blocks,
-            terms.apr,
-        ],
-    };
+    let cellpack = build_init_cellpack(lending_id, terms);
     let edicts = vec![ProtostoneEdict {
         id: terms.loan_token.clone().into(),
         amount: terms.loan_amount,
@@ -314,10 +385,52 @@ pub fn build_init_cellpack(lending_id: &AlkaneId, terms: &LoanTerms) -> Cellpack
             terms.loan_amount,
             terms.duration_blocks,
             terms.apr,
+            terms.loan_flags,
+            terms.break_fee_bps,
+            terms.prepayment_penalty_bps,
+            terms.prepayment_lockout_blocks,
+            terms.default_notice_blocks,
+            terms.max_take_delay_blocks,
+            terms.grace_blocks,
+            terms.late_penalty_bps,
+            terms.reservation_deposit,
+            terms.reservation_blocks,
+            terms.allowed_taker.block,
+            terms.allowed_taker.tx,
+            terms.apr_precision,
         ],
     }
 }
 
+/// Creditor and debitor settle in one call (opcode 17): the offer is created
+/// from `terms` and taken at once.
+///
+/// Sends both the loan tokens and the collateral; the creditor note, loan
+/// tokens and debitor note all come back to the same output. Returns the
+/// indexed block.
+pub fn init_and_take(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    terms: &LoanTerms,
+) -> Result<Block> {
+    let mut cellpack = build_init_cellpack(lending_id, terms);
+    cellpack.inputs[0] = 17;
+    let edicts = vec![
+        ProtostoneEdict {
+            id: terms.loan_token.into(),
+            amount: terms.loan_amount,
+            output: 0,
+        },
+        ProtostoneEdict {
+            id: terms.collateral_token.into(),
+            amount: terms.collateral_amount,
+            output: 0,
+        },
+    ];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
 /// Debitor takes the loan by providing collateral (opcode 1).
 ///
 /// Sends `terms.collateral_amount` of collateral tokens and receives the loan
@@ -340,6 +453,70 @@ pub fn take_loan(
     execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
 }
 
+/// Would-be debitor reserves the offer (opcode 16).
+///
+/// Sends `terms.reservation_deposit` of collateral tokens and receives the
+/// reservation note. Returns the indexed block.
+pub fn reserve_offer(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    terms: &LoanTerms,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![16],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: terms.collateral_token.clone().into(),
+        amount: terms.reservation_deposit,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Debitor takes the loan (opcode 1) and repays it (opcode 2) in the same
+/// block, sending `repayment_amount` loan tokens.
+///
+/// The repay transaction spends vout 0 of the take transaction, so both land
+/// at `height`. Returns the indexed block; its last tx is the repayment.
+pub fn take_and_repay_in_block(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    terms: &LoanTerms,
+    repayment_amount: u128,
+) -> Result<Block> {
+    execute_cellpacks_in_block(
+        prev_block,
+        height,
+        vec![
+            (
+                Cellpack {
+                    target: lending_id.clone(),
+                    inputs: vec![1],
+                },
+                vec![ProtostoneEdict {
+                    id: terms.collateral_token.clone().into(),
+                    amount: terms.collateral_amount,
+                    output: 0,
+                }],
+            ),
+            (
+                Cellpack {
+                    target: lending_id.clone(),
+                    inputs: vec![2],
+                },
+                vec![ProtostoneEdict {
+                    id: terms.loan_token.clone().into(),
+                    amount: repayment_amount,
+                    output: 0,
+                }],
+            ),
+        ],
+    )
+}
+
 /// Debitor repays the loan (opcode 2).
 ///
 /// Sends the full repayment amount (principal + interest) in loan tokens.
@@ -352,6 +529,20 @@ pub fn repay_loan(
 ) -> Result<Block> {
     let repayment_amount =
         calculate_repayment_amount(terms.loan_amount, terms.apr, terms.duration_blocks);
+    repay_loan_with_amount(prev_block, height, lending_id, terms, repayment_amount)
+}
+
+/// Debitor repays the loan (opcode 2) sending exactly `repayment_amount` loan tokens.
+///
+/// Used when fees or penalties make the amount due differ from the full-term
+/// repayment. Returns the indexed block.
+pub fn repay_loan_with_amount(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    terms: &LoanTerms,
+    repayment_amount: u128,
+) -> Result<Block> {
     let cellpack = Cellpack {
         target: lending_id.clone(),
         inputs: vec![2],
@@ -425,6 +616,246 @@ pub fn cancel_loan_offer(
     execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
 }
 
+/// Creditor calls an open-term loan (opcode 9), starting the notice period.
+///
+/// Sends the auth token to prove ownership. Returns the indexed block.
+pub fn call_loan(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![9],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: lending_id.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Creditor refreshes the loan offer (opcode 13).
+///
+/// Sends the auth token to prove ownership. Returns the indexed block.
+pub fn refresh_offer(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![13],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: lending_id.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Creditor gives notice of default (opcode 10).
+///
+/// Sends the auth token to prove ownership. Returns the indexed block.
+pub fn notice_of_default(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![10],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: lending_id.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Creditor approves capitalizing accrued interest (opcode 6).
+///
+/// Sends the auth token to prove ownership. `extension_blocks` of 0 revokes a
+/// pending approval. Returns the indexed block.
+pub fn approve_capitalization(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    extension_blocks: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![6, extension_blocks],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: lending_id.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Debitor capitalizes accrued interest (opcode 7).
+///
+/// Sends the debitor note to prove the caller is the debitor. Returns the
+/// indexed block.
+pub fn capitalize_interest(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    debitor_note: &AlkaneId,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![7],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: debitor_note.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Creditor labels their side of the position (opcode 11).
+///
+/// Sends the auth token to prove ownership. Returns the indexed block.
+pub fn set_creditor_label(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    label: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![11, label],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: lending_id.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Debitor labels their side of the position (opcode 12).
+///
+/// Sends the debitor note to prove the caller is the debitor. Returns the
+/// indexed block.
+pub fn set_debitor_label(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    debitor_note: &AlkaneId,
+    label: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![12, label],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: debitor_note.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Creditor records an identity attestation pointer (opcode 14).
+///
+/// Sends the auth token to prove ownership. Returns the indexed block.
+pub fn set_creditor_disclosure(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    disclosure: [u128; 3],
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![14, disclosure[0], disclosure[1], disclosure[2]],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: lending_id.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Debitor records an identity attestation pointer (opcode 15).
+///
+/// Sends the debitor note to prove the caller is the debitor. Returns the
+/// indexed block.
+pub fn set_debitor_disclosure(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    debitor_note: &AlkaneId,
+    disclosure: [u128; 3],
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![15, disclosure[0], disclosure[1], disclosure[2]],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: debitor_note.clone().into(),
+        amount: 1,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Creditor cancels a bond-mode offer (opcode 4), returning `bond_amount`
+/// bonds along with the auth token. Returns the indexed block.
+pub fn cancel_loan_offer_with_bonds(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    bond_token: &AlkaneId,
+    bond_amount: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![4],
+    };
+    let edicts = vec![
+        ProtostoneEdict {
+            id: lending_id.clone().into(),
+            amount: 1,
+            output: 0,
+        },
+        ProtostoneEdict {
+            id: bond_token.clone().into(),
+            amount: bond_amount,
+            output: 0,
+        },
+    ];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
+/// Bond holder redeems `amount` bonds (opcode 8). Returns the indexed block.
+pub fn redeem_bonds(
+    prev_block: &Block,
+    height: u32,
+    lending_id: &AlkaneId,
+    bond_token: &AlkaneId,
+    amount: u128,
+) -> Result<Block> {
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![8],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: bond_token.clone().into(),
+        amount,
+        output: 0,
+    }];
+    execute_cellpack_with_edicts(prev_block, height, cellpack, edicts)
+}
+
 // ============================================================================
 // View function helpers
 // ============================================================================
@@ -437,10 +868,19 @@ pub fn call_view(
     height: u32,
     lending_id: &AlkaneId,
     opcode: u128,
+) -> Result<Vec<u8>> {
+    call_view_with_inputs(height, lending_id, vec![opcode])
+}
+
+/// Call a view function taking arguments; `inputs` starts with the opcode.
+pub fn call_view_with_inputs(
+    height: u32,
+    lending_id: &AlkaneId,
+    inputs: Vec<u128>,
 ) -> Result<Vec<u8>> {
     let cellpack = Cellpack {
         target: lending_id.clone(),
-        inputs: vec![opcode],
+        inputs,
     };
     let block = execute_cellpack_no_balance(height, cellpack)?;
     let outpoint = protostone_outpoint(&block, PROTOSTONE_VOUT);
@@ -456,6 +896,34 @@ pub fn read_u128_le(data: &[u8], offset: usize) -> u128 {
     u128::from_le_bytes(bytes)
 }
 
+/// Read the debitor note id minted on take via GetDebitorNote (opcode 97).
+pub fn get_debitor_note(height: u32, lending_id: &AlkaneId) -> Result<AlkaneId> {
+    let data = call_view(height, lending_id, 97)?;
+    Ok(AlkaneId {
+        block: read_u128_le(&data, 0),
+        tx: read_u128_le(&data, 16),
+    })
+}
+
+/// Read the creditor note id minted on offer via GetCreditorNote (opcode 119).
+pub fn get_creditor_note(height: u32, lending_id: &AlkaneId) -> Result<AlkaneId> {
+    let data = call_view(height, lending_id, 119)?;
+    Ok(AlkaneId {
+        block: read_u128_le(&data, 0),
+        tx: read_u128_le(&data, 16),
+    })
+}
+
+/// Read the bond token id and supply via GetBondToken (opcode 98).
+pub fn get_bond_token(height: u32, lending_id: &AlkaneId) -> Result<(AlkaneId, u128)> {
+    let data = call_view(height, lending_id, 98)?;
+    let bond_token = AlkaneId {
+        block: read_u128_le(&data, 0),
+        tx: read_u128_le(&data, 16),
+    };
+    Ok((bond_token, read_u128_le(&data, 32)))
+}
+
 // ============================================================================
 // Composite setup helpers
 // ============================================================================
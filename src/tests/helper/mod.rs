@@ -1,2 +1,3 @@
 pub mod common;
 pub mod lending_helpers;
+pub mod bundler_helpers;
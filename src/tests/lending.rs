@@ -5,18 +5,26 @@
 
 #![cfg(test)]
 
-use crate::tests::helper::common::calculate_repayment_amount;
+use crate::tests::helper::common::{
+    calculate_early_repayment_amount, calculate_per_block_repayment_amount,
+    calculate_prepayment_penalty, calculate_present_value, calculate_repayment_amount,
+    APR_PRECISION, BLOCKS_PER_YEAR,
+    FLAG_ABSOLUTE_DEADLINE, FLAG_EARLY_REPAYMENT_REBATE, FLAG_MINIMUM_BLOCK_INTEREST, FLAG_OPEN_TERM,
+    FLAG_PER_BLOCK_RATE, FLAG_ZERO_COUPON_BOND,
+    MAX_COLLATERAL_PER_LOAN, MAX_LOAN_PER_COLLATERAL, PER_BLOCK_RATE_PRECISION, PRICE_PRECISION,
+};
 use crate::tests::helper::lending_helpers::{
-    self as h, LoanTerms, COLLATERAL_AMOUNT, DEPLOY_HEIGHT, INIT_TOKEN_SUPPLY, LOAN_AMOUNT,
-    APR_500_BPS, DURATION_BLOCKS,
+    self as h, LoanTerms, COLLATERAL_AMOUNT, DEPLOY_HEIGHT, DUST_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY,
+    INIT_TOKEN_SUPPLY, LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS,
 };
 
 use alkanes::tests::helpers::get_last_outpoint_sheet;
-use alkanes_support::cellpack::Cellpack;
+use alkanes_support::{cellpack::Cellpack, id::AlkaneId};
 use anyhow::Result;
 #[allow(unused_imports)]
 use metashrew_core::{println, stdio::{stdout, Write}};
 use protorune_support::balance_sheet::BalanceSheetOperations;
+use protorune_support::protostone::ProtostoneEdict;
 use wasm_bindgen_test::wasm_bindgen_test;
 
 /// Contract state constants (mirror contract's internal values)
@@ -163,6 +171,632 @@ fn test_case2_loan_default_claim_collateral() -> Result<()> {
     Ok(())
 }
 
+// ============================================================================
+// Creditor Note Burn Tests
+// ============================================================================
+
+/// Test that ClaimRepayment consumes the creditor auth token and that the
+/// tracked supply drops to zero, so the claim cannot be repeated.
+#[wasm_bindgen_test]
+fn test_claim_repayment_burns_creditor_note() -> Result<()> {
+    let (repay_block, ids) = h::setup_to_repaid_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 4, lending_id, 102)?;
+    assert_eq!(h::read_u128_le(&data, 0), 1, "One creditor note before the claim");
+
+    let claim_block = h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 5, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&(*lending_id).into()), 0, "Creditor note should be consumed");
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY,
+        "Creditor should still receive the repayment"
+    );
+
+    let data = h::call_view(DEPLOY_HEIGHT + 6, lending_id, 102)?;
+    assert_eq!(h::read_u128_le(&data, 0), 0, "No creditor notes left in circulation");
+
+    let again_block = h::claim_repayment(&claim_block, DEPLOY_HEIGHT + 7, lending_id)?;
+    h::assert_revert(&again_block, "Auth token is not in incoming alkanes")?;
+
+    println!("ClaimRepayment burned the creditor note");
+    Ok(())
+}
+
+/// Test that ClaimDefaultedCollateral consumes the creditor auth token.
+#[wasm_bindgen_test]
+fn test_claim_defaulted_collateral_burns_creditor_note() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let past_deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32 + 1;
+
+    let claim_block = h::claim_defaulted_collateral(&take_block, past_deadline, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&(*lending_id).into()), 0, "Creditor note should be consumed");
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    let data = h::call_view(past_deadline + 1, lending_id, 102)?;
+    assert_eq!(h::read_u128_le(&data, 0), 0, "No creditor notes left in circulation");
+
+    println!("ClaimDefaultedCollateral burned the creditor note");
+    Ok(())
+}
+
+// ============================================================================
+// Default Notice Tests
+// ============================================================================
+
+/// Notice period used by the default notice tests.
+const TEST_DEFAULT_NOTICE_BLOCKS: u128 = 100;
+
+/// Deploy + init an offer requiring notice of default + take.
+/// Returns the take block, IDs, terms and the repayment deadline.
+fn setup_default_notice_loan() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms, u32)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.default_notice_blocks = TEST_DEFAULT_NOTICE_BLOCKS;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    let deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32;
+    Ok((take_block, ids, terms, deadline))
+}
+
+/// Test that the creditor must give notice and wait out the notice period
+/// before claiming collateral, and that the notice is visible in views.
+#[wasm_bindgen_test]
+fn test_default_notice_required_before_claim() -> Result<()> {
+    const STATUS_OVERDUE: u128 = 1 << 8;
+    const STATUS_GRACE: u128 = 1 << 9;
+    const EVENT_DEFAULT_NOTICE: u128 = 9;
+
+    let (take_block, ids, _terms, deadline) = setup_default_notice_loan()?;
+    let lending_id = &ids.lending_contract;
+
+    let no_notice = h::claim_defaulted_collateral(&take_block, deadline + 1, lending_id)?;
+    h::assert_revert(&no_notice, "Notice of default required")?;
+
+    // Overdue without notice: repayment is still accepted
+    let data = h::call_view(deadline + 1, lending_id, 103)?;
+    assert_eq!(
+        h::read_u128_le(&data, 0),
+        STATE_LOAN_ACTIVE | STATUS_OVERDUE | STATUS_GRACE,
+        "Overdue loan awaiting notice is in its grace window"
+    );
+
+    let notice_height = deadline + 2;
+    let notice_block = h::notice_of_default(&no_notice, notice_height, lending_id)?;
+
+    let data = h::call_view(notice_height + 1, lending_id, 104)?;
+    assert_eq!(h::read_u128_le(&data, 0), TEST_DEFAULT_NOTICE_BLOCKS);
+    assert_eq!(h::read_u128_le(&data, 16), notice_height as u128);
+    let claimable_after = notice_height as u128 + TEST_DEFAULT_NOTICE_BLOCKS;
+    assert_eq!(h::read_u128_le(&data, 32), claimable_after);
+
+    let data = h::call_view(notice_height + 1, lending_id, 103)?;
+    assert_eq!(
+        h::read_u128_le(&data, 0),
+        STATE_LOAN_ACTIVE | STATUS_OVERDUE | STATUS_GRACE,
+        "Loan is in its grace window"
+    );
+
+    let data = h::call_view_with_inputs(notice_height + 1, lending_id, vec![96, 2, 1])?;
+    assert_eq!(h::read_u128_le(&data, 32 + 16), EVENT_DEFAULT_NOTICE, "Notice is recorded in history");
+
+    let in_window = h::claim_defaulted_collateral(&notice_block, claimable_after as u32, lending_id)?;
+    h::assert_revert(&in_window, "Default notice period has not elapsed")?;
+
+    let claim_block = h::claim_defaulted_collateral(&in_window, claimable_after as u32 + 1, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Default notice test passed");
+    Ok(())
+}
+
+/// Test that the borrower can still repay during the notice window.
+#[wasm_bindgen_test]
+fn test_default_notice_allows_late_repayment() -> Result<()> {
+    let (take_block, ids, terms, deadline) = setup_default_notice_loan()?;
+    let lending_id = &ids.lending_contract;
+
+    let notice_block = h::notice_of_default(&take_block, deadline + 1, lending_id)?;
+    let again_block = h::notice_of_default(&notice_block, deadline + 2, lending_id)?;
+    h::assert_revert(&again_block, "Notice of default already given")?;
+
+    let repay_block = h::repay_loan(&again_block, deadline + 3, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Borrower should recover collateral by repaying inside the window"
+    );
+
+    let data = h::call_view(deadline + 4, lending_id, 92)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_REPAID);
+
+    println!("Late repayment inside notice window succeeded");
+    Ok(())
+}
+
+/// Test that NoticeOfDefault is rejected when no notice is configured or
+/// before the deadline.
+#[wasm_bindgen_test]
+fn test_default_notice_invalid() -> Result<()> {
+    let (take_block, ids, _terms, deadline) = setup_default_notice_loan()?;
+    let early = h::notice_of_default(&take_block, deadline, &ids.lending_contract)?;
+    h::assert_revert(&early, "Loan has not defaulted yet - deadline not passed")?;
+
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let block = h::notice_of_default(&take_block, deadline + 1, &ids.lending_contract)?;
+    h::assert_revert(&block, "Loan does not require a notice of default")?;
+
+    println!("Invalid notices of default correctly rejected");
+    Ok(())
+}
+
+// ============================================================================
+// Offer Reservation Tests
+// ============================================================================
+
+/// Reservation deposit and period used by the reservation tests.
+const TEST_RESERVATION_DEPOSIT: u128 = COLLATERAL_AMOUNT / 100;
+const TEST_RESERVATION_BLOCKS: u128 = 20;
+
+/// Deploy + init an offer that takes reservations + reserve it.
+/// Returns the reserve block, IDs, terms and the reserved-until block.
+fn setup_reserved_offer() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms, u32)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.reservation_deposit = TEST_RESERVATION_DEPOSIT;
+    terms.reservation_blocks = TEST_RESERVATION_BLOCKS;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let reserve_block = h::reserve_offer(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    let reserved_until = DEPLOY_HEIGHT + 2 + TEST_RESERVATION_BLOCKS as u32;
+    Ok((reserve_block, ids, terms, reserved_until))
+}
+
+/// Take with only the collateral, leaving the wallet's other alkanes (e.g. a
+/// reservation note) behind.
+fn take_with_collateral_only(
+    prev_block: &bitcoin::Block,
+    height: u32,
+    ids: &h::LendingDeploymentIds,
+) -> Result<bitcoin::Block> {
+    let cellpack = Cellpack {
+        target: ids.lending_contract,
+        inputs: vec![1],
+    };
+    h::execute_cellpack_with_split(prev_block, height, cellpack, ids.collateral_token, COLLATERAL_AMOUNT)
+}
+
+/// Test that a reservation holds the offer for its note: others cannot take
+/// or cancel it, and the reserver gets the deposit back on take.
+#[wasm_bindgen_test]
+fn test_reservation_holds_offer() -> Result<()> {
+    let (reserve_block, ids, terms, reserved_until) = setup_reserved_offer()?;
+    let lending_id = &ids.lending_contract;
+
+    let sheet = get_last_outpoint_sheet(&reserve_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - TEST_RESERVATION_DEPOSIT);
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 115)?;
+    assert_eq!(data.len(), 80, "Reservation should be 5 × u128");
+    let note = AlkaneId { block: h::read_u128_le(&data, 0), tx: h::read_u128_le(&data, 16) };
+    assert_eq!(sheet.get(&note.into()), 1, "Reserver should hold the reservation note");
+    assert_eq!(h::read_u128_le(&data, 32), reserved_until as u128);
+    assert_eq!(h::read_u128_le(&data, 48), TEST_RESERVATION_DEPOSIT);
+
+    let sniped = take_with_collateral_only(&reserve_block, DEPLOY_HEIGHT + 4, &ids)?;
+    h::assert_revert_split(&sniped, "Loan offer is reserved")?;
+
+    let again = h::reserve_offer(&reserve_block, DEPLOY_HEIGHT + 5, lending_id, &terms)?;
+    h::assert_revert(&again, "Loan offer is reserved")?;
+
+    let cancel = h::cancel_loan_offer(&again, DEPLOY_HEIGHT + 6, lending_id)?;
+    h::assert_revert(&cancel, "Loan offer is reserved")?;
+
+    let take_block = h::take_loan(&cancel, reserved_until, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT,
+        "Deposit should be refunded on take"
+    );
+
+    println!("Reservation held the offer for its note");
+    Ok(())
+}
+
+/// Test that an expired reservation's deposit is forfeited: the take no
+/// longer refunds it and the creditor receives it with the repayment claim.
+#[wasm_bindgen_test]
+fn test_expired_reservation_forfeits_deposit() -> Result<()> {
+    let (reserve_block, ids, terms, reserved_until) = setup_reserved_offer()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(reserved_until + 1, lending_id, 115)?;
+    assert_eq!(h::read_u128_le(&data, 48), 0, "Expired reservation holds nothing");
+    assert_eq!(h::read_u128_le(&data, 64), TEST_RESERVATION_DEPOSIT);
+
+    let take_height = reserved_until + 2;
+    let take_block = h::take_loan(&reserve_block, take_height, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()),
+        INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT - TEST_RESERVATION_DEPOSIT,
+        "Deposit should not be refunded after expiry"
+    );
+
+    let repay_block = h::repay_loan(&take_block, take_height + 1, lending_id, &terms)?;
+    let claim_block = h::claim_repayment(&repay_block, take_height + 2, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Creditor should receive the forfeited deposit with the claim"
+    );
+
+    let data = h::call_view(take_height + 3, lending_id, 115)?;
+    assert_eq!(h::read_u128_le(&data, 64), 0, "Forfeited deposit should be paid out");
+
+    let data = h::call_view_with_inputs(take_height + 3, lending_id, vec![110, 0, 20])?;
+    let entries = h::read_u128_le(&data, 0) as usize;
+    let last = 32 + (entries - 1) * 112;
+    assert_eq!(h::read_u128_le(&data, last + 16), 3, "Paid with the repayment claim");
+    assert_eq!(h::read_u128_le(&data, last + 32), 1, "Paid from the debitor's deposit");
+    assert_eq!(h::read_u128_le(&data, last + 48), 2, "Paid to the creditor");
+    assert_eq!(h::read_u128_le(&data, last + 96), TEST_RESERVATION_DEPOSIT);
+
+    println!("Expired reservation deposit forfeited to the creditor");
+    Ok(())
+}
+
+/// Test that only an offer that stays takeable for the whole reservation can
+/// be reserved, so the deposit is never forfeited for a stale offer.
+#[wasm_bindgen_test]
+fn test_reservation_requires_takeable_offer() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.reservation_deposit = TEST_RESERVATION_DEPOSIT;
+    terms.reservation_blocks = TEST_RESERVATION_BLOCKS;
+    terms.max_take_delay_blocks = 30;
+
+    // Takeable through DEPLOY_HEIGHT + 31
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let block = h::reserve_offer(&init_block, DEPLOY_HEIGHT + 12, lending_id, &terms)?;
+    h::assert_revert(&block, "Loan offer would go stale during the reservation")?;
+    let block = h::reserve_offer(&block, DEPLOY_HEIGHT + 32, lending_id, &terms)?;
+    h::assert_revert(&block, "Loan offer is stale - creditor must refresh it")?;
+
+    // Refreshed at DEPLOY_HEIGHT + 33, takeable through DEPLOY_HEIGHT + 63
+    let block = h::refresh_offer(&block, DEPLOY_HEIGHT + 33, lending_id)?;
+    let reserve_block = h::reserve_offer(&block, DEPLOY_HEIGHT + 34, lending_id, &terms)?;
+    let reserved_until = DEPLOY_HEIGHT + 34 + TEST_RESERVATION_BLOCKS as u32;
+    let data = h::call_view(DEPLOY_HEIGHT + 35, lending_id, 115)?;
+    assert_eq!(h::read_u128_le(&data, 32), reserved_until as u128);
+
+    let take_block = h::take_loan(&reserve_block, reserved_until, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT,
+        "Deposit should be refunded on take"
+    );
+
+    println!("Reservation limited to the take window");
+    Ok(())
+}
+
+/// Test reservation validation at init and on offers without reservations.
+#[wasm_bindgen_test]
+fn test_reservation_validation() -> Result<()> {
+    let (init_block, ids) = h::setup_to_waiting_state()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.reservation_deposit = TEST_RESERVATION_DEPOSIT;
+    let block = h::reserve_offer(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    h::assert_revert(&block, "Offer does not take reservations")?;
+
+    let cases: Vec<(u128, u128, &str)> = vec![
+        (0, 0, "Reservation deposit and period must be set together"),
+        (FLAG_ZERO_COUPON_BOND, TEST_RESERVATION_BLOCKS, "Bond mode does not support reservations"),
+    ];
+    for (loan_flags, reservation_blocks, expected) in cases {
+        let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.loan_flags = loan_flags;
+        terms.reservation_deposit = TEST_RESERVATION_DEPOSIT;
+        terms.reservation_blocks = reservation_blocks;
+
+        let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+        let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+        h::assert_revert(&block, expected)?;
+    }
+
+    println!("Reservation validation passed");
+    Ok(())
+}
+
+// ============================================================================
+// Private Offer Tests
+// ============================================================================
+
+/// Test that a private offer can only be taken by a call carrying the allowed
+/// taker alkane. The harness wallet's leftover loan tokens stand in for the
+/// taker's note.
+#[wasm_bindgen_test]
+fn test_private_offer_requires_allowed_taker() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.allowed_taker = ids.loan_token;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let data = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 116)?;
+    assert_eq!(data.len(), 48, "Allowed taker should be 3 × u128");
+    assert_eq!(h::read_u128_le(&data, 0), 1, "Offer should be private");
+    assert_eq!(h::read_u128_le(&data, 16), ids.loan_token.block);
+    assert_eq!(h::read_u128_le(&data, 32), ids.loan_token.tx);
+
+    let sniped = take_with_collateral_only(&init_block, DEPLOY_HEIGHT + 3, &ids)?;
+    h::assert_revert_split(&sniped, "Loan offer is private")?;
+
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 4, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY,
+        "Allowed taker should receive the loan and keep its note"
+    );
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT);
+
+    println!("Private offer taken by the allowed taker");
+    Ok(())
+}
+
+/// Test that public offers report no allowed taker and that private offers
+/// reject invalid taker ids at init.
+#[wasm_bindgen_test]
+fn test_private_offer_validation() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 116)?;
+    assert_eq!(h::read_u128_le(&data, 0), 0, "Default offer should be public");
+
+    let cases: Vec<(AlkaneId, &str)> = vec![
+        (ids.lending_contract, "Allowed taker token cannot be the lending contract"),
+        (AlkaneId { block: 6, tx: 7 }, "Allowed taker token id is in reserved space"),
+    ];
+    for (allowed_taker, expected) in cases {
+        let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.allowed_taker = allowed_taker;
+
+        let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+        let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+        h::assert_revert(&block, expected)?;
+    }
+
+    println!("Private offer validation passed");
+    Ok(())
+}
+
+// ============================================================================
+// State Root Tests
+// ============================================================================
+
+/// Test that the state root is deterministic: unchanged by views, identical
+/// when the same blocks are indexed from scratch, and moved by a mutation.
+#[wasm_bindgen_test]
+fn test_state_root_commitment() -> Result<()> {
+    let (init_block, ids) = h::setup_to_waiting_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let root = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 117)?;
+    assert_eq!(root.len(), 32, "State root should be a sha256 hash");
+    let again = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 117)?;
+    assert_eq!(root, again, "Views should not move the state root");
+
+    h::take_loan(&init_block, DEPLOY_HEIGHT + 4, lending_id, &LoanTerms::default_from(&ids))?;
+    let taken = h::call_view(DEPLOY_HEIGHT + 5, lending_id, 117)?;
+    assert_ne!(root, taken, "Taking the loan should move the state root");
+
+    // A second indexer replaying the same blocks derives the same root
+    let (_init_block, replay_ids) = h::setup_to_waiting_state()?;
+    let replayed = h::call_view(DEPLOY_HEIGHT + 2, &replay_ids.lending_contract, 117)?;
+    assert_eq!(root, replayed, "Replayed state should hash identically");
+
+    println!("State root commitment passed");
+    Ok(())
+}
+
+/// Test GetCurrentHeight (opcode 118): the height of the block the call is
+/// indexed in, in any state.
+#[wasm_bindgen_test]
+fn test_get_current_height() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 1, lending_id, 118)?;
+    assert_eq!(data.len(), 16, "Current height should be a single u128");
+    assert_eq!(h::read_u128_le(&data, 0), DEPLOY_HEIGHT as u128 + 1);
+
+    let terms = LoanTerms::default_from(&ids);
+    h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    let data = h::call_view(DEPLOY_HEIGHT + 10, lending_id, 118)?;
+    assert_eq!(h::read_u128_le(&data, 0), DEPLOY_HEIGHT as u128 + 10);
+
+    println!("GetCurrentHeight test passed");
+    Ok(())
+}
+
+// ============================================================================
+// Init And Take Tests
+// ============================================================================
+
+/// Test InitAndTake (opcode 17): one call opens and takes the offer, the
+/// history records both events at the same height, and the loan then repays
+/// like any other.
+#[wasm_bindgen_test]
+fn test_init_and_take_lifecycle() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let terms = LoanTerms::default_from(&ids);
+
+    let take_block = h::init_and_take(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Loan tokens pass straight through");
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT);
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 90)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_ACTIVE);
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 2, lending_id, vec![96, 0, 10])?;
+    assert_eq!(h::read_u128_le(&data, 0), 2);
+    assert_eq!(h::read_u128_le(&data, 32 + 16), EVENT_OFFER_CREATED);
+    assert_eq!(h::read_u128_le(&data, 32 + 48 + 16), EVENT_LOAN_TAKEN);
+    assert_eq!(h::read_u128_le(&data, 32 + 48), DEPLOY_HEIGHT as u128 + 1);
+
+    let repay_block = h::repay_loan(&take_block, DEPLOY_HEIGHT + 3, lending_id, &terms)?;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - repayment);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("InitAndTake lifecycle passed");
+    Ok(())
+}
+
+/// Test that InitAndTake cannot take over an existing offer, and still
+/// honours the offer's own checks.
+#[wasm_bindgen_test]
+fn test_init_and_take_rejections() -> Result<()> {
+    let (init_block, ids) = h::setup_to_waiting_state()?;
+    let terms = LoanTerms::default_from(&ids);
+    let block = h::init_and_take(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    h::assert_revert(&block, "Contract already initialized")?;
+
+    // A private offer still needs its allowed taker
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.allowed_taker = AlkaneId { block: 2, tx: 99 };
+    let block = h::init_and_take(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    h::assert_revert(&block, "Loan offer is private")?;
+
+    println!("InitAndTake rejections passed");
+    Ok(())
+}
+
+// ============================================================================
+// Soft / Hard Deadline Tests
+// ============================================================================
+
+/// Grace period and late penalty used by the soft/hard deadline tests.
+const TEST_GRACE_BLOCKS: u128 = 50;
+const TEST_LATE_PENALTY_BPS: u128 = 200; // 2% of principal
+
+/// Deploy + init an offer with a grace period after its deadline + take.
+/// Returns the take block, IDs, terms and the soft deadline.
+fn setup_grace_period_loan() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms, u32)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.grace_blocks = TEST_GRACE_BLOCKS;
+    terms.late_penalty_bps = TEST_LATE_PENALTY_BPS;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    let deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32;
+    Ok((take_block, ids, terms, deadline))
+}
+
+/// Test that repaying between the soft and hard deadline is accepted with
+/// the late penalty, and that the views report both deadlines.
+#[wasm_bindgen_test]
+fn test_grace_period_late_repayment() -> Result<()> {
+    const STATUS_OVERDUE: u128 = 1 << 8;
+    const STATUS_GRACE: u128 = 1 << 9;
+
+    let (take_block, ids, terms, deadline) = setup_grace_period_loan()?;
+    let lending_id = &ids.lending_contract;
+    let hard_deadline = deadline + TEST_GRACE_BLOCKS as u32;
+
+    let data = h::call_view(deadline, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 80), 0, "No late penalty up to the soft deadline");
+
+    let late_height = deadline + 10;
+    let data = h::call_view(late_height, lending_id, 108)?;
+    assert_eq!(h::read_u128_le(&data, 0), deadline as u128);
+    assert_eq!(h::read_u128_le(&data, 48), 0, "Not defaulted inside the grace period");
+    assert_eq!(h::read_u128_le(&data, 64), hard_deadline as u128);
+
+    let data = h::call_view(late_height, lending_id, 103)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_ACTIVE | STATUS_OVERDUE | STATUS_GRACE);
+
+    let late_penalty = LOAN_AMOUNT * TEST_LATE_PENALTY_BPS / 10_000;
+    let due = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS) + late_penalty;
+    let data = h::call_view(late_height, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 0), due);
+    assert_eq!(h::read_u128_le(&data, 80), late_penalty);
+
+    let early_claim = h::claim_defaulted_collateral(&take_block, late_height, lending_id)?;
+    h::assert_revert(&early_claim, "Loan has not defaulted yet - deadline not passed")?;
+
+    let repay_block = h::repay_loan_with_amount(&early_claim, late_height + 1, lending_id, &terms, due)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - due);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Late repayment in the grace period paid {} penalty", late_penalty);
+    Ok(())
+}
+
+/// Test that collateral becomes claimable, and repayment closes, only after
+/// the hard deadline.
+#[wasm_bindgen_test]
+fn test_hard_deadline_default() -> Result<()> {
+    let (take_block, ids, terms, deadline) = setup_grace_period_loan()?;
+    let lending_id = &ids.lending_contract;
+    let hard_deadline = deadline + TEST_GRACE_BLOCKS as u32;
+
+    let in_grace = h::claim_defaulted_collateral(&take_block, hard_deadline, lending_id)?;
+    h::assert_revert(&in_grace, "Loan has not defaulted yet - deadline not passed")?;
+
+    let too_late = h::repay_loan(&in_grace, hard_deadline + 1, lending_id, &terms)?;
+    h::assert_revert(&too_late, "Loan has defaulted - deadline passed")?;
+
+    let claim_block = h::claim_defaulted_collateral(&too_late, hard_deadline + 2, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Collateral claimed after the hard deadline");
+    Ok(())
+}
+
+/// Test late penalty validation at init.
+#[wasm_bindgen_test]
+fn test_init_late_penalty_validation() -> Result<()> {
+    let cases: Vec<(u128, u128, u128, &str)> = vec![
+        (0, 0, TEST_LATE_PENALTY_BPS, "Late penalty requires a grace period"),
+        (0, TEST_GRACE_BLOCKS, 10_001, "Late penalty cannot exceed 100%"),
+        (
+            FLAG_ZERO_COUPON_BOND,
+            TEST_GRACE_BLOCKS,
+            TEST_LATE_PENALTY_BPS,
+            "Bond mode requires fixed full-term repayment",
+        ),
+    ];
+
+    for (loan_flags, grace_blocks, late_penalty_bps, expected) in cases {
+        let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.loan_flags = loan_flags;
+        terms.grace_blocks = grace_blocks;
+        terms.late_penalty_bps = late_penalty_bps;
+
+        let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+        let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+        h::assert_revert(&block, expected)?;
+    }
+
+    println!("Late penalty validation passed");
+    Ok(())
+}
+
 // ============================================================================
 // Loan Offer Cancellation Tests
 // ============================================================================
@@ -203,186 +837,1928 @@ fn test_cancel_loan_offer_success() -> Result<()> {
         "Collateral tokens should be unchanged (never deposited)"
     );
 
-    println!("\n=== LOAN OFFER CANCELLATION SUCCESSFUL ===");
+    println!("\n=== LOAN OFFER CANCELLATION SUCCESSFUL ===");
+    Ok(())
+}
+
+/// Test that cancelling a loan offer fails when the debitor has already taken.
+#[wasm_bindgen_test]
+fn test_cancel_loan_offer_fails_after_debitor_takes() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+
+    let cancel_block = h::cancel_loan_offer(&take_block, DEPLOY_HEIGHT + 3, &ids.lending_contract)?;
+
+    h::assert_revert(&cancel_block, "Cannot cancel - loan offer not in cancellable state")?;
+
+    println!("\n=== CANCEL CORRECTLY REJECTED — LOAN ALREADY TAKEN ===");
+    Ok(())
+}
+
+// ============================================================================
+// Insufficient Token Tests
+// ============================================================================
+
+/// Test that InitWithLoanOffer reverts when insufficient loan tokens are sent.
+/// Uses Edict+Cellpack split to pipe only half the required amount.
+#[wasm_bindgen_test]
+fn test_init_insufficient_loan() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let terms = LoanTerms::default_from(&ids);
+    let insufficient_amount = LOAN_AMOUNT / 2;
+
+    let init_cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+
+    let block = h::execute_cellpack_with_split(
+        &deploy_block,
+        DEPLOY_HEIGHT + 1,
+        init_cellpack,
+        ids.loan_token.clone(),
+        insufficient_amount,
+    )?;
+
+    h::assert_revert_split(&block, "Insufficient tokens")?;
+
+    println!("\n=== INIT CORRECTLY REJECTED — INSUFFICIENT LOAN TOKENS ===");
+    Ok(())
+}
+
+/// Test that TakeLoanWithCollateral reverts when insufficient collateral is sent.
+/// Uses Edict+Cellpack split to pipe only half the required collateral.
+#[wasm_bindgen_test]
+fn test_take_insufficient_collateral() -> Result<()> {
+    let (init_block, ids) = h::setup_to_waiting_state()?;
+    let insufficient_collateral = COLLATERAL_AMOUNT / 2;
+
+    let take_cellpack = Cellpack {
+        target: ids.lending_contract.clone(),
+        inputs: vec![1],
+    };
+
+    let block = h::execute_cellpack_with_split(
+        &init_block,
+        DEPLOY_HEIGHT + 2,
+        take_cellpack,
+        ids.collateral_token.clone(),
+        insufficient_collateral,
+    )?;
+
+    h::assert_revert_split(&block, "Insufficient tokens")?;
+
+    println!("\n=== TAKE CORRECTLY REJECTED — INSUFFICIENT COLLATERAL ===");
+    Ok(())
+}
+
+/// Repay with the loan tokens split over two edicts plus a stray collateral
+/// edict, in the given order, and check every token is settled exactly once.
+fn assert_repay_with_split_edicts(order: [usize; 3]) -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let due = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    let edicts = [
+        (ids.loan_token, due / 2),
+        (ids.loan_token, due - due / 2 + 1_000),
+        (ids.collateral_token, 1_000),
+    ];
+    let edicts = order
+        .iter()
+        .map(|&i| ProtostoneEdict {
+            id: edicts[i].0.into(),
+            amount: edicts[i].1,
+            output: 0,
+        })
+        .collect();
+    let cellpack = Cellpack {
+        target: ids.lending_contract,
+        inputs: vec![2],
+    };
+    let repay_block =
+        h::execute_cellpack_with_edicts(&take_block, DEPLOY_HEIGHT + 3, cellpack, edicts)?;
+
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()),
+        INIT_TOKEN_SUPPLY - due,
+        "Only the amount due should be kept, excess refunded once"
+    );
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()),
+        INIT_TOKEN_SUPPLY,
+        "Stray collateral and returned collateral should both arrive"
+    );
+    Ok(())
+}
+
+/// Test duplicate loan token transfers ahead of a stray token.
+#[wasm_bindgen_test]
+fn test_repay_duplicate_tokens_stray_last() -> Result<()> {
+    assert_repay_with_split_edicts([0, 1, 2])?;
+    println!("Repay with duplicate token edicts settled exactly");
+    Ok(())
+}
+
+/// Test a stray token interleaved between duplicate loan token transfers.
+#[wasm_bindgen_test]
+fn test_repay_duplicate_tokens_stray_interleaved() -> Result<()> {
+    assert_repay_with_split_edicts([1, 2, 0])?;
+    println!("Repay with interleaved token edicts settled exactly");
+    Ok(())
+}
+
+// ============================================================================
+// ClaimDefaultedCollateral Error Tests
+// ============================================================================
+
+/// Test that ClaimDefaultedCollateral reverts when the deadline has not yet passed.
+#[wasm_bindgen_test]
+fn test_claim_defaulted_collateral_before_deadline() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+
+    // Loan was taken at DEPLOY_HEIGHT + 2, deadline = (DEPLOY_HEIGHT + 2) + DURATION_BLOCKS.
+    // Attempt claim one block after take — well before the deadline.
+    let block = h::claim_defaulted_collateral(&take_block, DEPLOY_HEIGHT + 3, &ids.lending_contract)?;
+
+    h::assert_revert(&block, "Loan has not defaulted yet - deadline not passed")?;
+    println!("ClaimDefaultedCollateral before deadline correctly rejected");
+    Ok(())
+}
+
+// ============================================================================
+// InitWithLoanOffer Validation Error Tests
+// ============================================================================
+
+/// Test that InitWithLoanOffer reverts when collateral_amount is zero.
+#[wasm_bindgen_test]
+fn test_init_collateral_amount_zero() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = 0;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Collateral amount cannot be zero")?;
+    println!("Init collateral_amount=0 correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts when loan_amount is zero.
+#[wasm_bindgen_test]
+fn test_init_loan_amount_zero() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = 0;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Loan amount cannot be zero")?;
+    println!("Init loan_amount=0 correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts when duration_blocks is zero.
+#[wasm_bindgen_test]
+fn test_init_duration_zero() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.duration_blocks = 0;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Duration cannot be zero")?;
+    println!("Init duration=0 correctly rejected");
+    Ok(())
+}
+
+/// Test an interest-free loan (apr = 0): the quote is principal only and
+/// the creditor is repaid exactly the principal.
+#[wasm_bindgen_test]
+fn test_zero_apr_loan_lifecycle() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.apr = 0;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 0), LOAN_AMOUNT, "Amount due is the principal");
+    assert_eq!(h::read_u128_le(&data, 32), 0, "No interest accrues");
+
+    let repay_block = h::repay_loan_with_amount(
+        &take_block, DEPLOY_HEIGHT + 4, lending_id, &terms, LOAN_AMOUNT,
+    )?;
+    let claim_block = h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 5, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY,
+        "Creditor should be repaid exactly the principal"
+    );
+
+    println!("Interest-free loan repaid at principal");
+    Ok(())
+}
+
+/// Test that an interest-free offer cannot carry interest-based fees.
+#[wasm_bindgen_test]
+fn test_init_zero_apr_with_prepayment_penalty() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.apr = 0;
+    terms.loan_flags = FLAG_EARLY_REPAYMENT_REBATE;
+    terms.prepayment_penalty_bps = 500;
+    terms.prepayment_lockout_blocks = DURATION_BLOCKS / 2;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Interest-free loans cannot charge interest-based fees")?;
+    println!("Interest-free offer with prepayment penalty correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts when collateral and loan token are the same.
+#[wasm_bindgen_test]
+fn test_init_same_collateral_and_loan_token() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    // Set collateral token equal to loan token
+    terms.collateral_token = terms.loan_token.clone();
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Collateral and loan token cannot be the same")?;
+    println!("Init same-token correctly rejected");
+    Ok(())
+}
+
+/// Init with `terms` adjusted by `adjust` and assert it reverts with `expected`.
+fn assert_init_token_rejected(adjust: impl FnOnce(&mut LoanTerms, &AlkaneId), expected: &str) -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    adjust(&mut terms, &ids.lending_contract);
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, expected)
+}
+
+/// Test that InitWithLoanOffer rejects the zero AlkaneId as a token.
+#[wasm_bindgen_test]
+fn test_init_zero_token_id() -> Result<()> {
+    assert_init_token_rejected(
+        |terms, _| terms.collateral_token = AlkaneId { block: 0, tx: 0 },
+        "Collateral token id cannot be zero",
+    )?;
+    println!("Init with zero token id correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer rejects the lending contract as its own token.
+#[wasm_bindgen_test]
+fn test_init_self_token_id() -> Result<()> {
+    assert_init_token_rejected(
+        |terms, lending_id| terms.loan_token = *lending_id,
+        "Loan token cannot be the lending contract",
+    )?;
+    println!("Init with self as token correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer rejects ids in the reserved factory space.
+#[wasm_bindgen_test]
+fn test_init_reserved_token_id() -> Result<()> {
+    assert_init_token_rejected(
+        |terms, _| terms.collateral_token = AlkaneId { block: 6, tx: 2 },
+        "Collateral token id is in reserved space",
+    )?;
+    println!("Init with reserved token id correctly rejected");
+    Ok(())
+}
+
+/// Test that a failed supply query names the token and opcode it called.
+#[wasm_bindgen_test]
+fn test_init_undeployed_token_extcall_context() -> Result<()> {
+    assert_init_token_rejected(
+        |terms, _| terms.collateral_token = AlkaneId { block: 2, tx: 999 },
+        "Unable to query token supply: extcall to 2:999 opcode 101 failed",
+    )?;
+    println!("Supply query failure reported with extcall context");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts when called a second time
+/// (contract already initialized via `observe_initialization`).
+#[wasm_bindgen_test]
+fn test_init_already_initialized() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+
+    // Attempt a second init — should fail at observe_initialization
+    let terms = LoanTerms::default_from(&ids);
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 2, cellpack)?;
+
+    h::assert_revert(&block, "already initialized")?;
+    println!("Init already-initialized correctly rejected");
+    Ok(())
+}
+
+// ============================================================================
+// Collateral-to-Loan Ratio Bound Tests
+// ============================================================================
+
+/// Test that an offer exactly at the minimum collateral ratio is accepted
+/// (loan_amount == collateral_amount * MAX_LOAN_PER_COLLATERAL).
+#[wasm_bindgen_test]
+fn test_init_collateral_ratio_at_minimum() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = 1;
+    terms.loan_amount = MAX_LOAN_PER_COLLATERAL;
+
+    h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 92)?;
+    assert_eq!(
+        h::read_u128_le(&data, 0),
+        STATE_WAITING_FOR_DEBITOR_TAKE,
+        "Offer at the minimum collateral ratio should be accepted"
+    );
+    println!("Init at minimum collateral ratio accepted");
+    Ok(())
+}
+
+/// Test that an offer one unit below the minimum collateral ratio is rejected.
+#[wasm_bindgen_test]
+fn test_init_collateral_ratio_below_minimum() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = 1;
+    terms.loan_amount = MAX_LOAN_PER_COLLATERAL + 1;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Collateral-to-loan ratio below minimum")?;
+    println!("Init below minimum collateral ratio correctly rejected");
+    Ok(())
+}
+
+/// Test that an offer exactly at the maximum collateral ratio is accepted
+/// (collateral_amount == loan_amount * MAX_COLLATERAL_PER_LOAN).
+#[wasm_bindgen_test]
+fn test_init_collateral_ratio_at_maximum() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = MAX_COLLATERAL_PER_LOAN;
+    terms.loan_amount = 1;
+
+    h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 92)?;
+    assert_eq!(
+        h::read_u128_le(&data, 0),
+        STATE_WAITING_FOR_DEBITOR_TAKE,
+        "Offer at the maximum collateral ratio should be accepted"
+    );
+    println!("Init at maximum collateral ratio accepted");
+    Ok(())
+}
+
+/// Test that an offer one unit above the maximum collateral ratio is rejected.
+#[wasm_bindgen_test]
+fn test_init_collateral_ratio_above_maximum() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = MAX_COLLATERAL_PER_LOAN + 1;
+    terms.loan_amount = 1;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Collateral-to-loan ratio above maximum")?;
+    println!("Init above maximum collateral ratio correctly rejected");
+    Ok(())
+}
+
+/// Test that init caches both token supplies (GetTokenSupplies, opcode 105).
+#[wasm_bindgen_test]
+fn test_init_caches_token_supplies() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 105)?;
+    assert_eq!(h::read_u128_le(&data, 0), INIT_TOKEN_SUPPLY, "Collateral supply cached");
+    assert_eq!(h::read_u128_le(&data, 16), INIT_TOKEN_SUPPLY, "Loan supply cached");
+
+    println!("Token supplies cached at init");
+    Ok(())
+}
+
+/// Test that init rejects amounts the token supply can never cover.
+#[wasm_bindgen_test]
+fn test_init_amount_exceeds_token_supply() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = INIT_TOKEN_SUPPLY + 1;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, "Collateral amount exceeds token supply")?;
+
+    println!("Init with collateral above token supply correctly rejected");
+    Ok(())
+}
+
+// ============================================================================
+// Extreme Token Supply Tests
+// ============================================================================
+
+/// Test a loan against a single indivisible unit of collateral: the whole
+/// supply moves on take and goes to the creditor on default.
+#[wasm_bindgen_test]
+fn test_indivisible_collateral_default() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(DUST_TOKEN_SUPPLY, INIT_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.collateral_amount = DUST_TOKEN_SUPPLY;
+    terms.loan_amount = MAX_LOAN_PER_COLLATERAL;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), 0, "The only unit is locked");
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 105)?;
+    assert_eq!(h::read_u128_le(&data, 0), DUST_TOKEN_SUPPLY, "Collateral supply cached");
+
+    let past_deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32 + 1;
+    let claim_block = h::claim_defaulted_collateral(&take_block, past_deadline, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), DUST_TOKEN_SUPPLY);
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Indivisible collateral claimed on default");
+    Ok(())
+}
+
+/// Test a loan of the single unit of a supply-1 token: its interest rounds
+/// down to nothing, so the quote and the repayment are exactly the principal.
+#[wasm_bindgen_test]
+fn test_dust_loan_interest_rounds_down() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(INIT_TOKEN_SUPPLY, DUST_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = DUST_TOKEN_SUPPLY;
+    terms.collateral_amount = MAX_COLLATERAL_PER_LOAN;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    assert_eq!(calculate_repayment_amount(DUST_TOKEN_SUPPLY, APR_500_BPS, DURATION_BLOCKS), 1);
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 0), DUST_TOKEN_SUPPLY, "Amount due is the principal");
+    assert_eq!(h::read_u128_le(&data, 32), 0, "Interest rounds down to zero");
+
+    let repay_block = h::repay_loan(&take_block, DEPLOY_HEIGHT + 4, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), 0, "The only unit went back to the contract");
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    let claim_block = h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 5, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), DUST_TOKEN_SUPPLY);
+
+    println!("Dust loan repaid at par");
+    Ok(())
+}
+
+/// Test loans of a token with supply u128::MAX: a large loan repays exactly,
+/// an interest-free loan of the entire supply round-trips, and an interest
+/// bearing loan whose amount due exceeds u128 is rejected at init.
+#[wasm_bindgen_test]
+fn test_huge_supply_loans() -> Result<()> {
+    let principal: u128 = 1_000_000_000_000_000_000_000_000_000_000;
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = principal;
+    terms.collateral_amount = principal;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    let expected_due = calculate_repayment_amount(principal, APR_500_BPS, DURATION_BLOCKS);
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 0), expected_due);
+
+    let repay_block = h::repay_loan(&take_block, DEPLOY_HEIGHT + 4, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), HUGE_TOKEN_SUPPLY - expected_due);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), HUGE_TOKEN_SUPPLY);
+
+    // The entire supply, interest-free
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = HUGE_TOKEN_SUPPLY;
+    terms.collateral_amount = HUGE_TOKEN_SUPPLY;
+    terms.apr = 0;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    let repay_block = h::repay_loan(&take_block, DEPLOY_HEIGHT + 3, lending_id, &terms)?;
+    let claim_block = h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 4, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), HUGE_TOKEN_SUPPLY);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), HUGE_TOKEN_SUPPLY);
+
+    // The entire supply with interest cannot be repaid in one amount
+    let (_deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = HUGE_TOKEN_SUPPLY;
+    terms.collateral_amount = HUGE_TOKEN_SUPPLY;
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, "Overflow adding interest to principal")?;
+
+    println!("Huge supply loans passed");
+    Ok(())
+}
+
+// ============================================================================
+// Early Repayment Rebate Tests
+// ============================================================================
+
+/// Break fee used by the rebate tests: 10% of the rebated interest.
+const TEST_BREAK_FEE_BPS: u128 = 1_000;
+
+/// Deploy + init a rebate-mode offer + take. Returns the take block, IDs and terms.
+fn setup_rebate_loan() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_EARLY_REPAYMENT_REBATE;
+    terms.break_fee_bps = TEST_BREAK_FEE_BPS;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    Ok((take_block, ids, terms))
+}
+
+/// Take and repay a rebate-mode loan in one block with the given flags, and
+/// check the debitor paid exactly the early repayment for `charged_blocks`.
+fn assert_same_block_repayment(loan_flags: u128, charged_blocks: u128) -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = loan_flags;
+    terms.break_fee_bps = TEST_BREAK_FEE_BPS;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let full_term = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    let block = h::take_and_repay_in_block(
+        &init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms, full_term,
+    )?;
+
+    let expected_due = calculate_early_repayment_amount(
+        LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS, charged_blocks, TEST_BREAK_FEE_BPS,
+    );
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()),
+        INIT_TOKEN_SUPPLY - expected_due,
+        "Same-block repayment should charge {} block(s) of interest", charged_blocks
+    );
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+    Ok(())
+}
+
+/// Test that repaying in the take block charges no interest by default,
+/// only the break fee on the full rebate.
+#[wasm_bindgen_test]
+fn test_same_block_repayment_charges_no_interest() -> Result<()> {
+    assert_same_block_repayment(FLAG_EARLY_REPAYMENT_REBATE, 0)?;
+    println!("Same-block repayment charged no interest");
+    Ok(())
+}
+
+/// Test that FLAG_MINIMUM_BLOCK_INTEREST charges one block of interest for a
+/// repayment in the take block.
+#[wasm_bindgen_test]
+fn test_same_block_repayment_minimum_interest() -> Result<()> {
+    assert_same_block_repayment(FLAG_EARLY_REPAYMENT_REBATE | FLAG_MINIMUM_BLOCK_INTEREST, 1)?;
+    println!("Same-block repayment charged one block of interest");
+    Ok(())
+}
+
+/// Test that FLAG_MINIMUM_BLOCK_INTEREST is rejected without per-block accrual.
+#[wasm_bindgen_test]
+fn test_init_minimum_interest_requires_accrual() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_MINIMUM_BLOCK_INTEREST;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Minimum block interest requires per-block accrual")?;
+    println!("Minimum block interest without accrual correctly rejected");
+    Ok(())
+}
+
+/// Test an absolute-deadline loan taken after some delay: the deadline stays
+/// at the offered height and the debitor pays interest only for the blocks
+/// left when taking it.
+#[wasm_bindgen_test]
+fn test_absolute_deadline_lifecycle() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let deadline = DEPLOY_HEIGHT as u128 + 1 + DURATION_BLOCKS;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_ABSOLUTE_DEADLINE;
+    terms.duration_blocks = deadline;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_height = DEPLOY_HEIGHT + 11;
+    let take_block = h::take_loan(&init_block, take_height, lending_id, &terms)?;
+
+    let data = h::call_view(take_height + 1, lending_id, 108)?;
+    assert_eq!(h::read_u128_le(&data, 0), deadline, "Deadline should be the offered height");
+
+    let remaining = deadline - take_height as u128;
+    let expected_due = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, remaining);
+    assert!(
+        expected_due < calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS),
+        "Late take should shorten the term"
+    );
+    let repay_block =
+        h::repay_loan_with_amount(&take_block, take_height + 2, lending_id, &terms, expected_due)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - expected_due);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Absolute deadline {} charged {} blocks of interest", deadline, remaining);
+    Ok(())
+}
+
+/// Test that an absolute-deadline offer cannot be taken once the deadline is reached.
+#[wasm_bindgen_test]
+fn test_absolute_deadline_take_after_deadline() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let deadline = DEPLOY_HEIGHT + 10;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_ABSOLUTE_DEADLINE;
+    terms.duration_blocks = deadline as u128;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let block = h::take_loan(&init_block, deadline, &ids.lending_contract, &terms)?;
+
+    h::assert_revert(&block, "Loan offer has expired")?;
+    println!("Take after absolute deadline correctly rejected");
+    Ok(())
+}
+
+/// Test a loan quoted with a per-block rate: interest is rate × blocks with
+/// no BLOCKS_PER_YEAR rounding, and GetInterestRate (opcode 112) reports the
+/// quoted rate exactly alongside its APR equivalent.
+#[wasm_bindgen_test]
+fn test_per_block_rate_lifecycle() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let rate_per_block = PER_BLOCK_RATE_PRECISION / 1_000_000; // 0.0001% per block
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_PER_BLOCK_RATE;
+    terms.apr = rate_per_block;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let data = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 112)?;
+    assert_eq!(data.len(), 80, "Interest rate should be 5 × u128");
+    assert_eq!(h::read_u128_le(&data, 0), 1, "Rate should be quoted per block");
+    assert_eq!(h::read_u128_le(&data, 16), rate_per_block);
+    assert_eq!(
+        h::read_u128_le(&data, 32),
+        rate_per_block * APR_PRECISION * BLOCKS_PER_YEAR / PER_BLOCK_RATE_PRECISION
+    );
+    assert_eq!(h::read_u128_le(&data, 48), rate_per_block);
+    assert_eq!(h::read_u128_le(&data, 64), APR_PRECISION, "APR equivalent uses the default precision");
+
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 3, lending_id, &terms)?;
+    let expected_due =
+        calculate_per_block_repayment_amount(LOAN_AMOUNT, rate_per_block, DURATION_BLOCKS);
+    let repay_block =
+        h::repay_loan_with_amount(&take_block, DEPLOY_HEIGHT + 4, lending_id, &terms, expected_due)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - expected_due);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Per-block rate loan repaid {}", expected_due);
+    Ok(())
+}
+
+/// Test GetInterestRate (opcode 112) for an APR-quoted loan.
+#[wasm_bindgen_test]
+fn test_get_interest_rate_annual() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 112)?;
+    assert_eq!(h::read_u128_le(&data, 0), 0, "Rate should be quoted as APR");
+    assert_eq!(h::read_u128_le(&data, 16), APR_500_BPS);
+    assert_eq!(h::read_u128_le(&data, 32), APR_500_BPS);
+    assert_eq!(
+        h::read_u128_le(&data, 48),
+        APR_500_BPS * PER_BLOCK_RATE_PRECISION / (APR_PRECISION * BLOCKS_PER_YEAR)
+    );
+    assert_eq!(h::read_u128_le(&data, 64), APR_PRECISION);
+
+    println!("GetInterestRate annual test passed");
+    Ok(())
+}
+
+/// Test an offer quoted with a finer APR precision: interest, GetInterestRate
+/// and GetRealizedYield all use the loan's precision.
+#[wasm_bindgen_test]
+fn test_custom_apr_precision_lifecycle() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let precision: u128 = 1_000_000; // 6 decimal places of a percent
+    let apr: u128 = 51_234; // 5.1234%
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.apr_precision = precision;
+    terms.apr = apr;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let data = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 112)?;
+    assert_eq!(h::read_u128_le(&data, 0), 0, "Rate should be quoted as APR");
+    assert_eq!(h::read_u128_le(&data, 16), apr);
+    assert_eq!(h::read_u128_le(&data, 32), apr, "APR is reported at the loan's precision");
+    assert_eq!(
+        h::read_u128_le(&data, 48),
+        apr * PER_BLOCK_RATE_PRECISION / (precision * BLOCKS_PER_YEAR)
+    );
+    assert_eq!(h::read_u128_le(&data, 64), precision);
+
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 3, lending_id, &terms)?;
+    let interest = LOAN_AMOUNT * apr * DURATION_BLOCKS / (precision * BLOCKS_PER_YEAR);
+    let expected_due = LOAN_AMOUNT + interest;
+    let repay_block =
+        h::repay_loan_with_amount(&take_block, DEPLOY_HEIGHT + 4, lending_id, &terms, expected_due)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - expected_due);
+
+    let data = h::call_view(DEPLOY_HEIGHT + 5, lending_id, 113)?;
+    assert_eq!(h::read_u128_le(&data, 16), interest);
+    assert_eq!(
+        h::read_u128_le(&data, 48),
+        interest * precision * BLOCKS_PER_YEAR / LOAN_AMOUNT,
+        "Realized yield is annualized at the loan's precision"
+    );
+
+    println!("Custom APR precision loan repaid {}", expected_due);
+    Ok(())
+}
+
+/// Test that a large loan quoted at the finest APR precision prices its
+/// interest exactly: principal × rate exceeds u128 long before the interest
+/// does.
+#[wasm_bindgen_test]
+fn test_fine_precision_large_loan() -> Result<()> {
+    let principal: u128 = 1_000_000_000_000_000_000_000_000; // 1e24
+    let precision: u128 = 1_000_000_000_000;
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = principal;
+    terms.collateral_amount = principal;
+    terms.apr_precision = precision;
+    terms.apr = precision / 10; // 10%
+    terms.duration_blocks = BLOCKS_PER_YEAR;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    let expected_due = principal + principal / 10;
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 0), expected_due);
+
+    let repay_block =
+        h::repay_loan_with_amount(&take_block, DEPLOY_HEIGHT + 4, lending_id, &terms, expected_due)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), HUGE_TOKEN_SUPPLY - expected_due);
+
+    println!("Fine precision loan repaid {}", expected_due);
+    Ok(())
+}
+
+/// Test that an APR precision must be a supported power of ten and cannot be
+/// combined with a per-block rate.
+#[wasm_bindgen_test]
+fn test_apr_precision_validation() -> Result<()> {
+    let range_error = "APR precision must be a power of ten from 1e4 to 1e12";
+    let cases: Vec<(u128, u128, &str)> = vec![
+        (1_000, 0, range_error),
+        (12_345, 0, range_error),
+        (10_000_000_000_000, 0, range_error),
+        (1_000_000, FLAG_PER_BLOCK_RATE, "APR precision does not apply to per-block rates"),
+    ];
+    for (apr_precision, loan_flags, expected) in cases {
+        let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.apr_precision = apr_precision;
+        terms.loan_flags = loan_flags;
+
+        let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+        let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+        h::assert_revert(&block, expected)?;
+    }
+
+    println!("APR precision validation passed");
+    Ok(())
+}
+
+/// Test that an offer with max_take_delay_blocks goes stale, and that a
+/// creditor refresh makes it takeable again.
+#[wasm_bindgen_test]
+fn test_stale_offer_requires_refresh() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.max_take_delay_blocks = 10;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let stale_height = DEPLOY_HEIGHT + 12;
+    let block = h::take_loan(&init_block, stale_height, lending_id, &terms)?;
+    h::assert_revert(&block, "Loan offer is stale - creditor must refresh it")?;
+
+    let refresh_block = h::refresh_offer(&init_block, stale_height + 1, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&refresh_block)?;
+    assert_eq!(sheet.get(&lending_id.clone().into()), 1, "Auth token should be returned");
+
+    let take_block = h::take_loan(&refresh_block, stale_height + 11, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Debitor should receive the loan");
+
+    println!("Stale offer refresh test passed");
+    Ok(())
+}
+
+/// Test that absolute deadlines must lie in the future and exclude bond mode.
+#[wasm_bindgen_test]
+fn test_init_absolute_deadline_validation() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_ABSOLUTE_DEADLINE;
+    terms.duration_blocks = DEPLOY_HEIGHT as u128 + 1;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, "Deadline must be in the future")?;
+
+    terms.loan_flags = FLAG_ABSOLUTE_DEADLINE | FLAG_ZERO_COUPON_BOND;
+    terms.duration_blocks = DEPLOY_HEIGHT as u128 + 1 + DURATION_BLOCKS;
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 2, cellpack)?;
+    h::assert_revert(&block, "Bond mode requires fixed full-term repayment")?;
+
+    println!("Absolute deadline validation passed");
+    Ok(())
+}
+
+/// Test repaying a rebate-mode loan halfway through its duration.
+/// The debitor pays principal + accrued interest + break fee (excess refunded)
+/// and the creditor claims exactly that amount.
+#[wasm_bindgen_test]
+fn test_early_repayment_rebate_lifecycle() -> Result<()> {
+    let (take_block, ids, terms) = setup_rebate_loan()?;
+    let lending_id = &ids.lending_contract;
+
+    let elapsed = DURATION_BLOCKS / 2;
+    let repay_height = DEPLOY_HEIGHT + 2 + elapsed as u32;
+    let expected_due = calculate_early_repayment_amount(
+        LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS, elapsed, TEST_BREAK_FEE_BPS,
+    );
+    let full_term = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    assert!(expected_due < full_term, "Early repayment should be cheaper than full term");
+
+    // Helper sends the full-term amount; the excess must be refunded
+    let repay_block = h::repay_loan(&take_block, repay_height, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()),
+        INIT_TOKEN_SUPPLY - expected_due,
+        "Debitor should only pay principal + accrued interest + break fee"
+    );
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Debitor should get collateral back after early repayment"
+    );
+
+    let claim_block = h::claim_repayment(&repay_block, repay_height + 1, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY,
+        "Creditor should claim exactly the amount the debitor repaid"
+    );
+
+    println!("Early repayment paid {} instead of {}", expected_due, full_term);
+    Ok(())
+}
+
+/// Test GetRepaymentQuote (opcode 95) surfaces the break fee for a rebate-mode loan.
+#[wasm_bindgen_test]
+fn test_get_repayment_quote_rebate() -> Result<()> {
+    let (_take_block, ids, _terms) = setup_rebate_loan()?;
+
+    let elapsed = DURATION_BLOCKS / 4;
+    let data = h::call_view(DEPLOY_HEIGHT + 2 + elapsed as u32, &ids.lending_contract, 95)?;
+    assert_eq!(data.len(), 96, "Repayment quote should be 6 × u128");
+
+    let amount_due = h::read_u128_le(&data, 0);
+    let principal = h::read_u128_le(&data, 16);
+    let interest = h::read_u128_le(&data, 32);
+    let break_fee = h::read_u128_le(&data, 48);
+
+    let full_interest = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS) - LOAN_AMOUNT;
+    let accrued_interest = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, elapsed) - LOAN_AMOUNT;
+
+    assert_eq!(principal, LOAN_AMOUNT);
+    assert_eq!(interest, accrued_interest, "Only accrued interest should be charged");
+    assert_eq!(break_fee, (full_interest - accrued_interest) * TEST_BREAK_FEE_BPS / 10_000);
+    assert_eq!(amount_due, principal + interest + break_fee);
+    assert_eq!(
+        amount_due,
+        calculate_early_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS, elapsed, TEST_BREAK_FEE_BPS),
+    );
+
+    println!("GetRepaymentQuote rebate test passed");
+    Ok(())
+}
+
+/// Test GetRepaymentQuote (opcode 95) on a default (full-term) loan reports no break fee.
+#[wasm_bindgen_test]
+fn test_get_repayment_quote_full_term() -> Result<()> {
+    let (_take_block, ids) = h::setup_to_active_state()?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, &ids.lending_contract, 95)?;
+    let expected = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    assert_eq!(h::read_u128_le(&data, 0), expected, "Full-term loans owe full interest at any time");
+    assert_eq!(h::read_u128_le(&data, 48), 0, "Full-term loans have no break fee");
+    assert_eq!(h::read_u128_le(&data, 64), 0, "No prepayment penalty without a lockout");
+
+    println!("GetRepaymentQuote full-term test passed");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts when the break fee exceeds 100%.
+#[wasm_bindgen_test]
+fn test_init_break_fee_above_maximum() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_EARLY_REPAYMENT_REBATE;
+    terms.break_fee_bps = 10_001;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Break fee cannot exceed 100%")?;
+    println!("Init break fee > 100% correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts when a break fee is set without the rebate flag.
+#[wasm_bindgen_test]
+fn test_init_break_fee_without_rebate() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.break_fee_bps = TEST_BREAK_FEE_BPS;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Break fee requires early repayment rebate")?;
+    println!("Init break fee without rebate correctly rejected");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer reverts on unknown loan flag bits.
+#[wasm_bindgen_test]
+fn test_init_unsupported_loan_flags() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = 1 << 127;
+
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+
+    h::assert_revert(&block, "Unsupported loan flags")?;
+    println!("Init unsupported loan flags correctly rejected");
+    Ok(())
+}
+
+// ============================================================================
+// Prepayment Penalty Tests
+// ============================================================================
+
+/// Prepayment penalty used by the tests: 50% of the remaining interest.
+const TEST_PREPAYMENT_PENALTY_BPS: u128 = 5_000;
+
+/// Deploy + init a rebate-mode offer with a prepayment penalty during the
+/// first half of the duration + take. Returns the take block, IDs and terms.
+fn setup_prepayment_penalty_loan() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_EARLY_REPAYMENT_REBATE;
+    terms.prepayment_penalty_bps = TEST_PREPAYMENT_PENALTY_BPS;
+    terms.prepayment_lockout_blocks = DURATION_BLOCKS / 2;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    Ok((take_block, ids, terms))
+}
+
+/// Test that repaying inside the lockout charges the prepayment penalty on top
+/// of the rebated repayment, and that sending only the rebated amount reverts.
+#[wasm_bindgen_test]
+fn test_prepayment_penalty_within_lockout() -> Result<()> {
+    let (take_block, ids, terms) = setup_prepayment_penalty_loan()?;
+    let lending_id = &ids.lending_contract;
+
+    let elapsed = 100u128;
+    let repay_height = DEPLOY_HEIGHT + 2 + elapsed as u32;
+    let rebated = |elapsed: u128| {
+        calculate_early_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS, elapsed, 0)
+    };
+    let penalty = |elapsed: u128| {
+        calculate_prepayment_penalty(
+            LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS, elapsed, TEST_PREPAYMENT_PENALTY_BPS,
+        )
+    };
+    assert!(penalty(elapsed) > 0, "Penalty should be non-zero inside the lockout");
+
+    // The rebated amount alone is insufficient inside the lockout
+    let short_block =
+        h::repay_loan_with_amount(&take_block, repay_height, lending_id, &terms, rebated(elapsed))?;
+    h::assert_revert(&short_block, "Insufficient tokens")?;
+
+    // The reverted attempt refunded its tokens to vout 0, so spend from there
+    let due = rebated(elapsed + 1) + penalty(elapsed + 1);
+    let repay_block =
+        h::repay_loan_with_amount(&short_block, repay_height + 1, lending_id, &terms, due)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - due,
+        "Debitor should pay the rebated amount plus the penalty"
+    );
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Debitor should get collateral back after paying the penalty"
+    );
+
+    let data = h::call_view(repay_height + 2, lending_id, 92)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_REPAID);
+
+    println!("Prepayment penalty charged: {}", penalty(elapsed + 1));
+    Ok(())
+}
+
+/// Test that repaying after the lockout charges no prepayment penalty.
+#[wasm_bindgen_test]
+fn test_prepayment_penalty_after_lockout() -> Result<()> {
+    let (take_block, ids, terms) = setup_prepayment_penalty_loan()?;
+    let lending_id = &ids.lending_contract;
+
+    let repay_height = DEPLOY_HEIGHT + 2 + terms.prepayment_lockout_blocks as u32;
+
+    let data = h::call_view(repay_height, lending_id, 95)?;
+    assert_eq!(h::read_u128_le(&data, 64), 0, "No penalty once the lockout has ended");
+
+    // Helper sends the full-term amount; only the rebated amount is kept
+    let repay_block = h::repay_loan(&take_block, repay_height + 1, lending_id, &terms)?;
+    let elapsed = terms.prepayment_lockout_blocks + 1;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()),
+        INIT_TOKEN_SUPPLY
+            - calculate_early_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS, elapsed, 0),
+        "No penalty on top of the rebated amount after the lockout"
+    );
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Debitor should get collateral back after the lockout"
+    );
+
+    println!("Prepayment after lockout charged no penalty");
+    Ok(())
+}
+
+/// Test that InitWithLoanOffer validates the prepayment penalty parameters.
+#[wasm_bindgen_test]
+fn test_init_invalid_prepayment_penalty() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let cases: Vec<(u128, u128, &str)> = vec![
+        (10_001, 1, "Prepayment penalty cannot exceed 100%"),
+        (TEST_PREPAYMENT_PENALTY_BPS, 0, "Prepayment penalty and lockout must be set together"),
+        (0, 1, "Prepayment penalty and lockout must be set together"),
+        (TEST_PREPAYMENT_PENALTY_BPS, DURATION_BLOCKS + 1, "Prepayment lockout cannot exceed duration"),
+        (TEST_PREPAYMENT_PENALTY_BPS, DURATION_BLOCKS / 2, "Prepayment penalty requires early repayment rebate"),
+    ];
+
+    for (i, (penalty_bps, lockout_blocks, expected)) in cases.into_iter().enumerate() {
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.prepayment_penalty_bps = penalty_bps;
+        terms.prepayment_lockout_blocks = lockout_blocks;
+
+        let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+        let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1 + i as u32, cellpack)?;
+        h::assert_revert(&block, expected)?;
+    }
+
+    println!("Invalid prepayment penalty parameters correctly rejected");
+    Ok(())
+}
+
+/// Test that the break fee and the prepayment penalty may add up to 100% of
+/// the remaining interest but no more.
+#[wasm_bindgen_test]
+fn test_init_break_fee_plus_prepayment_penalty_limit() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_EARLY_REPAYMENT_REBATE;
+    terms.prepayment_penalty_bps = TEST_PREPAYMENT_PENALTY_BPS;
+    terms.prepayment_lockout_blocks = DURATION_BLOCKS / 2;
+
+    terms.break_fee_bps = 10_000 - TEST_PREPAYMENT_PENALTY_BPS + 1;
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, "Break fee and prepayment penalty cannot exceed 100% combined")?;
+
+    terms.break_fee_bps = 10_000 - TEST_PREPAYMENT_PENALTY_BPS;
+    h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    let data = h::call_view(DEPLOY_HEIGHT + 3, &ids.lending_contract, 92)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_WAITING_FOR_DEBITOR_TAKE);
+
+    println!("Break fee plus prepayment penalty limited to 100%");
+    Ok(())
+}
+
+// ============================================================================
+// Interest Capitalization Tests
+// ============================================================================
+
+/// Deadline extension approved by the creditor in the capitalization tests.
+const TEST_CAPITALIZATION_EXTENSION: u128 = 1_000;
+
+/// History event codes (mirror contract's internal values)
+const EVENT_OFFER_CREATED: u128 = 0;
+const EVENT_LOAN_TAKEN: u128 = 1;
+const EVENT_LOAN_REPAID: u128 = 2;
+const EVENT_INTEREST_CAPITALIZED: u128 = 6;
+
+/// Test the full capitalization flow: creditor approves, debitor executes,
+/// accrued interest moves into principal, the deadline is extended and the
+/// loan can be repaid on the new terms.
+#[wasm_bindgen_test]
+fn test_capitalize_interest_lifecycle() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let terms = LoanTerms::default_from(&ids);
+
+    let debitor_note = h::get_debitor_note(DEPLOY_HEIGHT + 3, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(sheet.get(&debitor_note.into()), 1, "Debitor should hold the note after take");
+
+    let approve_block = h::approve_capitalization(
+        &take_block, DEPLOY_HEIGHT + 100, lending_id, TEST_CAPITALIZATION_EXTENSION,
+    )?;
+    let capitalize_height = DEPLOY_HEIGHT + 200;
+    let capitalize_block =
+        h::capitalize_interest(&approve_block, capitalize_height, lending_id, &debitor_note)?;
+
+    let sheet = get_last_outpoint_sheet(&capitalize_block)?;
+    assert_eq!(sheet.get(&debitor_note.into()), 1, "Debitor note should be returned");
+
+    let elapsed = (capitalize_height - (DEPLOY_HEIGHT + 2)) as u128;
+    let new_principal = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, elapsed);
+    let new_deadline = DEPLOY_HEIGHT as u128 + 2 + DURATION_BLOCKS + TEST_CAPITALIZATION_EXTENSION;
+    let new_duration = new_deadline - capitalize_height as u128;
+    assert!(new_principal > LOAN_AMOUNT, "Accrued interest should be non-zero");
+
+    let data = h::call_view(capitalize_height + 1, lending_id, 90)?;
+    assert_eq!(h::read_u128_le(&data, 96), new_principal, "Principal should include accrued interest");
+    assert_eq!(h::read_u128_le(&data, 112), new_duration, "Duration should run to the new deadline");
+    assert_eq!(h::read_u128_le(&data, 144), new_deadline, "Deadline should be extended");
+    assert_eq!(h::read_u128_le(&data, 160), DEPLOY_HEIGHT as u128 + 2, "Start block is unchanged");
+
+    let expected_repayment = calculate_repayment_amount(new_principal, APR_500_BPS, new_duration);
+    let data = h::call_view(capitalize_height + 1, lending_id, 91)?;
+    assert_eq!(h::read_u128_le(&data, 0), expected_repayment);
+
+    let repay_block = h::repay_loan_with_amount(
+        &capitalize_block, capitalize_height + 2, lending_id, &terms, expected_repayment,
+    )?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Debitor should get collateral back after repaying the capitalized loan"
+    );
+
+    let data = h::call_view_with_inputs(capitalize_height + 3, lending_id, vec![96, 0, 10])?;
+    assert_eq!(h::read_u128_le(&data, 0), 4, "History should hold four entries");
+    let capitalized_entry = 32 + 2 * 48;
+    assert_eq!(h::read_u128_le(&data, capitalized_entry), capitalize_height as u128);
+    assert_eq!(h::read_u128_le(&data, capitalized_entry + 16), EVENT_INTEREST_CAPITALIZED);
+    assert_eq!(h::read_u128_le(&data, capitalized_entry + 32), new_principal - LOAN_AMOUNT);
+
+    println!("Capitalized {} of interest", new_principal - LOAN_AMOUNT);
+    Ok(())
+}
+
+/// Test that the repayment memoized at take for fixed-term loans matches the
+/// on-the-fly computation at every point of the term, for an APR loan, a
+/// per-block rate loan and an absolute-deadline loan taken late.
+#[wasm_bindgen_test]
+fn test_memoized_repayment_matches_computation() -> Result<()> {
+    let rate_per_block = PER_BLOCK_RATE_PRECISION / 1_000_000;
+    let absolute_deadline = DEPLOY_HEIGHT as u128 + 1 + DURATION_BLOCKS;
+    let take_delay = 50u32;
+    let cases = [
+        (0, APR_500_BPS, DURATION_BLOCKS, 1u32),
+        (FLAG_PER_BLOCK_RATE, rate_per_block, DURATION_BLOCKS, 1),
+        (FLAG_ABSOLUTE_DEADLINE, APR_500_BPS, absolute_deadline, take_delay),
+    ];
+
+    for (loan_flags, rate, duration_blocks, delay) in cases {
+        let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+        let lending_id = &ids.lending_contract;
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.loan_flags = loan_flags;
+        terms.apr = rate;
+        terms.duration_blocks = duration_blocks;
+
+        let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+        let take_height = DEPLOY_HEIGHT + 1 + delay;
+        h::take_loan(&init_block, take_height, lending_id, &terms)?;
+
+        // The absolute deadline is DURATION_BLOCKS after init, so a late take
+        // only has what is left of it
+        let duration = if loan_flags & FLAG_ABSOLUTE_DEADLINE != 0 {
+            absolute_deadline - take_height as u128
+        } else {
+            DURATION_BLOCKS
+        };
+        let expected = if loan_flags & FLAG_PER_BLOCK_RATE != 0 {
+            calculate_per_block_repayment_amount(LOAN_AMOUNT, rate, duration)
+        } else {
+            calculate_repayment_amount(LOAN_AMOUNT, rate, duration)
+        };
+        for elapsed in [0, 1, DURATION_BLOCKS as u32 / 2, DURATION_BLOCKS as u32 - delay - 1] {
+            let height = take_height + 1 + elapsed;
+            let amount = h::read_u128_le(&h::call_view(height, lending_id, 91)?, 0);
+            assert_eq!(amount, expected, "Flags {} at +{} blocks", loan_flags, elapsed);
+
+            let quote = h::call_view(height, lending_id, 95)?;
+            assert_eq!(h::read_u128_le(&quote, 0), expected, "Quote total, flags {}", loan_flags);
+            assert_eq!(h::read_u128_le(&quote, 32), expected - LOAN_AMOUNT, "Quote interest");
+        }
+    }
+
+    println!("Memoized repayment matches on-the-fly computation");
+    Ok(())
+}
+
+/// Test GetAccrualHistory (opcode 111) checkpoints interest at take,
+/// capitalization and repayment with a running total.
+#[wasm_bindgen_test]
+fn test_get_accrual_history() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let terms = LoanTerms::default_from(&ids);
+    let debitor_note = h::get_debitor_note(DEPLOY_HEIGHT + 3, lending_id)?;
+
+    let approve_block = h::approve_capitalization(
+        &take_block, DEPLOY_HEIGHT + 100, lending_id, TEST_CAPITALIZATION_EXTENSION,
+    )?;
+    let capitalize_height = DEPLOY_HEIGHT + 200;
+    let capitalize_block =
+        h::capitalize_interest(&approve_block, capitalize_height, lending_id, &debitor_note)?;
+
+    let elapsed = (capitalize_height - (DEPLOY_HEIGHT + 2)) as u128;
+    let new_principal = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, elapsed);
+    let new_deadline = DEPLOY_HEIGHT as u128 + 2 + DURATION_BLOCKS + TEST_CAPITALIZATION_EXTENSION;
+    let new_duration = new_deadline - capitalize_height as u128;
+    let repayment = calculate_repayment_amount(new_principal, APR_500_BPS, new_duration);
+    h::repay_loan_with_amount(
+        &capitalize_block, capitalize_height + 2, lending_id, &terms, repayment,
+    )?;
+
+    let data = h::call_view_with_inputs(capitalize_height + 3, lending_id, vec![111, 0, 10])?;
+    assert_eq!(data.len(), 32 + 3 * 64, "Page header + 3 checkpoints");
+    assert_eq!(h::read_u128_le(&data, 0), 3);
+
+    let capitalized = new_principal - LOAN_AMOUNT;
+    let charged = repayment - new_principal;
+    let expected = [
+        (DEPLOY_HEIGHT + 2, LOAN_AMOUNT, 0, 0),
+        (capitalize_height, new_principal, capitalized, capitalized),
+        (capitalize_height + 2, new_principal, charged, capitalized + charged),
+    ];
+    for (i, (height, principal, interest, total)) in expected.iter().enumerate() {
+        let entry = 32 + i * 64;
+        assert_eq!(h::read_u128_le(&data, entry), *height as u128, "Checkpoint {} block", i);
+        assert_eq!(h::read_u128_le(&data, entry + 16), *principal, "Checkpoint {} principal", i);
+        assert_eq!(h::read_u128_le(&data, entry + 32), *interest, "Checkpoint {} interest", i);
+        assert_eq!(h::read_u128_le(&data, entry + 48), *total, "Checkpoint {} total", i);
+    }
+    assert_eq!(capitalized + charged, repayment - LOAN_AMOUNT, "Total is all interest paid");
+
+    println!("GetAccrualHistory test passed");
+    Ok(())
+}
+
+/// Test GetRealizedYield (opcode 113): nothing while open, then the interest
+/// received over the blocks held once repaid, annualized with APR precision.
+#[wasm_bindgen_test]
+fn test_get_realized_yield() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let terms = LoanTerms::default_from(&ids);
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 113)?;
+    assert_eq!(data.len(), 64, "Realized yield should be 4 × u128");
+    assert_eq!(h::read_u128_le(&data, 0), 0, "Open loan has no realized yield");
+    assert_eq!(h::read_u128_le(&data, 16), 0);
+
+    // Full-term interest paid after half the term doubles the realized yield
+    let held = DURATION_BLOCKS / 2;
+    let repay_height = DEPLOY_HEIGHT + 2 + held as u32;
+    h::repay_loan(&take_block, repay_height, lending_id, &terms)?;
+
+    let interest = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS) - LOAN_AMOUNT;
+    let data = h::call_view(repay_height + 1, lending_id, 113)?;
+    assert_eq!(h::read_u128_le(&data, 0), 1, "Outcome should be repaid");
+    assert_eq!(h::read_u128_le(&data, 16), interest);
+    assert_eq!(h::read_u128_le(&data, 32), held);
+    assert_eq!(
+        h::read_u128_le(&data, 48),
+        interest * APR_PRECISION * BLOCKS_PER_YEAR / (LOAN_AMOUNT * held)
+    );
+
+    println!("GetRealizedYield test passed");
+    Ok(())
+}
+
+/// Test that the realized yield of a large loan at the finest APR precision
+/// is annualized without overflow: interest × 1e12 × BLOCKS_PER_YEAR exceeds
+/// u128 while the yield itself is small.
+#[wasm_bindgen_test]
+fn test_get_realized_yield_fine_precision() -> Result<()> {
+    let principal: u128 = 1_000_000_000_000_000_000_000_000; // 1e24
+    let precision: u128 = 1_000_000_000_000;
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_amount = principal;
+    terms.collateral_amount = principal;
+    terms.apr_precision = precision;
+    terms.apr = precision / 10; // 10%
+    terms.duration_blocks = BLOCKS_PER_YEAR;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+
+    // Full-term interest paid after half the term doubles the realized yield
+    let held = BLOCKS_PER_YEAR / 2;
+    let repay_height = DEPLOY_HEIGHT + 2 + held as u32;
+    let interest = principal / 10;
+    h::repay_loan_with_amount(&take_block, repay_height, lending_id, &terms, principal + interest)?;
+
+    let data = h::call_view(repay_height + 1, lending_id, 113)?;
+    assert_eq!(h::read_u128_le(&data, 0), 1, "Outcome should be repaid");
+    assert_eq!(h::read_u128_le(&data, 16), interest);
+    assert_eq!(h::read_u128_le(&data, 32), held);
+    assert_eq!(h::read_u128_le(&data, 48), 2 * terms.apr);
+
+    println!("Fine precision realized yield: {}", 2 * terms.apr);
+    Ok(())
+}
+
+/// Test that capitalization needs both the creditor's approval and the
+/// debitor note, and that a revoked approval can no longer be executed.
+#[wasm_bindgen_test]
+fn test_capitalize_interest_requires_consent() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let debitor_note = h::get_debitor_note(DEPLOY_HEIGHT + 3, lending_id)?;
+
+    // Debitor alone cannot capitalize
+    let block = h::capitalize_interest(&take_block, DEPLOY_HEIGHT + 10, lending_id, &debitor_note)?;
+    h::assert_revert(&block, "Capitalization not approved by creditor")?;
+
+    // Approval needs the creditor auth token
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![6, TEST_CAPITALIZATION_EXTENSION],
+    };
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 11, cellpack)?;
+    h::assert_revert(&block, "Auth token is not in incoming alkanes")?;
+
+    // Approved, but executed without the debitor note
+    let approve_block = h::approve_capitalization(
+        &take_block, DEPLOY_HEIGHT + 12, lending_id, TEST_CAPITALIZATION_EXTENSION,
+    )?;
+    let cellpack = Cellpack {
+        target: lending_id.clone(),
+        inputs: vec![7],
+    };
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 13, cellpack)?;
+    h::assert_revert(&block, "Debitor note is not in incoming alkanes")?;
+
+    // Revoked approval cannot be executed
+    let revoke_block = h::approve_capitalization(&approve_block, DEPLOY_HEIGHT + 14, lending_id, 0)?;
+    let block = h::capitalize_interest(&revoke_block, DEPLOY_HEIGHT + 15, lending_id, &debitor_note)?;
+    h::assert_revert(&block, "Capitalization not approved by creditor")?;
+
+    println!("Capitalization consent checks passed");
+    Ok(())
+}
+
+/// Test GetHistory (opcode 96) records the lifecycle and paginates.
+#[wasm_bindgen_test]
+fn test_get_history_lifecycle() -> Result<()> {
+    let (_repay_block, ids) = h::setup_to_repaid_state()?;
+    let lending_id = &ids.lending_contract;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 4, lending_id, vec![96, 0, 10])?;
+    assert_eq!(data.len(), 32 + 3 * 48, "Page header + 3 entries");
+    assert_eq!(h::read_u128_le(&data, 0), 3);
+
+    let expected = [
+        (DEPLOY_HEIGHT + 1, EVENT_OFFER_CREATED, LOAN_AMOUNT),
+        (DEPLOY_HEIGHT + 2, EVENT_LOAN_TAKEN, LOAN_AMOUNT),
+        (DEPLOY_HEIGHT + 3, EVENT_LOAN_REPAID, repayment),
+    ];
+    for (i, (height, event, amount)) in expected.iter().enumerate() {
+        let entry = 32 + i * 48;
+        assert_eq!(h::read_u128_le(&data, entry), *height as u128);
+        assert_eq!(h::read_u128_le(&data, entry + 16), *event);
+        assert_eq!(h::read_u128_le(&data, entry + 32), *amount);
+    }
+
+    // Pagination: offset 1, limit 1 returns only the take entry
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 4, lending_id, vec![96, 1, 1])?;
+    assert_eq!(data.len(), 32 + 48);
+    assert_eq!(h::read_u128_le(&data, 32 + 16), EVENT_LOAN_TAKEN);
+
+    // Offset past the end returns only the page header
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 4, lending_id, vec![96, 5, 10])?;
+    assert_eq!(data.len(), 32);
+
+    println!("GetHistory lifecycle test passed");
+    Ok(())
+}
+
+/// Test that a log page stops at the response size limit with the truncated
+/// flag set, and that the next page picks up the rest.
+#[wasm_bindgen_test]
+fn test_history_page_truncation() -> Result<()> {
+    const LOG_PAGE_TRUNCATED: u128 = 1;
+    // (1024 - 32) / 48: the most history entries one page can hold
+    const ENTRIES_PER_PAGE: u128 = 20;
+
+    let (mut block, ids) = h::setup_to_waiting_state()?;
+    let lending_id = &ids.lending_contract;
+    // Each refresh appends a history entry after the offer's creation
+    for i in 0..ENTRIES_PER_PAGE as u32 {
+        block = h::refresh_offer(&block, DEPLOY_HEIGHT + 2 + i, lending_id)?;
+    }
+    let height = DEPLOY_HEIGHT + 2 + ENTRIES_PER_PAGE as u32;
+
+    // Exactly a page's worth fits without truncation
+    let data = h::call_view_with_inputs(height, lending_id, vec![96, 0, ENTRIES_PER_PAGE])?;
+    assert_eq!(data.len(), 32 + 20 * 48);
+    assert_eq!(h::read_u128_le(&data, 0), ENTRIES_PER_PAGE + 1);
+    assert_eq!(h::read_u128_le(&data, 16), 0, "Page within the limit is not truncated");
+
+    // One entry more does not fit
+    let data = h::call_view_with_inputs(height, lending_id, vec![96, 0, 100])?;
+    assert_eq!(data.len(), 32 + 20 * 48, "Page should stop at the size limit");
+    assert_eq!(h::read_u128_le(&data, 16), LOG_PAGE_TRUNCATED);
+
+    let data = h::call_view_with_inputs(height, lending_id, vec![96, ENTRIES_PER_PAGE, 100])?;
+    assert_eq!(data.len(), 32 + 48, "Next page holds the remaining entry");
+    assert_eq!(h::read_u128_le(&data, 16), 0);
+    assert_eq!(h::read_u128_le(&data, 32), height as u128 - 1, "Last refresh");
+
+    println!("History page truncation test passed");
+    Ok(())
+}
+
+/// Test that repeatable calls stop at the history log limit while the loan
+/// lifecycle carries on past it.
+#[wasm_bindgen_test]
+fn test_history_log_limit() -> Result<()> {
+    const HISTORY_LOG_LIMIT: u128 = 64;
+
+    let (mut block, ids) = h::setup_to_waiting_state()?;
+    let lending_id = &ids.lending_contract;
+    let terms = LoanTerms::default_from(&ids);
+    // The offer's creation is the first entry
+    let refreshes = HISTORY_LOG_LIMIT as u32 - 1;
+    for i in 0..refreshes {
+        block = h::refresh_offer(&block, DEPLOY_HEIGHT + 2 + i, lending_id)?;
+    }
+    let height = DEPLOY_HEIGHT + 2 + refreshes;
+
+    let full = h::refresh_offer(&block, height, lending_id)?;
+    h::assert_revert(&full, "History log is full")?;
+
+    let take_block = h::take_loan(&full, height + 1, lending_id, &terms)?;
+    let sheet = get_last_outpoint_sheet(&take_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Take is not limited");
+
+    let data = h::call_view_with_inputs(height + 2, lending_id, vec![96, 0, 1])?;
+    assert_eq!(h::read_u128_le(&data, 0), HISTORY_LOG_LIMIT + 1);
+
+    println!("History log limit test passed");
+    Ok(())
+}
+
+/// Test GetTransfers (opcode 110) logs every payout of a full lifecycle with
+/// its cause, sender and recipient, and skips refunds.
+#[wasm_bindgen_test]
+fn test_get_transfers_lifecycle() -> Result<()> {
+    const EVENT_REPAYMENT_CLAIMED: u128 = 3;
+    const ROLE_DEBITOR: u128 = 1;
+    const ROLE_CREDITOR: u128 = 2;
+    const ROLE_CONTRACT: u128 = 4;
+
+    let (repay_block, ids) = h::setup_to_repaid_state()?;
+    let lending_id = &ids.lending_contract;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    let note = h::get_debitor_note(DEPLOY_HEIGHT + 4, lending_id)?;
+    h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 5, lending_id)?;
+
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 6, lending_id, vec![110, 0, 10])?;
+    assert_eq!(data.len(), 32 + 5 * 112, "Page header + 5 entries");
+    assert_eq!(h::read_u128_le(&data, 0), 5);
+
+    let expected = [
+        (DEPLOY_HEIGHT + 1, EVENT_OFFER_CREATED, ROLE_CONTRACT, ROLE_CREDITOR, *lending_id, 1),
+        (DEPLOY_HEIGHT + 2, EVENT_LOAN_TAKEN, ROLE_CREDITOR, ROLE_DEBITOR, ids.loan_token, LOAN_AMOUNT),
+        (DEPLOY_HEIGHT + 2, EVENT_LOAN_TAKEN, ROLE_CONTRACT, ROLE_DEBITOR, note, 1),
+        (
+            DEPLOY_HEIGHT + 3, EVENT_LOAN_REPAID, ROLE_DEBITOR, ROLE_DEBITOR,
+            ids.collateral_token, COLLATERAL_AMOUNT,
+        ),
+        (
+            DEPLOY_HEIGHT + 5, EVENT_REPAYMENT_CLAIMED, ROLE_DEBITOR, ROLE_CREDITOR,
+            ids.loan_token, repayment,
+        ),
+    ];
+    for (i, (height, event, from, to, token, amount)) in expected.iter().enumerate() {
+        let entry = 32 + i * 112;
+        assert_eq!(h::read_u128_le(&data, entry), *height as u128, "Entry {} block", i);
+        assert_eq!(h::read_u128_le(&data, entry + 16), *event, "Entry {} event", i);
+        assert_eq!(h::read_u128_le(&data, entry + 32), *from, "Entry {} sender", i);
+        assert_eq!(h::read_u128_le(&data, entry + 48), *to, "Entry {} recipient", i);
+        assert_eq!(h::read_u128_le(&data, entry + 64), token.block, "Entry {} token", i);
+        assert_eq!(h::read_u128_le(&data, entry + 80), token.tx, "Entry {} token", i);
+        assert_eq!(h::read_u128_le(&data, entry + 96), *amount, "Entry {} amount", i);
+    }
+
+    println!("GetTransfers lifecycle test passed");
+    Ok(())
+}
+
+// ============================================================================
+// Zero-Coupon Bond Mode Tests
+// ============================================================================
+
+/// Deploy + init a bond-mode offer. Returns the init block, IDs, terms and
+/// the bond token.
+fn setup_bond_offer() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms, AlkaneId)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_ZERO_COUPON_BOND;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let (bond_token, supply) = h::get_bond_token(DEPLOY_HEIGHT + 2, &ids.lending_contract)?;
+    assert_eq!(
+        supply,
+        calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS),
+        "Bond supply should equal the full-term repayment"
+    );
+    Ok((init_block, ids, terms, bond_token))
+}
+
+/// Test that bonds are minted to the creditor at init and redeem 1:1 for
+/// repaid loan tokens, while the auth-token claim path is disabled.
+#[wasm_bindgen_test]
+fn test_bond_mode_redeem_after_repayment() -> Result<()> {
+    const STATUS_CLAIMABLE: u128 = 1 << 11;
+
+    let (init_block, ids, terms, bond_token) = setup_bond_offer()?;
+    let lending_id = &ids.lending_contract;
+    let supply = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    let sheet = get_last_outpoint_sheet(&init_block)?;
+    assert_eq!(sheet.get(&bond_token.into()), supply, "Creditor should hold all bonds");
+
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+
+    // Bonds are not redeemable while the loan is running
+    let early_block = h::redeem_bonds(&take_block, DEPLOY_HEIGHT + 3, lending_id, &bond_token, supply)?;
+    h::assert_revert(&early_block, "Bonds are not redeemable yet")?;
+
+    let repay_block = h::repay_loan(&early_block, DEPLOY_HEIGHT + 4, lending_id, &terms)?;
+
+    let claim_block = h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 5, lending_id)?;
+    h::assert_revert(&claim_block, "Bond mode claims are made with RedeemBonds")?;
+
+    // Redeem in two parts
+    let half = supply / 2;
+    let first_block = h::redeem_bonds(&claim_block, DEPLOY_HEIGHT + 6, lending_id, &bond_token, half)?;
+    let sheet = get_last_outpoint_sheet(&first_block)?;
+    assert_eq!(sheet.get(&bond_token.into()), supply - half);
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - supply + half);
+    let data = h::call_view(DEPLOY_HEIGHT + 7, lending_id, 103)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_REPAID | STATUS_CLAIMABLE);
+
+    let second_block =
+        h::redeem_bonds(&first_block, DEPLOY_HEIGHT + 8, lending_id, &bond_token, supply - half)?;
+    let sheet = get_last_outpoint_sheet(&second_block)?;
+    assert_eq!(sheet.get(&bond_token.into()), 0, "All bonds should be retired");
+    assert_eq!(
+        sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY,
+        "Bond holder should receive the full repayment"
+    );
+    let data = h::call_view(DEPLOY_HEIGHT + 9, lending_id, 103)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_REPAID, "No bonds left to redeem");
+
+    println!("Bond mode repaid redemption test passed");
+    Ok(())
+}
+
+/// Test that after default bonds redeem pro-rata for the collateral.
+#[wasm_bindgen_test]
+fn test_bond_mode_redeem_after_default() -> Result<()> {
+    let (init_block, ids, terms, bond_token) = setup_bond_offer()?;
+    let lending_id = &ids.lending_contract;
+    let supply = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+    let past_deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32 + 1;
+
+    let claim_block = h::claim_defaulted_collateral(&take_block, past_deadline, lending_id)?;
+    h::assert_revert(&claim_block, "Bond mode claims are made with RedeemBonds")?;
+
+    let quarter = supply / 4;
+    let redeem_block =
+        h::redeem_bonds(&claim_block, past_deadline + 1, lending_id, &bond_token, quarter)?;
+    let sheet = get_last_outpoint_sheet(&redeem_block)?;
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()),
+        INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT + COLLATERAL_AMOUNT * quarter / supply,
+        "Bonds should redeem their share of the collateral"
+    );
+
+    let data = h::call_view(past_deadline + 2, lending_id, 92)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_DEFAULTED);
+
+    println!("Bond mode default redemption test passed");
     Ok(())
 }
 
-/// Test that cancelling a loan offer fails when the debitor has already taken.
+/// Test that bonds of a loan too large for collateral × bonds to fit in a
+/// u128 still redeem for the whole collateral after default.
 #[wasm_bindgen_test]
-fn test_cancel_loan_offer_fails_after_debitor_takes() -> Result<()> {
-    let (take_block, ids) = h::setup_to_active_state()?;
-
-    let cancel_block = h::cancel_loan_offer(&take_block, DEPLOY_HEIGHT + 3, &ids.lending_contract)?;
-
-    h::assert_revert(&cancel_block, "Cannot cancel - loan offer not in cancellable state")?;
+fn test_bond_mode_redeem_large_default() -> Result<()> {
+    let principal: u128 = 1_000_000_000_000_000_000_000_000_000_000;
+    let (deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+    let lending_id = &ids.lending_contract;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_ZERO_COUPON_BOND;
+    terms.loan_amount = principal;
+    terms.collateral_amount = principal;
+
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+    let (bond_token, supply) = h::get_bond_token(DEPLOY_HEIGHT + 2, lending_id)?;
+    assert!(principal.checked_mul(supply).is_none(), "Share must not fit a u128 product");
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+
+    let past_deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32 + 1;
+    let redeem_block = h::redeem_bonds(&take_block, past_deadline, lending_id, &bond_token, supply)?;
+    let sheet = get_last_outpoint_sheet(&redeem_block)?;
+    assert_eq!(sheet.get(&bond_token.into()), 0, "All bonds should be retired");
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), HUGE_TOKEN_SUPPLY,
+        "Bonds should redeem the whole collateral"
+    );
 
-    println!("\n=== CANCEL CORRECTLY REJECTED — LOAN ALREADY TAKEN ===");
+    println!("Bond mode large default redemption test passed");
     Ok(())
 }
 
-// ============================================================================
-// Insufficient Token Tests
-// ============================================================================
-
-/// Test that InitWithLoanOffer reverts when insufficient loan tokens are sent.
-/// Uses Edict+Cellpack split to pipe only half the required amount.
+/// Test that cancelling a bond-mode offer requires returning every bond.
 #[wasm_bindgen_test]
-fn test_init_insufficient_loan() -> Result<()> {
-    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
-    let terms = LoanTerms::default_from(&ids);
-    let insufficient_amount = LOAN_AMOUNT / 2;
+fn test_bond_mode_cancel_requires_all_bonds() -> Result<()> {
+    let (init_block, ids, _terms, bond_token) = setup_bond_offer()?;
+    let lending_id = &ids.lending_contract;
+    let supply = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
 
-    let init_cellpack = Cellpack {
-        target: ids.lending_contract.clone(),
-        inputs: vec![
-            0,
-            terms.collateral_token.block,
-            terms.collateral_token.tx,
-            terms.collateral_amount,
-            terms.loan_token.block,
-            terms.loan_token.tx,
-            terms.loan_amount,
-            terms.duration_blocks,
-            terms.apr,
-        ],
-    };
+    let short_block = h::cancel_loan_offer_with_bonds(
+        &init_block, DEPLOY_HEIGHT + 2, lending_id, &bond_token, supply - 1,
+    )?;
+    h::assert_revert(&short_block, "Insufficient tokens")?;
 
-    let block = h::execute_cellpack_with_split(
-        &deploy_block,
-        DEPLOY_HEIGHT + 1,
-        init_cellpack,
-        ids.loan_token.clone(),
-        insufficient_amount,
+    let cancel_block = h::cancel_loan_offer_with_bonds(
+        &short_block, DEPLOY_HEIGHT + 3, lending_id, &bond_token, supply,
     )?;
+    let sheet = get_last_outpoint_sheet(&cancel_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Escrow should be released");
+    assert_eq!(sheet.get(&bond_token.into()), 0, "Bonds should be retired");
 
-    h::assert_revert_split(&block, "Insufficient tokens")?;
+    let data = h::call_view(DEPLOY_HEIGHT + 4, lending_id, 92)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_UNINITIALIZED);
 
-    println!("\n=== INIT CORRECTLY REJECTED — INSUFFICIENT LOAN TOKENS ===");
+    println!("Bond mode cancel test passed");
     Ok(())
 }
 
-/// Test that TakeLoanWithCollateral reverts when insufficient collateral is sent.
-/// Uses Edict+Cellpack split to pipe only half the required collateral.
+/// Test that bond mode rejects terms whose repayment is not fixed.
 #[wasm_bindgen_test]
-fn test_take_insufficient_collateral() -> Result<()> {
-    let (init_block, ids) = h::setup_to_waiting_state()?;
-    let insufficient_collateral = COLLATERAL_AMOUNT / 2;
-
-    let take_cellpack = Cellpack {
-        target: ids.lending_contract.clone(),
-        inputs: vec![1],
-    };
-
-    let block = h::execute_cellpack_with_split(
-        &init_block,
-        DEPLOY_HEIGHT + 2,
-        take_cellpack,
-        ids.collateral_token.clone(),
-        insufficient_collateral,
-    )?;
+fn test_init_bond_mode_requires_fixed_repayment() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_ZERO_COUPON_BOND | FLAG_EARLY_REPAYMENT_REBATE;
 
-    h::assert_revert_split(&block, "Insufficient tokens")?;
+    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
+    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, "Bond mode requires fixed full-term repayment")?;
 
-    println!("\n=== TAKE CORRECTLY REJECTED — INSUFFICIENT COLLATERAL ===");
+    println!("Bond mode with rebate correctly rejected");
     Ok(())
 }
 
 // ============================================================================
-// ClaimDefaultedCollateral Error Tests
+// Open-Term Loan Tests
 // ============================================================================
 
-/// Test that ClaimDefaultedCollateral reverts when the deadline has not yet passed.
-#[wasm_bindgen_test]
-fn test_claim_defaulted_collateral_before_deadline() -> Result<()> {
-    let (take_block, ids) = h::setup_to_active_state()?;
-
-    // Loan was taken at DEPLOY_HEIGHT + 2, deadline = (DEPLOY_HEIGHT + 2) + DURATION_BLOCKS.
-    // Attempt claim one block after take — well before the deadline.
-    let block = h::claim_defaulted_collateral(&take_block, DEPLOY_HEIGHT + 3, &ids.lending_contract)?;
+/// Deploy + init an open-term offer (notice period DURATION_BLOCKS) + take.
+/// Returns the take block, IDs and terms.
+fn setup_open_term_loan() -> Result<(bitcoin::Block, h::LendingDeploymentIds, LoanTerms)> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut terms = LoanTerms::default_from(&ids);
+    terms.loan_flags = FLAG_OPEN_TERM;
 
-    h::assert_revert(&block, "Loan has not defaulted yet - deadline not passed")?;
-    println!("ClaimDefaultedCollateral before deadline correctly rejected");
-    Ok(())
+    let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_contract, &terms)?;
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 2, &ids.lending_contract, &terms)?;
+    Ok((take_block, ids, terms))
 }
 
-// ============================================================================
-// InitWithLoanOffer Validation Error Tests
-// ============================================================================
-
-/// Test that InitWithLoanOffer reverts when collateral_amount is zero.
+/// Test that an open-term loan has no deadline, accrues interest per block
+/// and can be repaid long after the notice period length has elapsed.
 #[wasm_bindgen_test]
-fn test_init_collateral_amount_zero() -> Result<()> {
-    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
-    let mut terms = LoanTerms::default_from(&ids);
-    terms.collateral_amount = 0;
+fn test_open_term_repay_any_time() -> Result<()> {
+    let (take_block, ids, terms) = setup_open_term_loan()?;
+    let lending_id = &ids.lending_contract;
 
-    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
-    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    let repay_height = DEPLOY_HEIGHT + 2 + 2 * DURATION_BLOCKS as u32;
+    let elapsed = 2 * DURATION_BLOCKS;
 
-    h::assert_revert(&block, "Collateral amount cannot be zero")?;
-    println!("Init collateral_amount=0 correctly rejected");
+    let data = h::call_view(repay_height, lending_id, 93)?;
+    assert_eq!(h::read_u128_le(&data, 0), u128::MAX, "Uncalled open-term loan has no deadline");
+
+    let expected = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, elapsed);
+    let data = h::call_view(repay_height, lending_id, 91)?;
+    assert_eq!(h::read_u128_le(&data, 0), expected, "Interest accrues for the elapsed blocks");
+
+    let repay_block = h::repay_loan_with_amount(&take_block, repay_height, lending_id, &terms, expected)?;
+    let sheet = get_last_outpoint_sheet(&repay_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Open-term loan repaid after {} blocks", elapsed);
     Ok(())
 }
 
-/// Test that InitWithLoanOffer reverts when loan_amount is zero.
+/// Test that calling an open-term loan starts the notice period and that the
+/// creditor can claim the collateral only once it has passed.
 #[wasm_bindgen_test]
-fn test_init_loan_amount_zero() -> Result<()> {
-    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
-    let mut terms = LoanTerms::default_from(&ids);
-    terms.loan_amount = 0;
+fn test_open_term_call_then_default() -> Result<()> {
+    let (take_block, ids, _terms) = setup_open_term_loan()?;
+    let lending_id = &ids.lending_contract;
 
-    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
-    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    let early_claim = h::claim_defaulted_collateral(&take_block, DEPLOY_HEIGHT + 100, lending_id)?;
+    h::assert_revert(&early_claim, "Loan has not defaulted yet")?;
 
-    h::assert_revert(&block, "Loan amount cannot be zero")?;
-    println!("Init loan_amount=0 correctly rejected");
+    let call_height = DEPLOY_HEIGHT + 101;
+    let call_block = h::call_loan(&early_claim, call_height, lending_id)?;
+
+    let data = h::call_view(call_height + 1, lending_id, 93)?;
+    assert_eq!(h::read_u128_le(&data, 0), DURATION_BLOCKS - 1, "Notice period is running");
+
+    let again_block = h::call_loan(&call_block, call_height + 2, lending_id)?;
+    h::assert_revert(&again_block, "Loan has already been called")?;
+
+    let notice_end = call_height + DURATION_BLOCKS as u32;
+    let in_notice = h::claim_defaulted_collateral(&again_block, notice_end, lending_id)?;
+    h::assert_revert(&in_notice, "Loan has not defaulted yet")?;
+
+    let claim_block = h::claim_defaulted_collateral(&in_notice, notice_end + 1, lending_id)?;
+    let sheet = get_last_outpoint_sheet(&claim_block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    let data = h::call_view(notice_end + 2, lending_id, 92)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_DEFAULTED);
+
+    println!("Open-term call and default test passed");
     Ok(())
 }
 
-/// Test that InitWithLoanOffer reverts when duration_blocks is zero.
+/// Test CallLoan on a fixed-term loan and open-term init with term options.
 #[wasm_bindgen_test]
-fn test_init_duration_zero() -> Result<()> {
+fn test_open_term_invalid_usage() -> Result<()> {
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let block = h::call_loan(&take_block, DEPLOY_HEIGHT + 3, &ids.lending_contract)?;
+    h::assert_revert(&block, "Loan is not open-term")?;
+
     let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
     let mut terms = LoanTerms::default_from(&ids);
-    terms.duration_blocks = 0;
-
+    terms.loan_flags = FLAG_OPEN_TERM | FLAG_EARLY_REPAYMENT_REBATE;
     let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
     let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    h::assert_revert(&block, "Open-term loans cannot use term-based options")?;
 
-    h::assert_revert(&block, "Duration cannot be zero")?;
-    println!("Init duration=0 correctly rejected");
+    println!("Open-term invalid usage correctly rejected");
     Ok(())
 }
 
-/// Test that InitWithLoanOffer reverts when collateral and loan token are the same.
+// ============================================================================
+// Position Label Tests
+// ============================================================================
+
+/// Test that each party can label its side of the position and that
+/// GetLabels (opcode 109) reports both.
 #[wasm_bindgen_test]
-fn test_init_same_collateral_and_loan_token() -> Result<()> {
-    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
-    let mut terms = LoanTerms::default_from(&ids);
-    // Set collateral token equal to loan token
-    terms.collateral_token = terms.loan_token.clone();
+fn test_position_labels() -> Result<()> {
+    const CREDITOR_LABEL: u128 = 0x7265_6631; // "ref1"
+    const DEBITOR_LABEL: u128 = 42;
 
-    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
-    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 1, cellpack)?;
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let note = h::get_debitor_note(DEPLOY_HEIGHT + 3, lending_id)?;
 
-    h::assert_revert(&block, "Collateral and loan token cannot be the same")?;
-    println!("Init same-token correctly rejected");
+    let data = h::call_view(DEPLOY_HEIGHT + 4, lending_id, 109)?;
+    assert_eq!(data.len(), 32, "Labels should be 2 × u128");
+    assert_eq!((h::read_u128_le(&data, 0), h::read_u128_le(&data, 16)), (0, 0), "Unset by default");
+
+    let creditor_block =
+        h::set_creditor_label(&take_block, DEPLOY_HEIGHT + 5, lending_id, CREDITOR_LABEL)?;
+    let debitor_block =
+        h::set_debitor_label(&creditor_block, DEPLOY_HEIGHT + 6, lending_id, &note, DEBITOR_LABEL)?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 7, lending_id, 109)?;
+    assert_eq!(h::read_u128_le(&data, 0), CREDITOR_LABEL);
+    assert_eq!(h::read_u128_le(&data, 16), DEBITOR_LABEL);
+
+    // Labels do not consume the tokens that authorized them
+    let sheet = get_last_outpoint_sheet(&debitor_block)?;
+    assert_eq!(sheet.get(&(*lending_id).into()), 1, "Creditor auth token should be returned");
+    assert_eq!(sheet.get(&note.into()), 1, "Debitor note should be returned");
+
+    println!("Position labels test passed");
     Ok(())
 }
 
-/// Test that InitWithLoanOffer reverts when called a second time
-/// (contract already initialized via `observe_initialization`).
+/// Test that each party can opt in to disclosing an attestation pointer and
+/// that GetDisclosures (opcode 114) reports it, and that zeros clear it.
 #[wasm_bindgen_test]
-fn test_init_already_initialized() -> Result<()> {
-    let (_init_block, ids) = h::setup_to_waiting_state()?;
+fn test_counterparty_disclosures() -> Result<()> {
+    const CREDITOR_DISCLOSURE: [u128; 3] = [0xaaaa_0001, 0xbbbb_0002, 0];
+    const DEBITOR_DISCLOSURE: [u128; 3] = [0xcccc_0003, 0xdddd_0004, 7];
 
-    // Attempt a second init — should fail at observe_initialization
-    let terms = LoanTerms::default_from(&ids);
-    let cellpack = h::build_init_cellpack(&ids.lending_contract, &terms);
-    let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 2, cellpack)?;
+    let (take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let note = h::get_debitor_note(DEPLOY_HEIGHT + 3, lending_id)?;
 
-    h::assert_revert(&block, "already initialized")?;
-    println!("Init already-initialized correctly rejected");
+    let data = h::call_view(DEPLOY_HEIGHT + 4, lending_id, 114)?;
+    assert_eq!(data, vec![0u8; 96], "Nothing disclosed by default");
+
+    let creditor_block =
+        h::set_creditor_disclosure(&take_block, DEPLOY_HEIGHT + 5, lending_id, CREDITOR_DISCLOSURE)?;
+    let debitor_block = h::set_debitor_disclosure(
+        &creditor_block, DEPLOY_HEIGHT + 6, lending_id, &note, DEBITOR_DISCLOSURE,
+    )?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 7, lending_id, 114)?;
+    let disclosed: Vec<u128> = (0..6).map(|i| h::read_u128_le(&data, i * 16)).collect();
+    assert_eq!(disclosed[..3], CREDITOR_DISCLOSURE);
+    assert_eq!(disclosed[3..], DEBITOR_DISCLOSURE);
+
+    h::set_creditor_disclosure(&debitor_block, DEPLOY_HEIGHT + 8, lending_id, [0; 3])?;
+    let data = h::call_view(DEPLOY_HEIGHT + 9, lending_id, 114)?;
+    assert_eq!(h::read_u128_le(&data, 0), 0, "Creditor disclosure should be cleared");
+    assert_eq!(h::read_u128_le(&data, 48), DEBITOR_DISCLOSURE[0]);
+
+    println!("Counterparty disclosure test passed");
     Ok(())
 }
 
@@ -630,6 +3006,32 @@ fn test_get_time_remaining_expired() -> Result<()> {
     Ok(())
 }
 
+/// Test GetBlocksUntilDeadline (opcode 108) before and after the deadline.
+#[wasm_bindgen_test]
+fn test_get_blocks_until_deadline() -> Result<()> {
+    let (_take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let deadline = (DEPLOY_HEIGHT as u128 + 2) + DURATION_BLOCKS;
+
+    let query_height = DEPLOY_HEIGHT + 3;
+    let data = h::call_view(query_height, lending_id, 108)?;
+    assert_eq!(data.len(), 80, "Countdown should be 5 × u128");
+    assert_eq!(h::read_u128_le(&data, 0), deadline);
+    assert_eq!(h::read_u128_le(&data, 16), query_height as u128);
+    assert_eq!(h::read_u128_le(&data, 32), deadline - query_height as u128);
+    assert_eq!(h::read_u128_le(&data, 48), 0, "Not defaulted before the deadline");
+    assert_eq!(h::read_u128_le(&data, 64), deadline, "No grace period: hard deadline is the deadline");
+
+    let expired_height = 850_000u32;
+    let data = h::call_view(expired_height, lending_id, 108)?;
+    assert_eq!(h::read_u128_le(&data, 16), expired_height as u128);
+    assert_eq!(h::read_u128_le(&data, 32), 0, "No blocks remaining after the deadline");
+    assert_eq!(h::read_u128_le(&data, 48), 1, "Defaulted after the deadline");
+
+    println!("GetBlocksUntilDeadline test passed");
+    Ok(())
+}
+
 /// Test GetTimeRemaining (opcode 93) when no active loan.
 /// Should return 0.
 #[wasm_bindgen_test]
@@ -645,6 +3047,273 @@ fn test_get_time_remaining_no_active_loan() -> Result<()> {
     Ok(())
 }
 
+/// Test GetLiquidationPrice (opcode 94) during an active loan.
+/// Should return repayment * 1e18 / collateral_amount.
+#[wasm_bindgen_test]
+fn test_get_liquidation_price_active() -> Result<()> {
+    let (_take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 94)?;
+    assert_eq!(data.len(), 16, "Liquidation price should be 16 bytes (u128)");
+    let price = h::read_u128_le(&data, 0);
+
+    let debt = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    let expected_price = debt * PRICE_PRECISION / COLLATERAL_AMOUNT;
+    assert_eq!(price, expected_price, "Liquidation price should be debt / collateral");
+
+    println!("GetLiquidationPrice returned: {} (expected: {})", price, expected_price);
+    Ok(())
+}
+
+/// Test GetLiquidationPrice (opcode 94) when no loan is active.
+/// Should return 0.
+#[wasm_bindgen_test]
+fn test_get_liquidation_price_waiting() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 94)?;
+    let price = h::read_u128_le(&data, 0);
+    assert_eq!(price, 0, "Liquidation price should be 0 when no loan is active");
+
+    println!("GetLiquidationPrice waiting test passed");
+    Ok(())
+}
+
+/// Test GetLiquidationPrice (opcode 94) on both sides of the debt at which
+/// scaling by 1e18 no longer fits a u128: the price keeps its fractional part.
+#[wasm_bindgen_test]
+fn test_get_liquidation_price_large_debt() -> Result<()> {
+    let collateral: u128 = 300_000_000_000_000_000_000;
+    // The largest debt whose scaled value fits a u128, the next one, and 5e20
+    let boundary = u128::MAX / PRICE_PRECISION;
+    assert!((boundary + 1).checked_mul(PRICE_PRECISION).is_none());
+    let split_price = |debt: u128| {
+        (debt / collateral) * PRICE_PRECISION + (debt % collateral) * PRICE_PRECISION / collateral
+    };
+    let cases = [
+        (boundary, boundary * PRICE_PRECISION / collateral),
+        (boundary + 1, split_price(boundary + 1)),
+        (500_000_000_000_000_000_000, 1_666_666_666_666_666_666),
+    ];
+
+    for (debt, expected_price) in cases {
+        let (deploy_block, ids) = h::deploy_lending_with_supplies(HUGE_TOKEN_SUPPLY, HUGE_TOKEN_SUPPLY)?;
+        let lending_id = &ids.lending_contract;
+        // Interest-free, so the debt is the principal
+        let mut terms = LoanTerms::default_from(&ids);
+        terms.loan_amount = debt;
+        terms.collateral_amount = collateral;
+        terms.apr = 0;
+
+        let init_block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, lending_id, &terms)?;
+        h::take_loan(&init_block, DEPLOY_HEIGHT + 2, lending_id, &terms)?;
+        let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 94)?;
+        assert_eq!(h::read_u128_le(&data, 0), expected_price, "Liquidation price of debt {}", debt);
+    }
+
+    println!("GetLiquidationPrice large debt test passed");
+    Ok(())
+}
+
+/// Read (amount due at maturity, blocks to maturity, present value, value
+/// per 1e18 units) from GetPresentValue (opcode 120).
+fn present_value(height: u32, lending_id: &AlkaneId, discount_apr: u128, precision: u128) -> Result<[u128; 4]> {
+    let data = h::call_view_with_inputs(height, lending_id, vec![120, discount_apr, precision])?;
+    assert_eq!(data.len(), 64, "Present value should be 4 × u128");
+    Ok([0, 1, 2, 3].map(|field| h::read_u128_le(&data, field * 16)))
+}
+
+/// Test GetPresentValue (opcode 120) prices an offer as if taken now and an
+/// active loan from its remaining term, and that discounting at the loan's
+/// own APR recovers the principal.
+#[wasm_bindgen_test]
+fn test_get_present_value() -> Result<()> {
+    let (init_block, ids) = h::setup_to_waiting_state()?;
+    let lending_id = &ids.lending_contract;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    let [amount, blocks, value, unit_value] = present_value(DEPLOY_HEIGHT + 2, lending_id, APR_500_BPS, 0)?;
+    assert_eq!((amount, blocks), (repayment, DURATION_BLOCKS), "Offer priced as if taken now");
+    assert_eq!(value, calculate_present_value(repayment, APR_500_BPS, DURATION_BLOCKS, APR_PRECISION));
+    assert!(value <= LOAN_AMOUNT && LOAN_AMOUNT - value <= 1, "Own APR recovers the principal");
+    assert_eq!(
+        unit_value,
+        calculate_present_value(PRICE_PRECISION, APR_500_BPS, DURATION_BLOCKS, APR_PRECISION)
+    );
+
+    // A finer precision quoting the same rate gives the same price
+    let fine = present_value(DEPLOY_HEIGHT + 2, lending_id, APR_500_BPS * 100, 1_000_000)?;
+    assert_eq!(fine[2], value, "Same rate at 1e6 precision");
+
+    let [_, _, undiscounted, par] = present_value(DEPLOY_HEIGHT + 2, lending_id, 0, 0)?;
+    assert_eq!((undiscounted, par), (repayment, PRICE_PRECISION), "Zero rate prices at face value");
+    let [_, _, cheaper, _] = present_value(DEPLOY_HEIGHT + 2, lending_id, 2 * APR_500_BPS, 0)?;
+    assert!(cheaper < value, "Higher discount rate lowers the price");
+
+    let block = h::execute_cellpack_no_balance(
+        DEPLOY_HEIGHT + 2,
+        Cellpack {
+            target: *lending_id,
+            inputs: vec![120, APR_500_BPS, 1_000],
+        },
+    )?;
+    h::assert_revert(&block, "APR precision must be a power of ten")?;
+
+    // Halfway through the term only the remaining blocks are discounted
+    let terms = LoanTerms::default_from(&ids);
+    let take_block = h::take_loan(&init_block, DEPLOY_HEIGHT + 3, lending_id, &terms)?;
+    let halfway = DEPLOY_HEIGHT + 3 + (DURATION_BLOCKS / 2) as u32;
+    let [amount, blocks, value, _] = present_value(halfway, lending_id, APR_500_BPS, 0)?;
+    assert_eq!((amount, blocks), (repayment, DURATION_BLOCKS - DURATION_BLOCKS / 2));
+    assert_eq!(value, calculate_present_value(repayment, APR_500_BPS, blocks, APR_PRECISION));
+    assert!(value > LOAN_AMOUNT, "Accrued interest is worth more than the principal");
+
+    h::repay_loan(&take_block, halfway + 1, lending_id, &terms)?;
+    assert_eq!(present_value(halfway + 2, lending_id, APR_500_BPS, 0)?, [0; 4], "Nothing to price once repaid");
+
+    println!("GetPresentValue test passed");
+    Ok(())
+}
+
+/// Test GetStatusWord (opcode 103) across active, overdue, repaid and claimed.
+#[wasm_bindgen_test]
+fn test_get_status_word() -> Result<()> {
+    const STATUS_OVERDUE: u128 = 1 << 8;
+    const STATUS_CLAIMABLE: u128 = 1 << 11;
+
+    let (repay_block, ids) = h::setup_to_repaid_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 4, lending_id, 103)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_REPAID | STATUS_CLAIMABLE);
+
+    h::claim_repayment(&repay_block, DEPLOY_HEIGHT + 5, lending_id)?;
+    let data = h::call_view(DEPLOY_HEIGHT + 6, lending_id, 103)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_REPAID, "Nothing left to claim");
+
+    let (_take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+    let deadline = DEPLOY_HEIGHT + 2 + DURATION_BLOCKS as u32;
+
+    let data = h::call_view(deadline, lending_id, 103)?;
+    assert_eq!(h::read_u128_le(&data, 0), STATE_LOAN_ACTIVE, "Not overdue at the deadline");
+
+    let data = h::call_view(deadline + 1, lending_id, 103)?;
+    assert_eq!(
+        h::read_u128_le(&data, 0),
+        STATE_LOAN_ACTIVE | STATUS_OVERDUE | STATUS_CLAIMABLE,
+        "Overdue loan has claimable collateral"
+    );
+
+    println!("GetStatusWord test passed");
+    Ok(())
+}
+
+/// Look up (role, callable) for `opcode` in a GetPermissions response.
+fn permission_for(data: &[u8], opcode: u128) -> (u128, u128) {
+    data.chunks(48)
+        .find(|entry| h::read_u128_le(entry, 0) == opcode)
+        .map(|entry| (h::read_u128_le(entry, 16), h::read_u128_le(entry, 32)))
+        .expect("opcode missing from access table")
+}
+
+/// Test GetPermissions (opcode 101) reports roles and tracks the current state.
+#[wasm_bindgen_test]
+fn test_get_permissions() -> Result<()> {
+    const ROLE_ANYONE: u128 = 0;
+    const ROLE_DEBITOR: u128 = 1;
+    const ROLE_CREDITOR: u128 = 2;
+
+    let (_take_block, ids) = h::setup_to_active_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 3, lending_id, 101)?;
+    assert_eq!(data.len() % 48, 0, "Entries are 3 × u128");
+
+    assert_eq!(permission_for(&data, 1), (ROLE_ANYONE, 0), "Take is closed once active");
+    assert_eq!(permission_for(&data, 2), (ROLE_ANYONE, 1));
+    assert_eq!(permission_for(&data, 3), (ROLE_CREDITOR, 1));
+    assert_eq!(permission_for(&data, 4), (ROLE_CREDITOR, 0), "Cancel is closed once active");
+    assert_eq!(permission_for(&data, 5), (ROLE_CREDITOR, 0));
+    assert_eq!(permission_for(&data, 7), (ROLE_DEBITOR, 1));
+    assert_eq!(permission_for(&data, 92), (ROLE_ANYONE, 1));
+
+    println!("GetPermissions test passed");
+    Ok(())
+}
+
+/// Test GetAbi (opcode 106) exports the derived opcode descriptions.
+#[wasm_bindgen_test]
+fn test_get_abi() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 106)?;
+    let abi = String::from_utf8(data)?;
+
+    assert!(abi.contains("opcode"), "ABI lists opcodes: {}", abi);
+    assert!(abi.contains("collateral_token"), "ABI names init inputs: {}", abi);
+    assert!(abi.contains("offset"), "ABI names GetHistory inputs: {}", abi);
+
+    println!("GetAbi test passed");
+    Ok(())
+}
+
+/// Test GetVersion (opcode 107) reports semver with build metadata.
+#[wasm_bindgen_test]
+fn test_get_version() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, &ids.lending_contract, 107)?;
+    let version = String::from_utf8(data)?;
+
+    let (semver, git_hash) = version
+        .split_once('+')
+        .ok_or_else(|| anyhow::anyhow!("Missing build metadata: {}", version))?;
+    let parts: Vec<&str> = semver.split('.').collect();
+    assert_eq!(parts.len(), 3, "major.minor.patch: {}", version);
+    assert!(parts.iter().all(|part| part.parse::<u64>().is_ok()), "Numeric semver: {}", version);
+    assert!(!git_hash.is_empty(), "Git hash present: {}", version);
+
+    println!("GetVersion test passed: {}", version);
+    Ok(())
+}
+
+/// Test that every state-changing opcode GetPermissions reports as closed
+/// reverts with its state error: the dispatcher enforces the same table.
+#[wasm_bindgen_test]
+fn test_permissions_enforced_in_waiting_state() -> Result<()> {
+    let (_init_block, ids) = h::setup_to_waiting_state()?;
+    let lending_id = &ids.lending_contract;
+
+    let data = h::call_view(DEPLOY_HEIGHT + 2, lending_id, 101)?;
+    let closed: Vec<(u128, &str)> = vec![
+        (2, "No active loan to repay"),
+        (3, "No active loan to claim"),
+        (5, "Loan must be repaid to claim"),
+        (6, "No active loan to capitalize"),
+        (7, "No active loan to capitalize"),
+        (8, "Bonds are not redeemable yet"),
+        (12, "No position to label"),
+    ];
+
+    for (i, (opcode, expected)) in closed.into_iter().enumerate() {
+        assert_eq!(permission_for(&data, opcode).1, 0, "Opcode {} should be closed", opcode);
+
+        let inputs = if opcode == 6 || opcode == 12 { vec![opcode, 1] } else { vec![opcode] };
+        let cellpack = Cellpack {
+            target: lending_id.clone(),
+            inputs,
+        };
+        let block = h::execute_cellpack_no_balance(DEPLOY_HEIGHT + 2 + i as u32, cellpack)?;
+        h::assert_revert(&block, expected)?;
+    }
+
+    println!("Access table enforced in waiting state");
+    Ok(())
+}
+
 /// Test GetName (opcode 99) and GetSymbol (opcode 100).
 /// The lending contract does not set a name or symbol, so both should return
 /// empty data.
@@ -4,29 +4,34 @@
 //! - Rounding errors (manipulating APR/duration to pay zero interest)
 //! - Unauthenticated access (calling restricted opcodes without auth token)
 //! - Integer overflow attacks on every arithmetic path in the contract
+//! - Transaction ordering within a block (calls racing for the same contract)
 //!
 //! The contract's interest formula is:
 //!   interest = principal * apr * duration / (APR_PRECISION * BLOCKS_PER_YEAR)
 //!            = principal * apr * duration / 525_600_000
 //!   repayment = principal + interest
 //!
-//! The contract uses checked_mul / checked_add which return Err on overflow.
+//! principal × (apr × duration) is taken in 256 bits; apr × duration, the
+//! quotient and the final sum use checked arithmetic which returns Err on
+//! overflow.
 //! These tests verify that overflow always causes a clean revert — never a
 //! silent wrap-around to zero.
 
 #![cfg(test)]
 
+use crate::tests::helper::common::calculate_repayment_amount;
 use crate::tests::helper::lending_helpers::{
-    self as h, LoanTerms, DEPLOY_HEIGHT, DURATION_BLOCKS,
-    INIT_TOKEN_SUPPLY,
+    self as h, LoanTerms, APR_500_BPS, COLLATERAL_AMOUNT, DEPLOY_HEIGHT, DURATION_BLOCKS,
+    INIT_TOKEN_SUPPLY, LOAN_AMOUNT,
 };
 
 use alkanes::tests::helpers::get_last_outpoint_sheet;
-use alkanes_support::cellpack::Cellpack;
+use alkanes_support::{cellpack::Cellpack, id::AlkaneId};
 use anyhow::Result;
 #[allow(unused_imports)]
 use metashrew_core::{println, stdio::{stdout, Write}};
 use protorune_support::balance_sheet::BalanceSheetOperations;
+use protorune_support::protostone::ProtostoneEdict;
 use wasm_bindgen_test::wasm_bindgen_test;
 
 // ============================================================================
@@ -154,12 +159,12 @@ fn test_unauthenticated_calls() -> Result<()> {
 // around to a small number, letting the borrower repay almost nothing.
 // ============================================================================
 
-/// ATTACK: Overflow principal × apr (first checked_mul).
+/// ATTACK: Overflow the interest itself.
 ///
 /// principal = INIT_TOKEN_SUPPLY = 10_000_000_000_000 (1e13)
-/// apr = u128::MAX / principal + 1  (just enough to overflow)
+/// apr = u128::MAX, duration = 1
 ///
-/// principal × apr > u128::MAX → checked_mul returns None → revert.
+/// principal × apr / 525_600_000 ≈ 6.5e42 > u128::MAX → revert.
 ///
 /// Without checked arithmetic this would wrap to a small number and the
 /// borrower could repay almost nothing.
@@ -175,15 +180,14 @@ fn test_overflow_principal_times_apr() -> Result<()> {
     // Use the full token supply as loan amount
     let loan_amount = INIT_TOKEN_SUPPLY; // 10_000_000_000_000
 
-    // Choose apr so that loan_amount * apr > u128::MAX
-    // u128::MAX / 10_000_000_000_000 ≈ 3.4e25
-    // We need apr > 3.4e25 to overflow. Use u128::MAX / loan_amount + 1.
-    let overflow_apr = u128::MAX / loan_amount + 1;
+    // The 256-bit product never overflows, but the interest it prices
+    // exceeds u128::MAX
+    let overflow_apr = u128::MAX;
 
     let mut terms = LoanTerms::default_from(&ids);
     terms.loan_amount = loan_amount;
     terms.apr = overflow_apr;
-    terms.duration_blocks = 1; // minimal duration, overflow is in first mul
+    terms.duration_blocks = 1; // minimal duration, overflow is in the quotient
 
     // Init should now revert — the contract validates that the repayment
     // amount is calculable before accepting the loan offer.
@@ -193,34 +197,28 @@ fn test_overflow_principal_times_apr() -> Result<()> {
 
     h::assert_revert(&init_block, "Overflow in interest calculation")?;
 
-    println!("PASS: principal * apr overflow rejected at init time");
+    println!("PASS: interest overflow rejected at init time");
     Ok(())
 }
 
-/// ATTACK: Overflow (principal × apr) × duration (second checked_mul).
-///
-/// Choose values where principal × apr fits in u128, but the product
-/// times duration overflows.
+/// ATTACK: Overflow apr × duration (checked_mul).
 ///
 /// principal = INIT_TOKEN_SUPPLY = 1e13
-/// apr = 1e12 (a huge but non-overflowing APR for the first mul)
-/// principal × apr = 1e25 (fits in u128, max is ~3.4e38)
-/// duration = u128::MAX / (principal × apr) + 1 → overflows second mul
+/// apr = 1e12 (absurd, but fits)
+/// duration = u128::MAX / apr + 1 → apr × duration overflows
 ///
 /// FINDING: Contract rejects the loan offer at init time — the overflow
-/// in the second multiplication is caught before any tokens are locked.
+/// in the rate multiplication is caught before any tokens are locked.
 #[wasm_bindgen_test]
 fn test_overflow_intermediate_times_duration() -> Result<()> {
     let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
     let lending_id = &ids.lending_contract;
 
     let loan_amount = INIT_TOKEN_SUPPLY;
-    let apr: u128 = 1_000_000_000_000; // 1e12 — absurd but doesn't overflow first mul
-    let first_product = loan_amount.checked_mul(apr).expect("should fit");
-    // first_product = 1e25, which is < u128::MAX (~3.4e38) ✓
+    let apr: u128 = 1_000_000_000_000; // 1e12 — absurd but fits
 
-    // Now pick duration so first_product * duration overflows
-    let overflow_duration = u128::MAX / first_product + 1;
+    // Now pick duration so apr * duration overflows
+    let overflow_duration = u128::MAX / apr + 1;
 
     let mut terms = LoanTerms::default_from(&ids);
     terms.loan_amount = loan_amount;
@@ -234,7 +232,7 @@ fn test_overflow_intermediate_times_duration() -> Result<()> {
 
     h::assert_revert(&init_block, "Overflow in interest calculation")?;
 
-    println!("PASS: (principal * apr) * duration overflow rejected at init time");
+    println!("PASS: apr * duration overflow rejected at init time");
     Ok(())
 }
 
@@ -249,22 +247,12 @@ fn test_overflow_intermediate_times_duration() -> Result<()> {
 /// That means: principal * apr * duration / 525_600_000 ≈ u128::MAX
 /// So: principal * apr * duration ≈ u128::MAX * 525_600_000
 ///
-/// But u128::MAX * 525_600_000 > u128::MAX, so the numerator would overflow
-/// first. We need a case where the numerator is large but doesn't overflow,
-/// yet the quotient is still close to u128::MAX.
-///
-/// Actually, the maximum non-overflowing numerator is u128::MAX itself.
-/// u128::MAX / 525_600_000 ≈ 6.47e29.
-/// So the max interest we can get without the mul overflowing is ~6.47e29.
-/// And principal is at most 1e13 (our supply).
-/// principal + interest = 1e13 + 6.47e29 ≈ 6.47e29, which fits in u128.
-///
-/// This means with our token supply, principal + interest can never overflow
-/// u128 without the multiplication overflowing first. The checked_mul will
-/// catch it before checked_add ever gets a chance to overflow.
+/// The numerator is taken in 256 bits, so it never overflows; with a
+/// principal of at most 1e13 (our supply), an interest that fits in u128
+/// leaves the sum within u128 unless it is within 1e13 of u128::MAX.
 ///
-/// This test verifies that understanding: with max-possible interest that
-/// doesn't overflow the muls, the add still fits.
+/// This test verifies that a triple product close to u128::MAX prices a
+/// huge but valid repayment rather than wrapping around.
 #[wasm_bindgen_test]
 fn test_overflow_principal_plus_interest_boundary() -> Result<()> {
     let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
@@ -393,7 +381,7 @@ fn test_overflow_griefing_attack_creditor_steals_collateral() -> Result<()> {
 
     // Creditor tries to set up a loan with an absurd APR that will overflow
     let loan_amount = INIT_TOKEN_SUPPLY;
-    let overflow_apr = u128::MAX / loan_amount + 1; // guarantees overflow
+    let overflow_apr = u128::MAX / DURATION_BLOCKS; // interest exceeds u128::MAX
 
     let mut terms = LoanTerms::default_from(&ids);
     terms.loan_amount = loan_amount;
@@ -526,3 +514,127 @@ fn test_near_overflow_boundary_no_wrap() -> Result<()> {
     println!("PASS: near-boundary calculation produces correct large value, no wrap-around");
     Ok(())
 }
+
+// ============================================================================
+// Same-Block Ordering
+// ============================================================================
+//
+// Miners choose the order of transactions in a block, so every ordering of
+// calls to one contract must resolve deterministically: the call that is
+// invalid at its position reverts with its tokens refunded, and the rest
+// apply as if it were absent.
+
+const STATE_WAITING_FOR_DEBITOR_TAKE: u128 = 1;
+const STATE_LOAN_ACTIVE: u128 = 2;
+
+/// A call for [`h::execute_cellpacks_in_block`]: `opcode` on the lending
+/// contract, sending `amount` of `token`.
+fn lending_call(
+    ids: &h::LendingDeploymentIds,
+    opcode: u128,
+    token: &AlkaneId,
+    amount: u128,
+) -> (Cellpack, Vec<ProtostoneEdict>) {
+    (
+        Cellpack { target: ids.lending_contract, inputs: vec![opcode] },
+        vec![ProtostoneEdict { id: (*token).into(), amount, output: 0 }],
+    )
+}
+
+/// InitWithLoanOffer with `terms`, sending the loan tokens.
+fn init_call(ids: &h::LendingDeploymentIds, terms: &LoanTerms) -> (Cellpack, Vec<ProtostoneEdict>) {
+    let (_, edicts) = lending_call(ids, 0, &terms.loan_token, terms.loan_amount);
+    (h::build_init_cellpack(&ids.lending_contract, terms), edicts)
+}
+
+/// Read the contract state (GetState, opcode 92) at `height`.
+fn state_at(height: u32, ids: &h::LendingDeploymentIds) -> Result<u128> {
+    Ok(h::read_u128_le(&h::call_view(height, &ids.lending_contract, 92)?, 0))
+}
+
+/// Take ordered before init: the take finds no offer and reverts, the init
+/// still creates it.
+#[wasm_bindgen_test]
+fn test_same_block_take_before_init() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let terms = LoanTerms::default_from(&ids);
+
+    let init = init_call(&ids, &terms);
+    let take = lending_call(&ids, 1, &ids.collateral_token, COLLATERAL_AMOUNT);
+    let block = h::execute_cellpacks_in_block(&deploy_block, DEPLOY_HEIGHT + 1, vec![take, init])?;
+
+    h::assert_revert_at(&block, 1, "Loan offer is not available")?;
+    assert_eq!(state_at(DEPLOY_HEIGHT + 2, &ids)?, STATE_WAITING_FOR_DEBITOR_TAKE);
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY, "Take collateral refunded");
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - LOAN_AMOUNT);
+
+    // The opposite order runs both
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let init = init_call(&ids, &terms);
+    let take = lending_call(&ids, 1, &ids.collateral_token, COLLATERAL_AMOUNT);
+    let block = h::execute_cellpacks_in_block(&deploy_block, DEPLOY_HEIGHT + 1, vec![init, take])?;
+
+    assert_eq!(state_at(DEPLOY_HEIGHT + 2, &ids)?, STATE_LOAN_ACTIVE);
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT);
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("PASS: take before init reverts, init before take runs both");
+    Ok(())
+}
+
+/// Repay ordered before take: the repay finds no loan and reverts with its
+/// tokens refunded, the take still goes through.
+#[wasm_bindgen_test]
+fn test_same_block_repay_before_take() -> Result<()> {
+    let (init_block, ids) = h::setup_to_waiting_state()?;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    let repay = lending_call(&ids, 2, &ids.loan_token, repayment);
+    let take = lending_call(&ids, 1, &ids.collateral_token, COLLATERAL_AMOUNT);
+    let block = h::execute_cellpacks_in_block(&init_block, DEPLOY_HEIGHT + 2, vec![repay, take])?;
+
+    h::assert_revert_at(&block, 1, "No active loan to repay")?;
+    assert_eq!(state_at(DEPLOY_HEIGHT + 3, &ids)?, STATE_LOAN_ACTIVE);
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT);
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Repayment refunded, loan received");
+
+    println!("PASS: repay before take reverts, take applies");
+    Ok(())
+}
+
+/// Two takes racing for one offer, in both orderings: the first in block
+/// order wins, the second reverts as already taken and keeps its collateral.
+#[wasm_bindgen_test]
+fn test_same_block_double_take() -> Result<()> {
+    // The takes differ only in the collateral sent; the winner's excess is
+    // refunded, so either ordering escrows exactly the required amount
+    for (first_amount, second_amount) in [
+        (COLLATERAL_AMOUNT, 2 * COLLATERAL_AMOUNT),
+        (2 * COLLATERAL_AMOUNT, COLLATERAL_AMOUNT),
+    ] {
+        let (init_block, ids) = h::setup_to_waiting_state()?;
+
+        let first = lending_call(&ids, 1, &ids.collateral_token, first_amount);
+        let second = lending_call(&ids, 1, &ids.collateral_token, second_amount);
+        let block = h::execute_cellpacks_in_block(&init_block, DEPLOY_HEIGHT + 2, vec![first, second])?;
+
+        h::assert_revert_at(&block, 2, "Loan offer already taken")?;
+        assert_eq!(state_at(DEPLOY_HEIGHT + 3, &ids)?, STATE_LOAN_ACTIVE);
+        let sheet = get_last_outpoint_sheet(&block)?;
+        assert_eq!(
+            sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - COLLATERAL_AMOUNT,
+            "Only the winning take's collateral is escrowed"
+        );
+        assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Winner received the loan");
+
+        // History records a single take
+        let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 3, &ids.lending_contract, vec![96, 0, 10])?;
+        assert_eq!(h::read_u128_le(&data, 0), 2, "Offer created + one take");
+    }
+
+    println!("PASS: exactly one of two same-block takes succeeds in either order");
+    Ok(())
+}
@@ -0,0 +1,121 @@
+//! Golden vectors for the lending wire format
+//!
+//! Each file in `fixtures/` holds one canonical encoding as hex, one field
+//! per line: `*.cellpack.hex` is a cellpack's target and inputs as LEB128
+//! varints (the protostone encoding, see `Cellpack::encipher`), `*.view.hex`
+//! is a view response as little-endian u128s. The tests check both
+//! directions: the fixture decodes to the expected fields, and the
+//! helpers/contract produce exactly the fixture bytes. A failure here means
+//! the wire format changed; update the fixture only if that change is
+//! intended.
+
+#![cfg(test)]
+
+use crate::tests::helper::common::calculate_repayment_amount;
+use crate::tests::helper::lending_helpers::{
+    self as h, LoanTerms, APR_500_BPS, COLLATERAL_AMOUNT, DEPLOY_HEIGHT, DURATION_BLOCKS,
+    LOAN_AMOUNT,
+};
+
+use alkanes_support::cellpack::Cellpack;
+use anyhow::Result;
+#[allow(unused_imports)]
+use metashrew_core::{println, stdio::{stdout, Write}};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// Decode a fixture: hex with arbitrary whitespace
+fn fixture_bytes(fixture: &str) -> Vec<u8> {
+    let hex: String = fixture.split_whitespace().collect();
+    hex::decode(hex).expect("fixture is not valid hex")
+}
+
+/// Split a view encoding into its u128 fields
+fn fields(data: &[u8]) -> Vec<u128> {
+    assert_eq!(data.len() % 16, 0, "encoding is not a whole number of u128s");
+    (0..data.len()).step_by(16).map(|offset| h::read_u128_le(data, offset)).collect()
+}
+
+/// Split a cellpack encoding into its LEB128 varint fields
+fn varint_fields(data: &[u8]) -> Vec<u128> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0u128, 0);
+    for byte in data {
+        value |= ((byte & 0x7f) as u128) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            values.push(value);
+            (value, shift) = (0, 0);
+        }
+    }
+    assert_eq!(shift, 0, "encoding ends inside a varint");
+    values
+}
+
+/// Test the InitWithLoanOffer and TakeLoanWithCollateral cellpack encodings.
+#[wasm_bindgen_test]
+fn test_golden_cellpacks() -> Result<()> {
+    let (_deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let terms = LoanTerms::default_from(&ids);
+
+    let init = fixture_bytes(include_str!("fixtures/init_loan_offer.cellpack.hex"));
+    let mut expected = vec![
+        2, 1, // lending contract
+        0, // InitWithLoanOffer
+        2, 2, COLLATERAL_AMOUNT,
+        2, 4, LOAN_AMOUNT,
+        DURATION_BLOCKS, APR_500_BPS,
+    ];
+    // flags, fees, penalties, timing options, reservation, allowed taker,
+    // APR precision
+    expected.extend([0; 13]);
+    assert_eq!(varint_fields(&init), expected, "Init fixture decodes to the default terms");
+    assert_eq!(
+        h::build_init_cellpack(&ids.lending_contract, &terms).encipher(), init,
+        "Init cellpack encoding changed"
+    );
+
+    let take = fixture_bytes(include_str!("fixtures/take_loan.cellpack.hex"));
+    assert_eq!(varint_fields(&take), vec![2, 1, 1]);
+    let cellpack = Cellpack { target: ids.lending_contract, inputs: vec![1] };
+    assert_eq!(cellpack.encipher(), take, "Take cellpack encoding changed");
+
+    println!("Golden cellpack vectors passed");
+    Ok(())
+}
+
+/// Test the GetRepaymentQuote (opcode 95) and GetHistory (opcode 96)
+/// response encodings.
+#[wasm_bindgen_test]
+fn test_golden_views() -> Result<()> {
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    let interest = repayment - LOAN_AMOUNT;
+
+    let quote = fixture_bytes(include_str!("fixtures/repayment_quote_active.view.hex"));
+    assert_eq!(
+        fields(&quote),
+        vec![repayment, LOAN_AMOUNT, interest, 0, 0, 0],
+        "Quote fixture: amount due, principal, interest, break fee, prepayment and late penalty"
+    );
+    let (_take_block, ids) = h::setup_to_active_state()?;
+    let data = h::call_view(DEPLOY_HEIGHT + 3, &ids.lending_contract, 95)?;
+    assert_eq!(data, quote, "GetRepaymentQuote encoding changed");
+
+    let history = fixture_bytes(include_str!("fixtures/history_repaid.view.hex"));
+    let block = |offset: u32| (DEPLOY_HEIGHT + offset) as u128;
+    assert_eq!(
+        fields(&history),
+        vec![
+            3, 0, // entry count, page flags
+            block(1), 0, LOAN_AMOUNT, // offer created
+            block(2), 1, LOAN_AMOUNT, // loan taken
+            block(3), 2, repayment, // loan repaid
+        ],
+        "History fixture decodes to the repaid lifecycle"
+    );
+    let (_repay_block, ids) = h::setup_to_repaid_state()?;
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 4, &ids.lending_contract, vec![96, 0, 10])?;
+    assert_eq!(data, history, "GetHistory encoding changed");
+
+    println!("Golden view vectors passed");
+    Ok(())
+}
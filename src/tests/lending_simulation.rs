@@ -0,0 +1,286 @@
+//! Randomized lending simulation
+//!
+//! Runs a book of loans with pseudo-random terms side by side on one chain,
+//! each in its own lending contract cloned from a single template. Offers are
+//! all made in the first blocks; takes, repayments, calls, default notices
+//! and claims then interleave over several hundred blocks, so many loans are
+//! live at once. Checks invariants that must hold on every path regardless of
+//! which loan options are combined:
+//! - the amount due the contract quotes (GetRepaymentQuote) right before each
+//!   repayment matches the reference interest math, and paying exactly that
+//!   amount settles the loan
+//! - tokens are conserved: once every loan is settled and claimed, the wallet
+//!   acting as both creditor and debitor holds the full supply of both tokens
+//! - every contract ends in the expected terminal state
+//!
+//! The generator is seeded, so a failing run can be replayed exactly.
+
+#![cfg(test)]
+
+use crate::tests::helper::common::{
+    calculate_early_repayment_amount, calculate_repayment_amount, FLAG_EARLY_REPAYMENT_REBATE,
+    FLAG_OPEN_TERM,
+};
+use crate::tests::helper::lending_helpers::{
+    self as h, LoanTerms, DEPLOY_HEIGHT, INIT_TOKEN_SUPPLY,
+};
+
+use alkanes::tests::helpers::get_last_outpoint_sheet;
+use alkanes_support::{cellpack::Cellpack, id::AlkaneId};
+use anyhow::Result;
+#[allow(unused_imports)]
+use metashrew_core::{println, stdio::{stdout, Write}};
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use protorune_support::protostone::ProtostoneEdict;
+use std::collections::BTreeMap;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// Contract state constants (mirror contract's internal values)
+const STATE_LOAN_REPAID: u128 = 3;
+const STATE_LOAN_DEFAULTED: u128 = 4;
+
+/// Number of loans simulated
+const LOAN_COUNT: u128 = 48;
+
+/// Offers made per block while the book is originated
+const OFFERS_PER_BLOCK: u128 = 12;
+
+/// Blocks over which the offers are taken, once all of them are made
+const TAKE_WINDOW_BLOCKS: u128 = 200;
+
+/// Sequence number of the first cloned lending contract: the template and
+/// the two tokens with their auth tokens take 1 through 5. Takes mint
+/// debitor notes from the factory, which also takes sequence numbers, so
+/// every offer is made before the first take.
+const FIRST_CLONE_SEQUENCE: u128 = 6;
+
+/// Seed for the simulation's pseudo-random generator
+const SIMULATION_SEED: u64 = 0x5eed_1e4d_2024_0001;
+
+/// Minimal xorshift64 generator, enough to vary loan terms deterministically.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    /// Uniform value in `lo..=hi`
+    fn range(&mut self, lo: u128, hi: u128) -> u128 {
+        lo + self.next() as u128 % (hi - lo + 1)
+    }
+
+    /// True with probability `percent`/100
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+/// Loan repayment mode chosen for a loan
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    FullTerm,
+    Rebate,
+    OpenTerm,
+}
+
+/// One call of the simulation. `quote` is the amount due the contract must
+/// report in the call's block, checked before a repayment.
+struct Step {
+    loan: u128,
+    cellpack: Cellpack,
+    edicts: Vec<ProtostoneEdict>,
+    quote: Option<u128>,
+}
+
+/// Calls of the simulation by block height
+type Schedule = BTreeMap<u32, Vec<Step>>;
+
+/// Lending contract of loan `index`
+fn lending_id(index: u128) -> AlkaneId {
+    AlkaneId {
+        block: 2,
+        tx: FIRST_CLONE_SEQUENCE + index,
+    }
+}
+
+/// Schedule a call of `opcode` on loan `index` at `height`, sending `amount`
+/// of `token`
+fn schedule(
+    steps: &mut Schedule,
+    height: u32,
+    index: u128,
+    opcode: u128,
+    token: AlkaneId,
+    amount: u128,
+    quote: Option<u128>,
+) {
+    let cellpack = Cellpack {
+        target: lending_id(index),
+        inputs: vec![opcode],
+    };
+    let edicts = vec![ProtostoneEdict {
+        id: token.into(),
+        amount,
+        output: 0,
+    }];
+    steps.entry(height).or_default().push(Step { loan: index, cellpack, edicts, quote });
+}
+
+/// Draw the terms and fate of loan `index`, schedule its calls, and return
+/// the terminal state it must end in.
+fn plan_loan(
+    rng: &mut Rng,
+    steps: &mut Schedule,
+    ids: &h::LendingDeploymentIds,
+    index: u128,
+    take_height: u32,
+) -> u128 {
+    let lending = lending_id(index);
+    let mode = match rng.range(0, 2) {
+        0 => Mode::FullTerm,
+        1 => Mode::Rebate,
+        _ => Mode::OpenTerm,
+    };
+
+    let mut terms = LoanTerms::default_from(ids);
+    terms.loan_amount = rng.range(1_000, 1_000_000_000);
+    terms.collateral_amount = terms.loan_amount * rng.range(1, 3);
+    terms.duration_blocks = rng.range(10, 200);
+    terms.apr = rng.range(0, 5_000);
+    match mode {
+        Mode::FullTerm => {}
+        Mode::Rebate => {
+            terms.loan_flags = FLAG_EARLY_REPAYMENT_REBATE;
+            // Interest-free loans cannot charge a break fee
+            if terms.apr != 0 {
+                terms.break_fee_bps = rng.range(0, 10_000);
+            }
+        }
+        Mode::OpenTerm => terms.loan_flags = FLAG_OPEN_TERM,
+    }
+    if rng.chance(30) {
+        terms.default_notice_blocks = rng.range(1, 50);
+    }
+    let repays = rng.chance(60);
+
+    println!(
+        "Loan {}: {:?} loan={} collateral={} duration={} apr={} notice={} take={} repays={}",
+        index, mode, terms.loan_amount, terms.collateral_amount, terms.duration_blocks,
+        terms.apr, terms.default_notice_blocks, take_height, repays
+    );
+
+    // The offer clones the uninitialized template (5:n copies 2:n) and
+    // initializes the copy in the same call
+    let offer_height = DEPLOY_HEIGHT + 1 + (index / OFFERS_PER_BLOCK) as u32;
+    let template = AlkaneId {
+        block: 5,
+        tx: ids.lending_contract.tx,
+    };
+    let offer = h::build_init_cellpack(&template, &terms);
+    steps.entry(offer_height).or_default().push(Step {
+        loan: index,
+        cellpack: offer,
+        edicts: vec![ProtostoneEdict {
+            id: terms.loan_token.into(),
+            amount: terms.loan_amount,
+            output: 0,
+        }],
+        quote: None,
+    });
+    schedule(steps, take_height, index, 1, terms.collateral_token, terms.collateral_amount, None);
+
+    if repays {
+        let elapsed = match mode {
+            Mode::OpenTerm => rng.range(1, 2 * terms.duration_blocks),
+            _ => rng.range(1, terms.duration_blocks),
+        };
+        let amount = match mode {
+            Mode::FullTerm => {
+                calculate_repayment_amount(terms.loan_amount, terms.apr, terms.duration_blocks)
+            }
+            Mode::Rebate => calculate_early_repayment_amount(
+                terms.loan_amount, terms.apr, terms.duration_blocks, elapsed, terms.break_fee_bps,
+            ),
+            Mode::OpenTerm => calculate_repayment_amount(terms.loan_amount, terms.apr, elapsed),
+        };
+
+        let repay_height = take_height + elapsed as u32;
+        schedule(steps, repay_height, index, 2, terms.loan_token, amount, Some(amount));
+        schedule(steps, repay_height + 1, index, 5, lending, 1, None);
+        STATE_LOAN_REPAID
+    } else {
+        let deadline = match mode {
+            Mode::OpenTerm => {
+                let call_height = take_height + rng.range(1, terms.duration_blocks) as u32;
+                schedule(steps, call_height, index, 9, lending, 1, None);
+                call_height + terms.duration_blocks as u32
+            }
+            _ => take_height + terms.duration_blocks as u32,
+        };
+
+        if terms.default_notice_blocks == 0 {
+            schedule(steps, deadline + 1, index, 3, lending, 1, None);
+        } else {
+            schedule(steps, deadline + 1, index, 10, lending, 1, None);
+            let claim_height = deadline + 2 + terms.default_notice_blocks as u32;
+            schedule(steps, claim_height, index, 3, lending, 1, None);
+        }
+        STATE_LOAN_DEFAULTED
+    }
+}
+
+/// Simulate a book of overlapping randomized loans on one chain and check
+/// quotes, conservation and final states.
+#[wasm_bindgen_test]
+fn test_randomized_loan_simulation() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let mut rng = Rng(SIMULATION_SEED);
+
+    let first_take = DEPLOY_HEIGHT + 1 + LOAN_COUNT.div_ceil(OFFERS_PER_BLOCK) as u32;
+    let mut steps = Schedule::new();
+    let expected_states: Vec<u128> = (0..LOAN_COUNT)
+        .map(|index| {
+            let take_height = first_take + rng.range(0, TAKE_WINDOW_BLOCKS) as u32;
+            plan_loan(&mut rng, &mut steps, &ids, index, take_height)
+        })
+        .collect();
+
+    let mut block = deploy_block;
+    let mut height = DEPLOY_HEIGHT;
+    for (block_height, calls) in steps {
+        for step in calls.iter().filter(|step| step.quote.is_some()) {
+            let data = h::call_view(block_height, &lending_id(step.loan), 95)?;
+            assert_eq!(
+                Some(h::read_u128_le(&data, 0)), step.quote,
+                "Loan {}: quoted amount due differs from the reference math", step.loan
+            );
+        }
+        let calls = calls.into_iter().map(|step| (step.cellpack, step.edicts)).collect();
+        block = h::execute_cellpacks_in_block(&block, block_height, calls)?;
+        height = block_height;
+    }
+
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Loan token supply not conserved");
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Collateral token supply not conserved"
+    );
+
+    for (index, expected) in (0..LOAN_COUNT).zip(expected_states) {
+        let data = h::call_view(height + 1, &lending_id(index), 92)?;
+        assert_eq!(h::read_u128_le(&data, 0), expected, "Loan {}: unexpected final state", index);
+    }
+
+    println!(
+        "Simulated {} overlapping loans over {} blocks",
+        LOAN_COUNT, height - DEPLOY_HEIGHT
+    );
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Mass default stress test
+//!
+//! Originates a few hundred loans on one chain, each in its own lending
+//! contract cloned from a single template, then lets most of them default
+//! together and claims every defaulted collateral within a few blocks. Checks
+//! that every claim in the wave goes through (a call running out of fuel would
+//! revert and strand its tokens in the contract):
+//! - tokens are conserved: the wallet acting as creditor and debitor of every
+//!   loan ends with the full supply of both tokens
+//! - every contract ends in the expected terminal state with its creditor
+//!   note burned
+
+#![cfg(test)]
+
+use crate::tests::helper::common::calculate_repayment_amount;
+use crate::tests::helper::lending_helpers::{
+    self as h, LoanTerms, DEPLOY_HEIGHT, DURATION_BLOCKS, INIT_TOKEN_SUPPLY,
+};
+
+use alkanes::tests::helpers::get_last_outpoint_sheet;
+use alkanes_support::{cellpack::Cellpack, id::AlkaneId};
+use anyhow::Result;
+use bitcoin::Block;
+#[allow(unused_imports)]
+use metashrew_core::{println, stdio::{stdout, Write}};
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use protorune_support::protostone::ProtostoneEdict;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// Contract state constants (mirror contract's internal values)
+const STATE_WAITING_FOR_DEBITOR_TAKE: u128 = 1;
+const STATE_LOAN_REPAID: u128 = 3;
+const STATE_LOAN_DEFAULTED: u128 = 4;
+
+/// Number of loans originated
+const LOAN_COUNT: u128 = 200;
+
+/// Calls per block, so origination and the default wave span few blocks
+const CALLS_PER_BLOCK: usize = 25;
+
+/// One loan in this many is repaid; the rest default
+const REPAID_ONE_IN: u128 = 5;
+
+/// Sequence number of the first cloned lending contract: the template and
+/// the two tokens with their auth tokens take 1 through 5
+const FIRST_CLONE_SEQUENCE: u128 = 6;
+
+/// Lending contract of loan `index`
+fn lending_id(index: u128) -> AlkaneId {
+    AlkaneId {
+        block: 2,
+        tx: FIRST_CLONE_SEQUENCE + index,
+    }
+}
+
+/// Whether loan `index` is repaid: the last of every `REPAID_ONE_IN` loans
+fn is_repaid(index: u128) -> bool {
+    index % REPAID_ONE_IN == REPAID_ONE_IN - 1
+}
+
+/// Run `calls` in consecutive blocks from `height`, `CALLS_PER_BLOCK` per
+/// block. Returns the last block and the height after it.
+fn execute_in_blocks(
+    prev_block: Block,
+    mut height: u32,
+    calls: Vec<(Cellpack, Vec<ProtostoneEdict>)>,
+) -> Result<(Block, u32)> {
+    let mut block = prev_block;
+    for batch in calls.chunks(CALLS_PER_BLOCK) {
+        block = h::execute_cellpacks_in_block(&block, height, batch.to_vec())?;
+        height += 1;
+    }
+    Ok((block, height))
+}
+
+/// A call sending `amount` of `token` to `target`
+fn call_with_tokens(
+    target: AlkaneId,
+    inputs: Vec<u128>,
+    token: AlkaneId,
+    amount: u128,
+) -> (Cellpack, Vec<ProtostoneEdict>) {
+    let edicts = vec![ProtostoneEdict {
+        id: token.into(),
+        amount,
+        output: 0,
+    }];
+    (Cellpack { target, inputs }, edicts)
+}
+
+/// Test a wave of defaults claimed back to back across hundreds of loans.
+#[wasm_bindgen_test]
+fn test_mass_default_wave() -> Result<()> {
+    let (deploy_block, ids) = h::deploy_lending_with_tokens()?;
+    let terms = LoanTerms::default_from(&ids);
+    let repayment = calculate_repayment_amount(terms.loan_amount, terms.apr, terms.duration_blocks);
+
+    // Originate: each offer clones the uninitialized template (5:n copies
+    // 2:n) and initializes the copy in the same call
+    let template = AlkaneId {
+        block: 5,
+        tx: ids.lending_contract.tx,
+    };
+    let offers = (0..LOAN_COUNT)
+        .map(|_| {
+            let cellpack = h::build_init_cellpack(&template, &terms);
+            call_with_tokens(cellpack.target, cellpack.inputs, ids.loan_token, terms.loan_amount)
+        })
+        .collect();
+    let (block, height) = execute_in_blocks(deploy_block, DEPLOY_HEIGHT + 1, offers)?;
+    for index in [0, LOAN_COUNT - 1] {
+        let data = h::call_view(height, &lending_id(index), 92)?;
+        assert_eq!(h::read_u128_le(&data, 0), STATE_WAITING_FOR_DEBITOR_TAKE, "Loan {} offered", index);
+    }
+
+    let takes = (0..LOAN_COUNT)
+        .map(|index| {
+            call_with_tokens(lending_id(index), vec![1], ids.collateral_token, terms.collateral_amount)
+        })
+        .collect();
+    let (block, height) = execute_in_blocks(block, height, takes)?;
+    let last_deadline = height - 1 + DURATION_BLOCKS as u32;
+
+    let repayments = (0..LOAN_COUNT)
+        .filter(|index| is_repaid(*index))
+        .map(|index| call_with_tokens(lending_id(index), vec![2], ids.loan_token, repayment))
+        .collect();
+    let (block, _) = execute_in_blocks(block, height, repayments)?;
+
+    // The wave: every defaulted loan is claimed in the blocks right after the
+    // last deadline, interleaved with the repaid loans' claims
+    let claims = (0..LOAN_COUNT)
+        .map(|index| {
+            let opcode = if is_repaid(index) { 5 } else { 3 };
+            call_with_tokens(lending_id(index), vec![opcode], lending_id(index), 1)
+        })
+        .collect();
+    let (block, height) = execute_in_blocks(block, last_deadline + 1, claims)?;
+
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY, "Loan token supply not conserved");
+    assert_eq!(
+        sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY,
+        "Collateral token supply not conserved"
+    );
+
+    let mut defaulted = 0;
+    for index in 0..LOAN_COUNT {
+        let lending = lending_id(index);
+        let expected = if is_repaid(index) { STATE_LOAN_REPAID } else { STATE_LOAN_DEFAULTED };
+        let data = h::call_view(height, &lending, 92)?;
+        assert_eq!(h::read_u128_le(&data, 0), expected, "Loan {}: unexpected final state", index);
+        let data = h::call_view(height, &lending, 102)?;
+        assert_eq!(h::read_u128_le(&data, 0), 0, "Loan {}: creditor note not burned", index);
+        assert_eq!(sheet.get(&lending.into()), 0, "Loan {}: creditor note left in the wallet", index);
+        if expected == STATE_LOAN_DEFAULTED {
+            defaulted += 1;
+        }
+    }
+    assert_eq!(defaulted, LOAN_COUNT - LOAN_COUNT / REPAID_ONE_IN);
+
+    println!("Claimed {} defaults out of {} loans", defaulted, LOAN_COUNT);
+    Ok(())
+}
@@ -0,0 +1,372 @@
+//! Loan bundler integration tests
+//!
+//! Bundles the creditor notes of two loans lent in the same token, settles
+//! one by repayment and one by default, and redeems the shares for the
+//! proceeds. The wallet acts as creditor, debitor, sponsor and share holder,
+//! so once everything is settled it holds the full supply of both tokens;
+//! where two holders are needed, a split exit leaves the rest of the wallet
+//! on a separate output.
+
+#![cfg(test)]
+
+use crate::tests::helper::bundler_helpers::{self as b, BundlerDeploymentIds};
+use crate::tests::helper::common::{
+    calculate_present_value, calculate_repayment_amount, APR_PRECISION, FLAG_OPEN_TERM,
+    PRICE_PRECISION,
+};
+use crate::tests::helper::lending_helpers::{
+    self as h, LoanTerms, APR_500_BPS, COLLATERAL_AMOUNT, DEPLOY_HEIGHT, DURATION_BLOCKS,
+    HUGE_TOKEN_SUPPLY, INIT_TOKEN_SUPPLY, LOAN_AMOUNT,
+};
+
+use alkanes::tests::helpers::get_last_outpoint_sheet;
+use alkanes_support::cellpack::Cellpack;
+use anyhow::Result;
+use bitcoin::Block;
+#[allow(unused_imports)]
+use metashrew_core::{println, stdio::{stdout, Write}};
+use protorune_support::balance_sheet::BalanceSheetOperations;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+/// Blocks the bundle accepts loans for in these tests
+const JOIN_BLOCKS: u128 = 4;
+
+/// Blocks between a notice of default (or a call) and the default
+const NOTICE_BLOCKS: u128 = 10;
+
+/// Offer and take both loans with the default terms; returns the block after
+/// the second take (taken at `DEPLOY_HEIGHT + 4`).
+fn setup_two_active_loans() -> Result<(Block, BundlerDeploymentIds)> {
+    let (deploy_block, ids) = b::deploy_bundler_with_loans()?;
+    let terms_a = LoanTerms::default_from(&ids.lending(&ids.lending_a));
+    let terms_b = LoanTerms::default_from(&ids.lending(&ids.lending_b));
+
+    let block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_a, &terms_a)?;
+    let block = h::init_loan_offer(&block, DEPLOY_HEIGHT + 2, &ids.lending_b, &terms_b)?;
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 3, &ids.lending_a, &terms_a)?;
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 4, &ids.lending_b, &terms_b)?;
+    Ok((block, ids))
+}
+
+// ============================================================================
+// Lifecycle Tests
+// ============================================================================
+
+/// Test the full bundle lifecycle: join two loans, collect one repayment and
+/// one defaulted collateral, then exit with every share.
+#[wasm_bindgen_test]
+fn test_loan_bundler_lifecycle() -> Result<()> {
+    let (take_block, ids) = setup_two_active_loans()?;
+    let bundler = &ids.bundler;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+
+    // Open the bundle; joins are accepted through DEPLOY_HEIGHT + 9
+    let block = b::init_bundler(&take_block, DEPLOY_HEIGHT + 5, bundler, &ids.loan_token, JOIN_BLOCKS)?;
+    let bundle = b::get_bundle(DEPLOY_HEIGHT + 6, bundler)?;
+    assert_eq!(bundle.loan_token, ids.loan_token);
+    assert_eq!(bundle.join_end_block, (DEPLOY_HEIGHT + 5) as u128 + JOIN_BLOCKS);
+    let sponsor_note = bundle.sponsor_note;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&sponsor_note.into()), 1, "Sponsor should hold the sponsor note");
+    assert_eq!(h::get_creditor_note(DEPLOY_HEIGHT + 6, &ids.lending_a)?, ids.lending_a);
+
+    // Join both loans: one share per unit of principal
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 7, bundler, &sponsor_note, &ids.lending_a)?;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 8, bundler, &sponsor_note, &ids.lending_b)?;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&(*bundler).into()), 2 * LOAN_AMOUNT, "Shares minted for both loans");
+    assert_eq!(sheet.get(&ids.lending_a.into()), 0, "Creditor note A is held by the bundle");
+    assert_eq!(sheet.get(&ids.lending_b.into()), 0, "Creditor note B is held by the bundle");
+    assert_eq!(sheet.get(&sponsor_note.into()), 1, "Sponsor note is refunded on join");
+
+    let bundle = b::get_bundle(DEPLOY_HEIGHT + 9, bundler)?;
+    assert_eq!(bundle.loan_count, 2);
+    assert_eq!(bundle.share_supply, 2 * LOAN_AMOUNT);
+    let data = h::call_view_with_inputs(DEPLOY_HEIGHT + 9, bundler, vec![91, 0])?;
+    assert_eq!(h::read_u128_le(&data, 0), ids.lending_a.block);
+    assert_eq!(h::read_u128_le(&data, 16), ids.lending_a.tx);
+    assert_eq!(h::read_u128_le(&data, 64), LOAN_AMOUNT, "Shares minted for loan A");
+
+    // Each loan is priced over its own remaining term
+    let pv = |blocks: u128| calculate_present_value(repayment, APR_500_BPS, blocks, APR_PRECISION);
+    let value_a = pv(DURATION_BLOCKS - 6);
+    let value_b = pv(DURATION_BLOCKS - 5);
+    let [pending, pending_value, held, total, per_share] =
+        b::get_present_value(DEPLOY_HEIGHT + 9, bundler, APR_500_BPS, 0)?;
+    assert_eq!((pending, pending_value, held), (2, value_a + value_b, 0));
+    assert_eq!(total, value_a + value_b);
+    assert_eq!(per_share, total * PRICE_PRECISION / (2 * LOAN_AMOUNT));
+
+    // Loan A is repaid and collected; loan B is still running
+    let terms_a = LoanTerms::default_from(&ids.lending(&ids.lending_a));
+    let block = h::repay_loan(&block, DEPLOY_HEIGHT + 10, &ids.lending_a, &terms_a)?;
+    let block = b::collect_loan(&block, DEPLOY_HEIGHT + 11, bundler, 0)?;
+    assert_eq!(b::get_holdings(DEPLOY_HEIGHT + 12, bundler)?, vec![(ids.loan_token, repayment)]);
+    let [pending, pending_value, held, total, _] =
+        b::get_present_value(DEPLOY_HEIGHT + 12, bundler, APR_500_BPS, 0)?;
+    assert_eq!((pending, pending_value), (1, pv(DURATION_BLOCKS - 8)), "Only loan B is left to price");
+    assert_eq!((held, total), (repayment, repayment + pv(DURATION_BLOCKS - 8)));
+
+    let again_block = b::collect_loan(&block, DEPLOY_HEIGHT + 13, bundler, 0)?;
+    h::assert_revert(&again_block, "Loan already collected")?;
+
+    // Loan B defaults and its collateral is collected
+    let past_deadline = DEPLOY_HEIGHT + 4 + DURATION_BLOCKS as u32 + 1;
+    let block = b::collect_loan(&again_block, past_deadline, bundler, 1)?;
+    assert_eq!(
+        b::get_holdings(past_deadline + 1, bundler)?,
+        vec![(ids.loan_token, repayment), (ids.collateral_token, COLLATERAL_AMOUNT)]
+    );
+    assert_eq!(b::get_bundle(past_deadline + 1, bundler)?.collected_count, 2);
+
+    // The shares take every holding, leaving the wallet whole
+    let block = b::exit_bundle(&block, past_deadline + 2, bundler, 2 * LOAN_AMOUNT)?;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+    assert_eq!(sheet.get(&(*bundler).into()), 0, "All shares redeemed");
+    assert_eq!(
+        b::get_holdings(past_deadline + 3, bundler)?,
+        vec![(ids.loan_token, 0), (ids.collateral_token, 0)]
+    );
+    assert_eq!(b::get_bundle(past_deadline + 3, bundler)?.share_supply, 0);
+
+    println!("Loan bundler lifecycle passed");
+    Ok(())
+}
+
+/// Test two holders with uneven shares: the first exits before anything is
+/// collected and claims its part of each loan with the exit note as the
+/// loans are collected; the second exits once everything is collected and
+/// takes the rest.
+#[wasm_bindgen_test]
+fn test_loan_bundler_early_exit() -> Result<()> {
+    let (take_block, ids) = setup_two_active_loans()?;
+    let bundler = &ids.bundler;
+    let repayment = calculate_repayment_amount(LOAN_AMOUNT, APR_500_BPS, DURATION_BLOCKS);
+    let supply = 2 * LOAN_AMOUNT;
+    let first_shares = 3 * supply / 10;
+
+    let block = b::init_bundler(&take_block, DEPLOY_HEIGHT + 5, bundler, &ids.loan_token, JOIN_BLOCKS)?;
+    let sponsor_note = b::get_bundle(DEPLOY_HEIGHT + 6, bundler)?.sponsor_note;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 7, bundler, &sponsor_note, &ids.lending_a)?;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 8, bundler, &sponsor_note, &ids.lending_b)?;
+    let terms_a = LoanTerms::default_from(&ids.lending(&ids.lending_a));
+    let block = h::repay_loan(&block, DEPLOY_HEIGHT + 10, &ids.lending_a, &terms_a)?;
+
+    // The first holder exits before any loan is collected: nothing to pay
+    // yet, but the exit note keeps its claim
+    let split_block = b::exit_bundle_split(&block, DEPLOY_HEIGHT + 11, bundler, first_shares)?;
+    let exit = b::get_exit(DEPLOY_HEIGHT + 12, bundler, 0)?;
+    assert_eq!((exit.shares, exit.next_collection, exit.settled), (first_shares, 0, false));
+    let sheet = get_last_outpoint_sheet(&split_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), 0, "Nothing collected yet");
+    assert_eq!(sheet.get(&exit.note.into()), 1, "First holder receives the exit note");
+    assert_eq!(b::get_bundle(DEPLOY_HEIGHT + 12, bundler)?.share_supply, supply - first_shares);
+
+    // Loan A is collected; the note claims its part of the repayment
+    let block = b::collect_loan(&split_block, DEPLOY_HEIGHT + 12, bundler, 0)?;
+    let block = b::claim_exit(&block, DEPLOY_HEIGHT + 13, bundler, &exit.note, 0)?;
+    let first_repayment = repayment * first_shares / supply;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), first_repayment, "First holder's part of the repayment");
+    assert_eq!(sheet.get(&exit.note.into()), 1, "Note is refunded while loan B is outstanding");
+    assert!(!b::get_exit(DEPLOY_HEIGHT + 14, bundler, 0)?.settled);
+
+    // Loan B defaults; the last claim takes its part of the collateral and
+    // settles the exit
+    let past_deadline = DEPLOY_HEIGHT + 4 + DURATION_BLOCKS as u32 + 1;
+    let block = b::collect_loan(&block, past_deadline, bundler, 1)?;
+    let block = b::claim_exit(&block, past_deadline + 1, bundler, &exit.note, 0)?;
+    let first_collateral = COLLATERAL_AMOUNT * first_shares / supply;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), first_repayment);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), first_collateral);
+    assert_eq!(sheet.get(&exit.note.into()), 0, "Note is kept once the exit is settled");
+    assert!(b::get_exit(past_deadline + 2, bundler, 0)?.settled);
+    let again_block = b::claim_exit(&block, past_deadline + 2, bundler, &exit.note, 0)?;
+    h::assert_revert(&again_block, "Exit already settled")?;
+
+    // The second holder exits after every collection and takes the rest
+    let block = b::exit_bundle_from(&split_block, b::SPLIT_LEFTOVER_VOUT, past_deadline + 3, bundler)?;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY - first_repayment);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY - first_collateral);
+    assert_eq!(sheet.get(&(*bundler).into()), 0, "All shares redeemed");
+    assert_eq!(
+        b::get_holdings(past_deadline + 4, bundler)?,
+        vec![(ids.loan_token, 0), (ids.collateral_token, 0)]
+    );
+    assert_eq!(b::get_bundle(past_deadline + 4, bundler)?.share_supply, 0);
+
+    println!("Loan bundler early exit passed");
+    Ok(())
+}
+
+/// Test exits from a bundle whose holdings times its share supply does not
+/// fit in a u128: two holders of uneven parts of a 1e30 loan each get their
+/// exact share of the repayment.
+#[wasm_bindgen_test]
+fn test_loan_bundler_large_exit() -> Result<()> {
+    let principal: u128 = 1_000_000_000_000_000_000_000_000_000_000;
+    let (deploy_block, ids) = b::deploy_bundler_with_supply(HUGE_TOKEN_SUPPLY)?;
+    let bundler = &ids.bundler;
+    let mut terms = LoanTerms::default_from(&ids.lending(&ids.lending_a));
+    terms.loan_amount = principal;
+    terms.collateral_amount = principal;
+    let repayment = calculate_repayment_amount(principal, APR_500_BPS, DURATION_BLOCKS);
+
+    let block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_a, &terms)?;
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 2, &ids.lending_a, &terms)?;
+    let block = b::init_bundler(&block, DEPLOY_HEIGHT + 3, bundler, &ids.loan_token, JOIN_BLOCKS)?;
+    let sponsor_note = b::get_bundle(DEPLOY_HEIGHT + 4, bundler)?.sponsor_note;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 4, bundler, &sponsor_note, &ids.lending_a)?;
+    let block = h::repay_loan(&block, DEPLOY_HEIGHT + 5, &ids.lending_a, &terms)?;
+    let block = b::collect_loan(&block, DEPLOY_HEIGHT + 6, bundler, 0)?;
+    assert!(repayment.checked_mul(principal).is_none(), "Payout must not fit a u128 product");
+
+    // A quarter of the shares takes exactly a quarter of the repayment
+    let split_block = b::exit_bundle_split(&block, DEPLOY_HEIGHT + 8, bundler, principal / 4)?;
+    let sheet = get_last_outpoint_sheet(&split_block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), repayment / 4);
+
+    let block = b::exit_bundle_from(&split_block, b::SPLIT_LEFTOVER_VOUT, DEPLOY_HEIGHT + 9, bundler)?;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), HUGE_TOKEN_SUPPLY - repayment / 4);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), HUGE_TOKEN_SUPPLY);
+    assert_eq!(b::get_holdings(DEPLOY_HEIGHT + 10, bundler)?, vec![(ids.loan_token, 0)]);
+
+    println!("Loan bundler large exit passed");
+    Ok(())
+}
+
+/// Test the sponsor acting as creditor of bundled loans that cannot default
+/// on their own: a loan requiring notice of default, and an open-term loan
+/// that has to be called first.
+#[wasm_bindgen_test]
+fn test_loan_bundler_notice_and_call() -> Result<()> {
+    let (deploy_block, ids) = b::deploy_bundler_with_loans()?;
+    let bundler = &ids.bundler;
+    let mut terms_a = LoanTerms::default_from(&ids.lending(&ids.lending_a));
+    terms_a.default_notice_blocks = NOTICE_BLOCKS;
+    // Once called, loan B is due within the notice period
+    let mut terms_b = LoanTerms::default_from(&ids.lending(&ids.lending_b));
+    terms_b.loan_flags = FLAG_OPEN_TERM;
+    terms_b.duration_blocks = NOTICE_BLOCKS;
+
+    let block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_a, &terms_a)?;
+    let block = h::init_loan_offer(&block, DEPLOY_HEIGHT + 2, &ids.lending_b, &terms_b)?;
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 3, &ids.lending_a, &terms_a)?;
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 4, &ids.lending_b, &terms_b)?;
+    let block = b::init_bundler(&block, DEPLOY_HEIGHT + 5, bundler, &ids.loan_token, JOIN_BLOCKS)?;
+    let sponsor_note = b::get_bundle(DEPLOY_HEIGHT + 6, bundler)?.sponsor_note;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 6, bundler, &sponsor_note, &ids.lending_a)?;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 7, bundler, &sponsor_note, &ids.lending_b)?;
+
+    // Only the sponsor calls, and only open-term loans
+    let no_sponsor = h::execute_cellpack_no_balance(
+        DEPLOY_HEIGHT + 8,
+        Cellpack {
+            target: *bundler,
+            inputs: vec![4, 1],
+        },
+    )?;
+    h::assert_revert(&no_sponsor, "Sponsor note is not in incoming alkanes")?;
+    let block = b::call_bundled_loan(&block, DEPLOY_HEIGHT + 8, bundler, &sponsor_note, 0)?;
+    h::assert_revert(&block, "Loan is not open-term")?;
+
+    // Called at DEPLOY_HEIGHT + 9, loan B defaults after DEPLOY_HEIGHT + 19
+    let block = b::call_bundled_loan(&block, DEPLOY_HEIGHT + 9, bundler, &sponsor_note, 1)?;
+    let called_deadline = DEPLOY_HEIGHT + 9 + NOTICE_BLOCKS as u32;
+    let block = b::collect_loan(&block, called_deadline + 1, bundler, 1)?;
+    assert_eq!(b::get_holdings(called_deadline + 2, bundler)?, vec![(ids.collateral_token, COLLATERAL_AMOUNT)]);
+
+    // Loan A needs notice before its collateral can be collected
+    let deadline = DEPLOY_HEIGHT + 3 + DURATION_BLOCKS as u32;
+    let block = b::collect_loan(&block, deadline + 1, bundler, 0)?;
+    h::assert_revert(&block, "Notice of default required")?;
+    let block = b::notice_bundled_loan(&block, deadline + 2, bundler, &sponsor_note, 0)?;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&sponsor_note.into()), 1, "Sponsor note is refunded");
+    assert_eq!(sheet.get(&ids.lending_a.into()), 0, "Creditor note stays with the bundle");
+
+    let block = b::collect_loan(&block, deadline + 2 + NOTICE_BLOCKS as u32, bundler, 0)?;
+    h::assert_revert(&block, "Default notice period has not elapsed")?;
+    let claimable = deadline + 3 + NOTICE_BLOCKS as u32;
+    let block = b::collect_loan(&block, claimable, bundler, 0)?;
+    assert_eq!(b::get_bundle(claimable + 1, bundler)?.collected_count, 2);
+
+    // Both collaterals go back to the wallet along with the loans it took
+    let block = b::exit_bundle(&block, claimable + 1, bundler, 2 * LOAN_AMOUNT)?;
+    let sheet = get_last_outpoint_sheet(&block)?;
+    assert_eq!(sheet.get(&ids.loan_token.into()), INIT_TOKEN_SUPPLY);
+    assert_eq!(sheet.get(&ids.collateral_token.into()), INIT_TOKEN_SUPPLY);
+
+    println!("Loan bundler notice and call passed");
+    Ok(())
+}
+
+// ============================================================================
+// Rejection Tests
+// ============================================================================
+
+/// Test that the bundler only accepts active loans in its loan token, from
+/// the sponsor, during the join window, and only releases holdings after it.
+#[wasm_bindgen_test]
+fn test_loan_bundler_rejections() -> Result<()> {
+    let (deploy_block, ids) = b::deploy_bundler_with_loans()?;
+    let bundler = &ids.bundler;
+    let terms_a = LoanTerms::default_from(&ids.lending(&ids.lending_a));
+    // Loan B lends the collateral token against the loan token
+    let mut terms_b = LoanTerms::default_from(&ids.lending(&ids.lending_b));
+    terms_b.loan_token = ids.collateral_token;
+    terms_b.collateral_token = ids.loan_token;
+
+    // Joins are accepted through DEPLOY_HEIGHT + 7
+    let block = h::init_loan_offer(&deploy_block, DEPLOY_HEIGHT + 1, &ids.lending_a, &terms_a)?;
+    let block = h::init_loan_offer(&block, DEPLOY_HEIGHT + 2, &ids.lending_b, &terms_b)?;
+    let block = b::init_bundler(&block, DEPLOY_HEIGHT + 3, bundler, &ids.loan_token, JOIN_BLOCKS)?;
+    let sponsor_note = b::get_bundle(DEPLOY_HEIGHT + 4, bundler)?.sponsor_note;
+
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 4, bundler, &sponsor_note, &ids.lending_a)?;
+    h::assert_revert(&block, "Only active loans can be bundled")?;
+
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 5, &ids.lending_a, &terms_a)?;
+    let block = h::take_loan(&block, DEPLOY_HEIGHT + 6, &ids.lending_b, &terms_b)?;
+    let block = b::join_bundle(&block, DEPLOY_HEIGHT + 7, bundler, &sponsor_note, &ids.lending_b)?;
+    h::assert_revert(&block, "Loan is not lent in the bundle's loan token")?;
+
+    let no_sponsor = h::execute_cellpack_no_balance(
+        DEPLOY_HEIGHT + 7,
+        Cellpack {
+            target: *bundler,
+            inputs: vec![1, ids.lending_a.block, ids.lending_a.tx],
+        },
+    )?;
+    h::assert_revert(&no_sponsor, "Sponsor note is not in incoming alkanes")?;
+
+    let early_block = b::exit_bundle(&block, DEPLOY_HEIGHT + 7, bundler, 1)?;
+    h::assert_revert(&early_block, "Join window is still open")?;
+
+    let block = b::join_bundle(&early_block, DEPLOY_HEIGHT + 8, bundler, &sponsor_note, &ids.lending_a)?;
+    h::assert_revert(&block, "Join window has closed")?;
+
+    let block = b::collect_loan(&block, DEPLOY_HEIGHT + 9, bundler, 0)?;
+    h::assert_revert(&block, "No bundled loan at index 0")?;
+
+    let no_shares = h::execute_cellpack_no_balance(
+        DEPLOY_HEIGHT + 9,
+        Cellpack {
+            target: *bundler,
+            inputs: vec![3],
+        },
+    )?;
+    h::assert_revert(&no_shares, "No bundle shares sent")?;
+
+    let bundle = b::get_bundle(DEPLOY_HEIGHT + 10, bundler)?;
+    assert_eq!(bundle.loan_count, 0, "No loan was bundled");
+    assert_eq!(bundle.share_supply, 0);
+
+    println!("Loan bundler rejections passed");
+    Ok(())
+}
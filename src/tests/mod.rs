@@ -1,4 +1,8 @@
 pub mod helper;
 pub mod lending;
 pub mod std;
-pub mod lending_attack;
\ No newline at end of file
+pub mod lending_attack;
+pub mod lending_simulation;
+pub mod lending_golden;
+pub mod loan_bundler;
+pub mod lending_stress;
\ No newline at end of file